@@ -0,0 +1,459 @@
+//! Adapter implementing the `futures` I/O traits for a pipe.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::{ffi, D3xxError, Device, Pipe, SyncDevice};
+
+/// An overlapped transfer on a pipe of a [`SyncDevice`], owning its buffer.
+///
+/// The device lock is only held for each driver call, so other threads may use the
+/// device while the transfer is in progress. The clone of the device keeps it open until
+/// the transfer has been released.
+struct Transfer {
+    device: SyncDevice,
+    id: Pipe,
+    /// Data to write, or the buffer being read into.
+    buf: Vec<u8>,
+    /// Boxed so its address stays fixed while the driver uses it.
+    overlapped: Box<ffi::_OVERLAPPED>,
+    /// Whether the transfer may still be in progress.
+    pending: bool,
+}
+
+// SAFETY: the overlapped structure is owned by the transfer and is only passed to the
+// driver while holding the device lock, like the `Device` itself.
+unsafe impl Send for Transfer {}
+
+impl Transfer {
+    /// Start reading up to `len` bytes from the pipe.
+    fn read(device: &SyncDevice, id: Pipe, len: usize) -> crate::Result<Self> {
+        Self::start(device, id, vec![0; len], true)
+    }
+
+    /// Start writing `data` to the pipe.
+    fn write(device: &SyncDevice, id: Pipe, data: &[u8]) -> crate::Result<Self> {
+        Self::start(device, id, data.to_vec(), false)
+    }
+
+    fn start(device: &SyncDevice, id: Pipe, mut buf: Vec<u8>, read: bool) -> crate::Result<Self> {
+        let overlapped = device.with(|d| {
+            let mut overlapped = Box::new(d.api().initialize_overlapped(d.ffi_handle())?);
+            let res = if read {
+                d.api()
+                    .read_pipe_async(d.ffi_handle(), u8::from(id), &mut buf, &mut overlapped)
+            } else {
+                d.api()
+                    .write_pipe_async(d.ffi_handle(), u8::from(id), &buf, &mut overlapped)
+            };
+            if let Err(e) = d.pipe(id).maybe_abort(res) {
+                let _ = d.api().release_overlapped(d.ffi_handle(), &mut overlapped);
+                return Err(e);
+            }
+            Ok(overlapped)
+        })?;
+        Ok(Self {
+            device: device.clone(),
+            id,
+            buf,
+            overlapped,
+            pending: true,
+        })
+    }
+
+    /// Check whether the transfer has completed, returning the number of bytes
+    /// transferred. The pipe is aborted if the transfer failed.
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<crate::Result<usize>> {
+        let device = self.device.clone();
+        let result = device.with(|d| {
+            let result = d
+                .api()
+                .overlapped_result(d.ffi_handle(), &mut self.overlapped, false);
+            if matches!(result, Err(D3xxError::IoPending | D3xxError::IoIncomplete)) {
+                return None;
+            }
+            self.pending = false;
+            Some(d.pipe(self.id).maybe_abort(result))
+        });
+        result.map_or_else(
+            || {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            },
+            Poll::Ready,
+        )
+    }
+
+    /// Abort the transfer if it may still be in progress, then release it.
+    fn release(&mut self, device: &Device) {
+        if self.pending {
+            let _ = device
+                .api()
+                .abort_pipe(device.ffi_handle(), u8::from(self.id));
+            let _ = device
+                .api()
+                .overlapped_result(device.ffi_handle(), &mut self.overlapped, true);
+        }
+        let _ = device
+            .api()
+            .release_overlapped(device.ffi_handle(), &mut self.overlapped);
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        let device = self.device.clone();
+        device.with(|d| self.release(d));
+    }
+}
+
+/// A pipe implementing [`AsyncRead`] and [`AsyncWrite`] from the `futures` crate.
+///
+/// The pipe belongs to a [`SyncDevice`], so an `AsyncPipe` is `Send` and may be used on
+/// a multi-threaded executor. The device lock is only held for each driver call, not
+/// while waiting for a transfer to complete.
+///
+/// Since the buffers passed to `poll_read` and `poll_write` are only borrowed for a
+/// single poll, each transfer uses a buffer owned by the adapter instead:
+///
+/// - A read fills an internal buffer the size of the caller's buffer. The data is copied
+///   out once the transfer completes; if the caller's buffer has since shrunk, the rest is
+///   returned by the following reads.
+/// - A write copies the caller's data and starts the transfer, and then reports the data
+///   as written. Only one write is in progress at a time: the next `poll_write` waits for
+///   it to complete, and returns its error if it failed. Use `poll_flush` to wait for the
+///   last write.
+///
+/// Dropping the adapter while a transfer is pending aborts the pipe.
+///
+/// This type requires the `futures` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// use d3xx::{Device, Pipe, SyncDevice};
+/// use futures::io::AsyncWriteExt;
+///
+/// # async fn example() -> std::io::Result<()> {
+/// let device = SyncDevice::new(Device::open("ABC123")?);
+/// let mut pipe = device.async_pipe(Pipe::Out0);
+/// pipe.write_all(b"hello").await?;
+/// pipe.flush().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncPipe {
+    device: SyncDevice,
+    id: Pipe,
+    /// Read in progress.
+    read: Option<Transfer>,
+    /// Data from a completed read which did not fit in the caller's buffer.
+    leftover: Vec<u8>,
+    /// Write in progress, which has already been reported as written.
+    write: Option<Transfer>,
+}
+
+impl AsyncPipe {
+    /// Create a new `AsyncPipe` for the given device and pipe ID.
+    #[must_use]
+    pub fn new(device: SyncDevice, id: Pipe) -> Self {
+        Self {
+            device,
+            id,
+            read: None,
+            leftover: Vec::new(),
+            write: None,
+        }
+    }
+
+    /// Get the pipe ID.
+    #[must_use]
+    pub fn id(&self) -> Pipe {
+        self.id
+    }
+
+    /// Get the device the pipe belongs to.
+    #[must_use]
+    pub fn device(&self) -> &SyncDevice {
+        &self.device
+    }
+
+    /// Drive the write in progress, if any, to completion.
+    ///
+    /// A write which transferred fewer bytes than it was given fails with
+    /// [`io::ErrorKind::WriteZero`], since it has already been reported as written.
+    fn poll_write_transfer(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(transfer) = self.write.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+        let result = std::task::ready!(transfer.poll(cx));
+        let len = transfer.buf.len();
+        self.write = None;
+        match result {
+            Ok(n) if n < len => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                format!("pipe accepted {n} of {len} bytes"),
+            ))),
+            Ok(_) => Poll::Ready(Ok(())),
+            Err(e) => Poll::Ready(Err(e.into())),
+        }
+    }
+}
+
+impl std::fmt::Debug for AsyncPipe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncPipe")
+            .field("device", &self.device)
+            .field("id", &self.id)
+            .field("reading", &self.read.is_some())
+            .field("writing", &self.write.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SyncDevice {
+    /// Create an [`AsyncPipe`] for the given pipe, implementing the `futures` I/O traits.
+    ///
+    /// This requires the `futures` feature.
+    #[must_use]
+    pub fn async_pipe(&self, pipe: Pipe) -> AsyncPipe {
+        AsyncPipe::new(self.clone(), pipe)
+    }
+}
+
+impl AsyncRead for AsyncPipe {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.read.is_none() {
+            if !this.leftover.is_empty() || buf.is_empty() {
+                let n = buf.len().min(this.leftover.len());
+                buf[..n].copy_from_slice(&this.leftover[..n]);
+                this.leftover.drain(..n);
+                return Poll::Ready(Ok(n));
+            }
+            this.read = Some(Transfer::read(&this.device, this.id, buf.len())?);
+        }
+        let transfer = this.read.as_mut().expect("read in progress");
+        let result = std::task::ready!(transfer.poll(cx));
+        let mut data = std::mem::take(&mut transfer.buf);
+        this.read = None;
+        data.truncate(result?);
+        let n = buf.len().min(data.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        data.drain(..n);
+        this.leftover = data;
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for AsyncPipe {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        std::task::ready!(this.poll_write_transfer(cx))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        this.write = Some(Transfer::write(&this.device, this.id, buf)?);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_transfer(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        task::{Wake, Waker},
+    };
+
+    use super::*;
+    use crate::mock::{self, Call, MockApi};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn poll<T>(f: impl FnOnce(&mut Context<'_>) -> Poll<T>) -> Poll<T> {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        f(&mut Context::from_waker(&waker))
+    }
+
+    fn assert_traits<T: AsyncRead + AsyncWrite + Unpin + Send>() {}
+
+    fn async_pipe(api: &Arc<MockApi>, id: Pipe) -> AsyncPipe {
+        SyncDevice::new(mock::device(api)).async_pipe(id)
+    }
+
+    #[test]
+    fn implements_traits() {
+        assert_traits::<AsyncPipe>();
+    }
+
+    #[test]
+    fn read_keeps_leftover() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abcdef");
+        // The transfer is still pending when first polled.
+        api.fail_once_when(|c| *c == Call::OverlappedResult, D3xxError::IoPending);
+        let mut pipe = async_pipe(&api, Pipe::In0);
+        let mut buf = [0; 8];
+        assert!(poll(|cx| Pin::new(&mut pipe).poll_read(cx, &mut buf)).is_pending());
+        // The caller's buffer shrinks before the transfer completes.
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_read(cx, &mut buf[..4])),
+            Poll::Ready(Ok(4))
+        ));
+        assert_eq!(&buf[..4], b"abcd");
+        // The remaining data is returned without another transfer.
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_read(cx, &mut buf)),
+            Poll::Ready(Ok(2))
+        ));
+        assert_eq!(&buf[..2], b"ef");
+        assert_eq!(
+            api.calls()
+                .iter()
+                .filter(|c| matches!(c, Call::ReadPipeAsync { .. }))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn write_and_flush() {
+        let api = Arc::new(MockApi::default());
+        let mut pipe = async_pipe(&api, Pipe::Out1);
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_write(cx, b"hello")),
+            Poll::Ready(Ok(5))
+        ));
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_write(cx, b"")),
+            Poll::Ready(Ok(0))
+        ));
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_close(cx)),
+            Poll::Ready(Ok(()))
+        ));
+        assert_eq!(api.writes(0x03), [b"hello".to_vec()]);
+    }
+
+    #[test]
+    fn write_reports_current_buffer() {
+        let api = Arc::new(MockApi::default());
+        // The first write is still pending when the second is submitted.
+        api.fail_once_when(|c| *c == Call::OverlappedResult, D3xxError::IoPending);
+        let mut pipe = async_pipe(&api, Pipe::Out0);
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_write(cx, b"hello")),
+            Poll::Ready(Ok(5))
+        ));
+        assert!(poll(|cx| Pin::new(&mut pipe).poll_write(cx, b"ab")).is_pending());
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_write(cx, b"ab")),
+            Poll::Ready(Ok(2))
+        ));
+        // A write completed by a flush is not reported again.
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_flush(cx)),
+            Poll::Ready(Ok(()))
+        ));
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_write(cx, b"xyz")),
+            Poll::Ready(Ok(3))
+        ));
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_flush(cx)),
+            Poll::Ready(Ok(()))
+        ));
+        assert_eq!(
+            api.writes(0x02),
+            [b"hello".to_vec(), b"ab".to_vec(), b"xyz".to_vec()]
+        );
+    }
+
+    #[test]
+    fn write_error_surfaces_on_flush() {
+        let api = Arc::new(MockApi::default());
+        api.fail_once_when(|c| *c == Call::OverlappedResult, D3xxError::IoError);
+        let mut pipe = async_pipe(&api, Pipe::Out0);
+        assert!(matches!(
+            poll(|cx| Pin::new(&mut pipe).poll_write(cx, b"hello")),
+            Poll::Ready(Ok(5))
+        ));
+        let Poll::Ready(Err(e)) = poll(|cx| Pin::new(&mut pipe).poll_flush(cx)) else {
+            panic!("expected an error");
+        };
+        assert_eq!(e.kind(), io::Error::from(D3xxError::IoError).kind());
+        assert!(api.calls().contains(&Call::AbortPipe { pipe: 0x02 }));
+    }
+
+    #[test]
+    fn drop_while_pending_aborts_before_release() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"data");
+        api.fail_once_when(|c| *c == Call::OverlappedResult, D3xxError::IoPending);
+        let mut pipe = async_pipe(&api, Pipe::In0);
+        let mut buf = [0; 4];
+        assert!(poll(|cx| Pin::new(&mut pipe).poll_read(cx, &mut buf)).is_pending());
+        let calls = api.calls().len();
+        drop(pipe);
+        // The transfer is released before the device is closed.
+        let calls = api.calls()[calls..].to_vec();
+        assert_eq!(
+            calls[..3],
+            [
+                Call::AbortPipe { pipe: 0x82 },
+                Call::OverlappedResult,
+                Call::ReleaseOverlapped,
+            ]
+        );
+        assert_eq!(calls.last(), Some(&Call::Close));
+    }
+
+    #[test]
+    fn read_error() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |c| matches!(c, Call::ReadPipeAsync { .. }),
+            D3xxError::IoError,
+        );
+        let mut pipe = async_pipe(&api, Pipe::In0);
+        let mut buf = [0; 4];
+        let Poll::Ready(Err(e)) = poll(|cx| Pin::new(&mut pipe).poll_read(cx, &mut buf)) else {
+            panic!("expected an error");
+        };
+        assert_eq!(e.kind(), io::Error::from(D3xxError::IoError).kind());
+        assert_eq!(
+            api.calls(),
+            [
+                Call::InitializeOverlapped,
+                Call::ReadPipeAsync { pipe: 0x82, len: 4 },
+                Call::AbortPipe { pipe: 0x82 },
+                Call::ReleaseOverlapped,
+            ]
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Opening a device with its pipes configured in one step.
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    ffi::api::{D3xxApi, Ffi},
+    AbortPolicy, Device, OpenBy, Pipe, Result,
+};
+
+/// Builder for opening a [`Device`] and configuring it before use.
+///
+/// The device is opened with `FT_Create` when [`open`](Self::open) is called, and each
+/// setting is then applied in turn. If any setting fails, the device is closed and the
+/// error is returned, so a device is only ever handed out fully configured.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use d3xx::{DeviceBuilder, Pipe};
+///
+/// let device = DeviceBuilder::serial_number("ABC123")
+///     .timeout(Duration::from_secs(1))
+///     .stream_size(Pipe::In0, 4096)
+///     .open()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct DeviceBuilder {
+    open_by: OpenBy,
+    timeout: Option<Duration>,
+    stream_sizes: Vec<(Pipe, usize)>,
+    /// Suspend timeout in milliseconds, where zero disables selective suspend.
+    suspend_timeout: Option<u32>,
+    abort_policy: AbortPolicy,
+}
+
+impl DeviceBuilder {
+    fn new(open_by: OpenBy) -> Self {
+        Self {
+            open_by,
+            timeout: None,
+            stream_sizes: Vec::new(),
+            suspend_timeout: None,
+            abort_policy: AbortPolicy::default(),
+        }
+    }
+
+    /// Open the device with the given serial number.
+    pub fn serial_number(serial_number: &str) -> Self {
+        Self::new(OpenBy::Serial(serial_number.to_owned()))
+    }
+
+    /// Open the device with the given description (product string).
+    pub fn description(description: &str) -> Self {
+        Self::new(OpenBy::Description(description.to_owned()))
+    }
+
+    /// Open the device at the given index in the driver's device list.
+    ///
+    /// The index corresponds to the position of the device in the list returned by
+    /// [`list_devices`](crate::list_devices).
+    pub fn index(index: usize) -> Self {
+        Self::new(OpenBy::Index(index))
+    }
+
+    /// Set the timeout for every pipe.
+    ///
+    /// See [`Device::set_all_pipe_timeouts`] for how the duration is converted.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Configure a pipe for streaming with the given size.
+    ///
+    /// See [`PipeIo::set_stream_size`](crate::PipeIo::set_stream_size). Setting the size
+    /// for the same pipe again replaces the earlier value.
+    pub fn stream_size(mut self, pipe: Pipe, size: usize) -> Self {
+        self.stream_sizes.retain(|&(p, _)| p != pipe);
+        self.stream_sizes.push((pipe, size));
+        self
+    }
+
+    /// Set the USB selective suspend timeout.
+    ///
+    /// See [`Device::set_suspend_timeout`]. This is only supported on Windows; elsewhere
+    /// [`open`](Self::open) fails with
+    /// [`D3xxError::NotSupported`](crate::D3xxError::NotSupported).
+    pub fn suspend_timeout(mut self, timeout: Option<u32>) -> Self {
+        self.suspend_timeout = Some(timeout.unwrap_or(0));
+        self
+    }
+
+    /// Set which transfer errors abort the pipe they occurred on.
+    ///
+    /// See [`Device::set_abort_policy`].
+    pub fn abort_policy(mut self, policy: AbortPolicy) -> Self {
+        self.abort_policy = policy;
+        self
+    }
+
+    /// Open the device and apply the configured settings.
+    ///
+    /// Fails with [`D3xxError::InvalidArgs`](crate::D3xxError::InvalidArgs) if the serial number or description
+    /// contains a null byte.
+    pub fn open(&self) -> Result<Device> {
+        self.open_with(Arc::new(Ffi))
+    }
+
+    /// Open the device using the given driver API.
+    pub(crate) fn open_with(&self, api: Arc<dyn D3xxApi>) -> Result<Device> {
+        let mut device = Device::open_by(api, self.open_by.clone())?;
+        device.set_abort_policy(self.abort_policy);
+        match self.configure(&device) {
+            Ok(()) => Ok(device),
+            Err(e) => {
+                let _ = device.close();
+                Err(e)
+            }
+        }
+    }
+
+    /// Apply the settings to a newly opened device.
+    fn configure(&self, device: &Device) -> Result<()> {
+        if let Some(timeout) = self.timeout {
+            device.set_all_pipe_timeouts(timeout)?;
+        }
+        for &(pipe, size) in &self.stream_sizes {
+            device.pipe(pipe).set_stream_size(Some(size))?;
+        }
+        if let Some(timeout) = self.suspend_timeout {
+            device.set_suspend_timeout(Some(timeout))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ffi,
+        mock::{Call, MockApi},
+        D3xxError, StreamPipeTarget,
+    };
+
+    #[test]
+    fn settings_are_applied() {
+        let api = Arc::new(MockApi::default());
+        let device = DeviceBuilder::description("FT601")
+            .timeout(Duration::from_micros(249_500))
+            .stream_size(Pipe::In1, 1024)
+            .stream_size(Pipe::In1, 4096)
+            .suspend_timeout(None)
+            .open_with(api.clone())
+            .unwrap();
+        let mut expected = vec![Call::Create {
+            flags: ffi::FT_OPEN_BY_DESCRIPTION,
+        }];
+        expected.extend(Pipe::all().map(|pipe| Call::SetPipeTimeout {
+            pipe: pipe.into(),
+            timeout_ms: 250,
+        }));
+        expected.push(Call::SetStreamPipe {
+            pipe: 0x83,
+            target: StreamPipeTarget::ThisPipe,
+            size: Some(4096),
+        });
+        expected.push(Call::SetSuspendTimeout { timeout_ms: 0 });
+        assert_eq!(api.calls(), expected);
+        drop(device);
+    }
+
+    #[test]
+    fn abort_policy() {
+        let api = Arc::new(MockApi::default());
+        let device = DeviceBuilder::index(0)
+            .abort_policy(AbortPolicy::ExceptTimeout)
+            .open_with(api.clone())
+            .unwrap();
+        assert_eq!(device.abort_policy(), AbortPolicy::ExceptTimeout);
+        assert_eq!(
+            device.pipe(Pipe::In0).abort_policy(),
+            AbortPolicy::ExceptTimeout
+        );
+    }
+
+    #[test]
+    fn failure_closes_device() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| matches!(call, Call::SetStreamPipe { .. }),
+            D3xxError::InvalidParameter,
+        );
+        let result = DeviceBuilder::index(0)
+            .stream_size(Pipe::Out0, 512)
+            .open_with(api.clone());
+        assert_eq!(result.err(), Some(D3xxError::InvalidParameter));
+        let calls = api.calls();
+        assert_eq!(
+            calls[0],
+            Call::Create {
+                flags: ffi::FT_OPEN_BY_INDEX
+            }
+        );
+        assert_eq!(calls.last(), Some(&Call::Close));
+    }
+
+    #[test]
+    fn timeout_attempts_every_pipe() {
+        let api = Arc::new(MockApi::default());
+        api.fail_once_when(
+            |call| matches!(call, Call::SetPipeTimeout { pipe: 0x02, .. }),
+            D3xxError::InvalidParameter,
+        );
+        let result = DeviceBuilder::index(0)
+            .timeout(Duration::from_millis(100))
+            .open_with(api.clone());
+        assert_eq!(result.err(), Some(D3xxError::InvalidParameter));
+        let calls = api.calls();
+        let timeouts = calls
+            .iter()
+            .filter(|call| matches!(call, Call::SetPipeTimeout { .. }))
+            .count();
+        assert_eq!(timeouts, 8);
+        assert_eq!(calls.last(), Some(&Call::Close));
+    }
+
+    #[test]
+    fn invalid_serial_number() {
+        let api = Arc::new(MockApi::default());
+        let result = DeviceBuilder::serial_number("A\0B").open_with(api.clone());
+        assert_eq!(result.err(), Some(D3xxError::InvalidArgs));
+        assert!(api.calls().is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-use crate::{D3xxError, Result};
+use crate::{D3xxError, Pipe, Result};
 
 /// Configuration regarding data transfer.
 ///
@@ -10,6 +10,7 @@ use crate::{D3xxError, Result};
 /// - FIFO clock speed
 /// - FIFO mode
 /// - Channel configuration
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct DataTransferConfig {
     fifo_clock: FifoClock,
     fifo_mode: FifoMode,
@@ -26,6 +27,15 @@ impl DataTransferConfig {
         })
     }
 
+    /// Raw `FIFOClock`, `FIFOMode`, and `ChannelConfig` values.
+    pub(crate) fn raw(&self) -> (u8, u8, u8) {
+        (
+            self.fifo_clock.into(),
+            self.fifo_mode.into(),
+            self.channel_config.into(),
+        )
+    }
+
     /// Get the FIFO clock speed.
     #[must_use]
     pub fn fifo_clock(&self) -> &FifoClock {
@@ -59,6 +69,20 @@ pub enum FifoMode {
     Mode600,
 }
 
+impl FifoMode {
+    /// Check if this is the 245 FIFO mode.
+    #[must_use]
+    pub const fn is_245(self) -> bool {
+        matches!(self, Self::Mode245)
+    }
+
+    /// Check if this is the 600 FIFO mode.
+    #[must_use]
+    pub const fn is_600(self) -> bool {
+        matches!(self, Self::Mode600)
+    }
+}
+
 /// Clock speed of the FIFOs.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
@@ -69,6 +93,17 @@ pub enum FifoClock {
     Clock66Mhz,
 }
 
+impl FifoClock {
+    /// Get the clock speed in MHz.
+    #[must_use]
+    pub const fn mhz(self) -> u32 {
+        match self {
+            Self::Clock100Mhz => 100,
+            Self::Clock66Mhz => 66,
+        }
+    }
+}
+
 /// Channel configuration.
 ///
 /// A "channel" is a pair of pipes. Each channel has one IN pipe and one OUT pipe,
@@ -87,3 +122,71 @@ pub enum ChannelConfiguration {
     /// One IN pipe only.
     OneInPipe,
 }
+
+impl ChannelConfiguration {
+    /// Input pipes which are available under this configuration.
+    #[must_use]
+    pub const fn in_pipes(self) -> &'static [Pipe] {
+        match self {
+            Self::Four => &[Pipe::In0, Pipe::In1, Pipe::In2, Pipe::In3],
+            Self::Two => &[Pipe::In0, Pipe::In1],
+            Self::One | Self::OneInPipe => &[Pipe::In0],
+            Self::OneOutPipe => &[],
+        }
+    }
+
+    /// Output pipes which are available under this configuration.
+    #[must_use]
+    pub const fn out_pipes(self) -> &'static [Pipe] {
+        match self {
+            Self::Four => &[Pipe::Out0, Pipe::Out1, Pipe::Out2, Pipe::Out3],
+            Self::Two => &[Pipe::Out0, Pipe::Out1],
+            Self::One | Self::OneOutPipe => &[Pipe::Out0],
+            Self::OneInPipe => &[],
+        }
+    }
+
+    /// Total number of pipes available under this configuration.
+    #[must_use]
+    pub const fn pipe_count(self) -> usize {
+        self.in_pipes().len() + self.out_pipes().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifo_clock_mhz() {
+        assert_eq!(FifoClock::try_from(0).unwrap().mhz(), 100);
+        assert_eq!(FifoClock::try_from(1).unwrap().mhz(), 66);
+    }
+
+    #[test]
+    fn fifo_mode_checks() {
+        assert!(FifoMode::Mode245.is_245());
+        assert!(!FifoMode::Mode245.is_600());
+        assert!(FifoMode::Mode600.is_600());
+        assert!(!FifoMode::Mode600.is_245());
+    }
+
+    #[test]
+    fn channel_config_pipes() {
+        use ChannelConfiguration::{Four, One, OneInPipe, OneOutPipe, Two};
+
+        assert_eq!(Four.in_pipes(), Pipe::inputs());
+        assert_eq!(Four.out_pipes(), Pipe::outputs());
+        assert_eq!(Two.in_pipes(), [Pipe::In0, Pipe::In1]);
+        assert_eq!(Two.out_pipes(), [Pipe::Out0, Pipe::Out1]);
+        assert_eq!(One.in_pipes(), [Pipe::In0]);
+        assert_eq!(One.out_pipes(), [Pipe::Out0]);
+        assert_eq!(OneInPipe.in_pipes(), [Pipe::In0]);
+        assert!(OneInPipe.out_pipes().is_empty());
+        assert!(OneOutPipe.in_pipes().is_empty());
+        assert_eq!(OneOutPipe.out_pipes(), [Pipe::Out0]);
+
+        let counts = [Four, Two, One, OneInPipe, OneOutPipe].map(ChannelConfiguration::pipe_count);
+        assert_eq!(counts, [8, 4, 2, 1, 1]);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt::Display;
+
+use super::ChipConfiguration;
+
+/// A field which differs between two [`ChipConfiguration`]s.
+///
+/// Returned by [`ChipConfiguration::diff`]. Both values are formatted as strings so
+/// that differences in fields of any type can be reported together.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConfigDiff {
+    field: &'static str,
+    left: String,
+    right: String,
+}
+
+impl ConfigDiff {
+    /// Name of the differing field, e.g. `"fifo_clock"`.
+    #[must_use]
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+
+    /// Value of the field in the configuration `diff` was called on.
+    #[must_use]
+    pub fn left(&self) -> &str {
+        &self.left
+    }
+
+    /// Value of the field in the configuration passed to `diff`.
+    #[must_use]
+    pub fn right(&self) -> &str {
+        &self.right
+    }
+}
+
+impl Display for ConfigDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} != {}", self.field, self.left, self.right)
+    }
+}
+
+impl ChipConfiguration {
+    /// Compare against another configuration, returning the fields which differ.
+    ///
+    /// Fields are compared using their public accessors and reported in a fixed order.
+    /// Fields which are not exposed, such as `FlashEEPROMDetection`, are not compared.
+    /// An empty result means the configurations are equivalent.
+    #[must_use]
+    pub fn diff(&self, other: &ChipConfiguration) -> Vec<ConfigDiff> {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .filter(|((_, left), (_, right))| left != right)
+            .map(|((field, left), (_, right))| ConfigDiff { field, left, right })
+            .collect()
+    }
+
+    /// Name and formatted value of each field compared by [`diff`](Self::diff).
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let strings = self.string_descriptor();
+        let power = self.power_config();
+        let drive = self.pin_drive_strengths();
+        let transfer = self.data_transfer();
+        let optional = self.optional_features();
+        vec![
+            ("vendor_id", format!("{:#06x}", self.vendor_id())),
+            ("product_id", format!("{:#06x}", self.product_id())),
+            ("manufacturer", format!("{:?}", strings.manufacturer())),
+            ("product", format!("{:?}", strings.product())),
+            ("serial_number", format!("{:?}", strings.serial_number())),
+            ("self_powered", power.self_powered().to_string()),
+            ("remote_wakeup", power.remote_wakeup().to_string()),
+            ("max_power", power.max_power().to_string()),
+            ("fifo_data_drive", format!("{:?}", drive.fifo_data())),
+            ("fifo_clock_drive", format!("{:?}", drive.fifo_clock())),
+            ("gpio0_drive", format!("{:?}", drive.gpio0())),
+            ("gpio1_drive", format!("{:?}", drive.gpio1())),
+            ("interrupt_latency", self.interrupt_latency().to_string()),
+            ("fifo_clock", format!("{:?}", transfer.fifo_clock())),
+            ("fifo_mode", format!("{:?}", transfer.fifo_mode())),
+            ("channel_config", format!("{:?}", transfer.channel_config())),
+            ("optional_features", format!("{:#06x}", optional.flags())),
+            (
+                "battery_charging",
+                optional
+                    .battery_flags()
+                    .map_or_else(|| "disabled".to_owned(), |flags| format!("{flags:#04x}")),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi;
+
+    fn raw() -> ffi::FT_60XCONFIGURATION {
+        let mut raw: ffi::FT_60XCONFIGURATION = unsafe { std::mem::zeroed() };
+        raw.VendorID = 0x0403;
+        raw.ProductID = 0x601F;
+        raw.bInterval = 9;
+        raw
+    }
+
+    fn config() -> ChipConfiguration {
+        ChipConfiguration::new(raw()).unwrap()
+    }
+
+    #[test]
+    fn identical() {
+        assert!(config().diff(&config()).is_empty());
+    }
+
+    #[test]
+    fn single_field() {
+        let golden = config();
+        let mut other = config();
+        other.set_interrupt_latency(4);
+        let diffs = golden.diff(&other);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].field(), "interrupt_latency");
+        assert_eq!(diffs[0].left(), "9");
+        assert_eq!(diffs[0].right(), "4");
+        assert_eq!(diffs[0].to_string(), "interrupt_latency: 9 != 4");
+    }
+
+    #[test]
+    fn multiple_fields_in_order() {
+        let golden = config();
+        let mut raw = raw();
+        raw.FIFOClock = 1;
+        raw.OptionalFeatureSupport = 0x0001;
+        raw.BatteryChargingGPIOConfig = 0xE4;
+        let mut other = ChipConfiguration::new(raw).unwrap();
+        other.string_descriptor_mut().set_product("Widget");
+        let diffs = golden.diff(&other);
+        let fields: Vec<_> = diffs.iter().map(ConfigDiff::field).collect();
+        assert_eq!(
+            fields,
+            [
+                "product",
+                "fifo_clock",
+                "optional_features",
+                "battery_charging"
+            ]
+        );
+        assert_eq!(diffs[3].left(), "disabled");
+        assert_eq!(diffs[3].right(), "0xe4");
+    }
+}
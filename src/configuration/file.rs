@@ -0,0 +1,269 @@
+//! Reading and writing chip configurations as binary images.
+
+use std::path::Path;
+
+use super::ChipConfiguration;
+use crate::{ffi, D3xxError, Result};
+
+/// Size in bytes of a configuration image.
+///
+/// This is the size of the `FT_60XCONFIGURATION` structure defined by the D3XX headers.
+/// See [`ChipConfiguration::from_bytes`] for the layout.
+pub const CHIP_CONFIGURATION_SIZE: usize = 152;
+
+impl ChipConfiguration {
+    /// Parse a configuration from a binary image.
+    ///
+    /// The image is the 152-byte `FT_60XCONFIGURATION` structure stored on the chip, with
+    /// multi-byte fields in little-endian order, as written by `FT_SetChipConfiguration`.
+    /// FTDI does not document the file format of the
+    /// [FT60X Chip Configuration Programmer](https://ftdichip.com/utilities/), so files it
+    /// exports may need converting to this layout first.
+    ///
+    /// Fails with [`D3xxError::InvalidArgs`] if the image is truncated or has trailing
+    /// bytes, and with the same errors as [`Device::chip_configuration`](crate::Device::chip_configuration)
+    /// if a field holds an invalid value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::new(decode(bytes)?)
+    }
+
+    /// Read and parse a configuration file.
+    ///
+    /// See [`from_bytes`](Self::from_bytes) for the expected format. Parsing errors are
+    /// converted into an [`std::io::Error`].
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Ok(Self::from_bytes(&bytes)?)
+    }
+
+    /// Convert the configuration into a binary image.
+    ///
+    /// This is the inverse of [`from_bytes`](Self::from_bytes), and produces the same
+    /// structure as [`to_ffi`](Self::to_ffi).
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode(&self.to_ffi()).to_vec()
+    }
+
+    /// Write the configuration to a file as a binary image.
+    ///
+    /// The file can be read back with [`from_file`](Self::from_file). See
+    /// [`from_bytes`](Self::from_bytes) for the format.
+    pub fn to_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+}
+
+/// Decode a little-endian configuration image.
+fn decode(bytes: &[u8]) -> Result<ffi::FT_60XCONFIGURATION> {
+    if bytes.len() != CHIP_CONFIGURATION_SIZE {
+        return Err(D3xxError::InvalidArgs);
+    }
+    let mut reader = Reader(bytes);
+    Ok(ffi::FT_60XCONFIGURATION {
+        VendorID: reader.u16(),
+        ProductID: reader.u16(),
+        StringDescriptors: reader.array(),
+        bInterval: reader.u8(),
+        PowerAttributes: reader.u8(),
+        PowerConsumption: reader.u16(),
+        Reserved2: reader.u8(),
+        FIFOClock: reader.u8(),
+        FIFOMode: reader.u8(),
+        ChannelConfig: reader.u8(),
+        OptionalFeatureSupport: reader.u16(),
+        BatteryChargingGPIOConfig: reader.u8(),
+        FlashEEPROMDetection: reader.u8(),
+        MSIO_Control: reader.u32(),
+        GPIO_Control: reader.u32(),
+    })
+}
+
+/// Encode a configuration as a little-endian image.
+pub(crate) fn encode(config: &ffi::FT_60XCONFIGURATION) -> [u8; CHIP_CONFIGURATION_SIZE] {
+    let mut bytes = [0; CHIP_CONFIGURATION_SIZE];
+    let mut writer = Writer(&mut bytes);
+    writer.put(&config.VendorID.to_le_bytes());
+    writer.put(&config.ProductID.to_le_bytes());
+    writer.put(&config.StringDescriptors);
+    writer.put(&[config.bInterval, config.PowerAttributes]);
+    writer.put(&config.PowerConsumption.to_le_bytes());
+    writer.put(&[
+        config.Reserved2,
+        config.FIFOClock,
+        config.FIFOMode,
+        config.ChannelConfig,
+    ]);
+    writer.put(&config.OptionalFeatureSupport.to_le_bytes());
+    writer.put(&[
+        config.BatteryChargingGPIOConfig,
+        config.FlashEEPROMDetection,
+    ]);
+    writer.put(&config.MSIO_Control.to_le_bytes());
+    writer.put(&config.GPIO_Control.to_le_bytes());
+    bytes
+}
+
+/// Reads fields from the front of an image whose length has already been checked.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn array<const N: usize>(&mut self) -> [u8; N] {
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        // unwrap(): `head` has exactly `N` bytes.
+        head.try_into().unwrap()
+    }
+
+    fn u8(&mut self) -> u8 {
+        u8::from_le_bytes(self.array())
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.array())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.array())
+    }
+}
+
+/// Writes fields to the front of an image.
+struct Writer<'a>(&'a mut [u8]);
+
+impl Writer<'_> {
+    fn put(&mut self, src: &[u8]) {
+        let (head, tail) = std::mem::take(&mut self.0).split_at_mut(src.len());
+        head.copy_from_slice(src);
+        self.0 = tail;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mock::{self, Call, MockApi};
+
+    /// A configuration image with valid string descriptors and recognizable values.
+    fn image() -> Vec<u8> {
+        let mut strings = [0u8; 128];
+        let mut offset = 0;
+        for part in ["FTDI", "FT601", "000000000001"] {
+            let bytes: Vec<u8> = part.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            strings[offset] = u8::try_from(bytes.len() + 2).unwrap();
+            strings[offset + 1] = 0x03;
+            strings[offset + 2..][..bytes.len()].copy_from_slice(&bytes);
+            offset += bytes.len() + 2;
+        }
+        let mut bytes = vec![0x03, 0x04, 0x1F, 0x60];
+        bytes.extend(strings);
+        bytes.extend([9, 0xE0, 0x60, 0x00, 0xAA, 1, 1, 2, 0x05, 0x00, 0xE4, 0x41]);
+        bytes.extend(0x0001_0823u32.to_le_bytes());
+        bytes.extend(0x0000_0E00u32.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn from_bytes() {
+        let config = ChipConfiguration::from_bytes(&image()).unwrap();
+        assert_eq!(config.vendor_id(), 0x0403);
+        assert_eq!(config.product_id(), 0x601F);
+        assert_eq!(config.interrupt_latency(), 9);
+        assert_eq!(config.string_descriptor().manufacturer(), "FTDI");
+        assert_eq!(config.string_descriptor().product(), "FT601");
+        assert_eq!(config.string_descriptor().serial_number(), "000000000001");
+        let raw = config.to_ffi();
+        assert_eq!(raw.PowerConsumption, 0x60);
+        assert_eq!(raw.Reserved2, 0xAA);
+        assert_eq!(raw.OptionalFeatureSupport, 0x0005);
+        assert_eq!(raw.FlashEEPROMDetection, 0x41);
+        assert_eq!(raw.MSIO_Control, 0x0001_0823);
+        assert_eq!(raw.GPIO_Control, 0x0000_0E00);
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = image();
+        let config = ChipConfiguration::from_bytes(&bytes).unwrap();
+        assert_eq!(config.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn to_bytes_from_bytes_identity() {
+        let mut config = ChipConfiguration::from_bytes(&image()).unwrap();
+        config.set_interrupt_latency(4);
+        config.string_descriptor_mut().set_product("Widget");
+        let bytes = config.to_bytes();
+        assert_eq!(bytes.len(), CHIP_CONFIGURATION_SIZE);
+
+        let reread = ChipConfiguration::from_bytes(&bytes).unwrap();
+        assert_eq!(reread.interrupt_latency(), 4);
+        assert_eq!(reread.string_descriptor().product(), "Widget");
+        assert_eq!(reread.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!("d3xx-config-{}.bin", std::process::id()));
+        let config = ChipConfiguration::from_bytes(&image()).unwrap();
+        config.to_file(&path).unwrap();
+        let reread = ChipConfiguration::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reread.unwrap().to_bytes(), image());
+    }
+
+    #[test]
+    fn chip_configuration_raw() {
+        let api = Arc::new(MockApi::default());
+        api.set_chip_config(decode(&image()).unwrap());
+        let device = mock::device(&api);
+
+        let raw = device.chip_configuration_raw().unwrap();
+        let decoded = device.chip_configuration().unwrap();
+        assert_eq!(raw.as_slice(), image());
+        assert_eq!(u16::from_le_bytes([raw[0], raw[1]]), decoded.vendor_id());
+        assert_eq!(u16::from_le_bytes([raw[2], raw[3]]), decoded.product_id());
+        assert_eq!(
+            api.calls(),
+            [Call::GetChipConfiguration, Call::GetChipConfiguration]
+        );
+    }
+
+    #[test]
+    fn invalid_length() {
+        let bytes = image();
+        assert_eq!(
+            ChipConfiguration::from_bytes(&bytes[..CHIP_CONFIGURATION_SIZE - 1]).err(),
+            Some(D3xxError::InvalidArgs)
+        );
+        assert_eq!(
+            ChipConfiguration::from_bytes(&[bytes, vec![0]].concat()).err(),
+            Some(D3xxError::InvalidArgs)
+        );
+        assert_eq!(
+            ChipConfiguration::from_bytes(&[]).err(),
+            Some(D3xxError::InvalidArgs)
+        );
+    }
+
+    #[test]
+    fn invalid_field() {
+        let mut bytes = image();
+        // ChannelConfig
+        bytes[139] = 0xFF;
+        assert_eq!(
+            ChipConfiguration::from_bytes(&bytes).err(),
+            Some(D3xxError::OtherError)
+        );
+    }
+
+    #[test]
+    fn from_file_missing() {
+        let err = ChipConfiguration::from_file("/nonexistent/d3xx.cfg")
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
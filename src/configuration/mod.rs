@@ -4,9 +4,9 @@
 //! The configuration of a chip is not one-to-one with the USB configuration descriptor, although
 //! some of the fields are.
 //!
-//! The configuration may be read from a device once it is opened. Writing configuration changes
-//! to the device is not yet supported. The chip configuration contains a large amount of information about
-//! the device, including:
+//! The configuration may be read from a device once it is opened, modified, and written back
+//! using [`Device::set_chip_configuration`](crate::Device::set_chip_configuration). The chip
+//! configuration contains a large amount of information about the device, including:
 //!
 //! - Identification
 //! - Power consumption
@@ -21,15 +21,24 @@
 //! which describes the configuration options in detail.
 
 mod data_transfer;
+mod diff;
+mod file;
 mod optional;
 mod pin_drive;
 mod power;
 mod string_descriptor;
 
-use std::ptr::addr_of_mut;
+use std::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    time::Duration,
+};
 
-use crate::{ffi, try_d3xx, Result};
+use crate::{ffi, util::microframe_interval, Result};
 pub use data_transfer::*;
+pub use diff::*;
+pub(crate) use file::encode;
+pub use file::CHIP_CONFIGURATION_SIZE;
 pub use optional::*;
 pub use pin_drive::*;
 pub use power::*;
@@ -37,8 +46,12 @@ pub use string_descriptor::*;
 
 /// `FT60x` chip configuration.
 ///
-/// The configuration may be read from a device once it is opened.
-/// Writing configuration changes to the device is not yet supported.
+/// The configuration may be read from a device once it is opened, and written back using
+/// [`Device::set_chip_configuration`](crate::Device::set_chip_configuration).
+///
+/// Configurations are compared by the fields exposed through their accessors. Fields which
+/// are not exposed, such as `FlashEEPROMDetection`, are ignored, so two equal configurations
+/// may still produce different images from [`to_ffi`](Self::to_ffi).
 pub struct ChipConfiguration {
     vid: u16,
     pid: u16,
@@ -48,15 +61,14 @@ pub struct ChipConfiguration {
     interrupt_latency: u8,
     data_transfer: DataTransferConfig,
     optional_features: OptionalFeatures,
+    /// Configuration as read from the device. Used to preserve fields which are
+    /// not exposed when writing the configuration back.
+    raw: ffi::FT_60XCONFIGURATION,
 }
 
 impl ChipConfiguration {
-    /// Create a new `ChipConfiguration` instance using the given handle.
-    ///
-    /// Attempts to read the chip configuration from the device.
-    pub(crate) fn new(handle: ffi::FT_HANDLE) -> Result<Self> {
-        let mut config: ffi::FT_60XCONFIGURATION = unsafe { std::mem::zeroed() };
-        try_d3xx!(unsafe { ffi::FT_GetChipConfiguration(handle, addr_of_mut!(config).cast()) })?;
+    /// Create a new `ChipConfiguration` from the raw configuration read from a device.
+    pub(crate) fn new(config: ffi::FT_60XCONFIGURATION) -> Result<Self> {
         Ok(Self {
             vid: config.VendorID,
             pid: config.ProductID,
@@ -73,9 +85,47 @@ impl ChipConfiguration {
                 config.OptionalFeatureSupport,
                 config.BatteryChargingGPIOConfig,
             ),
+            raw: config,
         })
     }
 
+    /// Convert the configuration back into the raw structure used by the D3XX driver.
+    ///
+    /// This reverses the field extraction performed when the configuration was read,
+    /// and is what [`Device::set_chip_configuration`](crate::Device::set_chip_configuration)
+    /// writes to the device. It may also be useful for debugging.
+    ///
+    /// Fields which are not exposed, such as the read-only flash detection flags and
+    /// the bits of `MSIO_Control`/`GPIO_Control` unrelated to drive strength, are
+    /// carried over unchanged from the configuration originally read.
+    #[must_use]
+    pub fn to_ffi(&self) -> ffi::FT_60XCONFIGURATION {
+        let (power_attributes, power_consumption) = self.power_config.raw();
+        let (fifo_clock, fifo_mode, channel_config) = self.data_transfer.raw();
+        let (msio_control, gpio_control) = self
+            .pin_drive_strength
+            .pack(self.raw.MSIO_Control, self.raw.GPIO_Control);
+        ffi::FT_60XCONFIGURATION {
+            VendorID: self.vid,
+            ProductID: self.pid,
+            StringDescriptors: self.string_descriptor.as_ffi_descriptor(),
+            bInterval: self.interrupt_latency,
+            PowerAttributes: power_attributes,
+            PowerConsumption: power_consumption,
+            FIFOClock: fifo_clock,
+            FIFOMode: fifo_mode,
+            ChannelConfig: channel_config,
+            OptionalFeatureSupport: self.optional_features.flags(),
+            BatteryChargingGPIOConfig: self
+                .optional_features
+                .battery_flags()
+                .unwrap_or(self.raw.BatteryChargingGPIOConfig),
+            MSIO_Control: msio_control,
+            GPIO_Control: gpio_control,
+            ..self.raw
+        }
+    }
+
     /// Vendor ID.
     #[must_use]
     pub fn vendor_id(&self) -> u16 {
@@ -147,6 +197,15 @@ impl ChipConfiguration {
         self.interrupt_latency
     }
 
+    /// Interrupt latency as a duration of time.
+    ///
+    /// This is `2**(latency-1) * 125us`. Raw values outside of `1..=16` are
+    /// clamped to that range.
+    #[must_use]
+    pub fn interrupt_latency_duration(&self) -> Duration {
+        microframe_interval(self.interrupt_latency)
+    }
+
     /// Set the interrupt latency. Values may be `1` through `16` (inclusive).
     ///
     /// The latency is translated as `2**(latency-1)` USB frames, and
@@ -194,3 +253,255 @@ impl ChipConfiguration {
         &mut self.optional_features
     }
 }
+
+impl PartialEq for ChipConfiguration {
+    fn eq(&self, other: &Self) -> bool {
+        self.vid == other.vid
+            && self.pid == other.pid
+            && self.string_descriptor == other.string_descriptor
+            && self.power_config == other.power_config
+            && self.pin_drive_strength == other.pin_drive_strength
+            && self.interrupt_latency == other.interrupt_latency
+            && self.data_transfer == other.data_transfer
+            && self.optional_features == other.optional_features
+    }
+}
+
+impl Eq for ChipConfiguration {}
+
+impl Hash for ChipConfiguration {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.vid.hash(state);
+        self.pid.hash(state);
+        self.string_descriptor.hash(state);
+        self.power_config.hash(state);
+        self.pin_drive_strength.hash(state);
+        self.interrupt_latency.hash(state);
+        self.data_transfer.hash(state);
+        self.optional_features.hash(state);
+    }
+}
+
+impl Debug for ChipConfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChipConfiguration")
+            .field("vid", &format_args!("{:#06x}", self.vid))
+            .field("pid", &format_args!("{:#06x}", self.pid))
+            .field("string_descriptor", &self.string_descriptor)
+            .field("power_config", &self.power_config)
+            .field("pin_drive_strength", &self.pin_drive_strength)
+            .field("interrupt_latency", &self.interrupt_latency)
+            .field("data_transfer", &self.data_transfer)
+            .field("optional_features", &self.optional_features)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mock::{self, Call, MockApi};
+
+    /// A configuration with valid string descriptors and every other field zeroed.
+    fn zeroed() -> ffi::FT_60XCONFIGURATION {
+        let mut config: ffi::FT_60XCONFIGURATION = unsafe { std::mem::zeroed() };
+        let mut offset = 0;
+        for part in ["FTDI", "FT601", "000000000001"] {
+            let bytes: Vec<u8> = part.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            config.StringDescriptors[offset] = u8::try_from(bytes.len() + 2).unwrap();
+            config.StringDescriptors[offset + 1] = 0x03;
+            config.StringDescriptors[offset + 2..][..bytes.len()].copy_from_slice(&bytes);
+            offset += bytes.len() + 2;
+        }
+        config
+    }
+
+    fn assert_ffi_eq(a: &ffi::FT_60XCONFIGURATION, b: &ffi::FT_60XCONFIGURATION) {
+        assert_eq!(a.VendorID, b.VendorID);
+        assert_eq!(a.ProductID, b.ProductID);
+        assert_eq!(a.StringDescriptors, b.StringDescriptors);
+        assert_eq!(a.bInterval, b.bInterval);
+        assert_eq!(a.PowerAttributes, b.PowerAttributes);
+        assert_eq!(a.PowerConsumption, b.PowerConsumption);
+        assert_eq!(a.Reserved2, b.Reserved2);
+        assert_eq!(a.FIFOClock, b.FIFOClock);
+        assert_eq!(a.FIFOMode, b.FIFOMode);
+        assert_eq!(a.ChannelConfig, b.ChannelConfig);
+        assert_eq!(a.OptionalFeatureSupport, b.OptionalFeatureSupport);
+        assert_eq!(a.BatteryChargingGPIOConfig, b.BatteryChargingGPIOConfig);
+        assert_eq!(a.FlashEEPROMDetection, b.FlashEEPROMDetection);
+        assert_eq!(a.MSIO_Control, b.MSIO_Control);
+        assert_eq!(a.GPIO_Control, b.GPIO_Control);
+    }
+
+    #[test]
+    fn to_ffi_identity() {
+        let mut raw = zeroed();
+        raw.VendorID = 0x0403;
+        raw.ProductID = 0x601F;
+        raw.bInterval = 9;
+        raw.PowerAttributes = 0xE0;
+        raw.PowerConsumption = 0x60;
+        raw.Reserved2 = 0xAA;
+        raw.FIFOClock = 1;
+        raw.FIFOMode = 1;
+        raw.ChannelConfig = 2;
+        raw.OptionalFeatureSupport = 0x0005;
+        raw.BatteryChargingGPIOConfig = 0xE4;
+        raw.FlashEEPROMDetection = 0x41;
+        raw.MSIO_Control = 0x0001_0823;
+        raw.GPIO_Control = 0x0000_0E00;
+
+        let config = ChipConfiguration::new(raw).unwrap();
+        assert_ffi_eq(&config.to_ffi(), &raw);
+        assert_ffi_eq(
+            &ChipConfiguration::new(config.to_ffi()).unwrap().to_ffi(),
+            &raw,
+        );
+    }
+
+    #[test]
+    fn to_ffi_preserves_battery_flags_when_disabled() {
+        let mut raw = zeroed();
+        raw.BatteryChargingGPIOConfig = 0xE4;
+        let config = ChipConfiguration::new(raw).unwrap();
+        assert!(config.optional_features().battery_charging().is_none());
+        assert_eq!(config.to_ffi().BatteryChargingGPIOConfig, 0xE4);
+    }
+
+    #[test]
+    fn round_trip_mutated() {
+        let raw = zeroed();
+        let mut config = ChipConfiguration::new(raw).unwrap();
+        config.set_interrupt_latency(9);
+        config.string_descriptor_mut().set_product("Widget");
+
+        let rebuilt = config.to_ffi();
+        let mut expected = raw;
+        expected.bInterval = 9;
+        expected.StringDescriptors = config.string_descriptor().as_ffi_descriptor();
+        assert_ffi_eq(&rebuilt, &expected);
+
+        let reread = ChipConfiguration::new(rebuilt).unwrap();
+        assert_eq!(reread.interrupt_latency(), 9);
+        assert_eq!(reread.string_descriptor().product(), "Widget");
+        assert_eq!(reread.string_descriptor().manufacturer(), "FTDI");
+    }
+
+    #[test]
+    fn equality() {
+        let config = ChipConfiguration::new(zeroed()).unwrap();
+        assert_eq!(config, ChipConfiguration::new(zeroed()).unwrap());
+
+        // Fields which are not exposed are ignored.
+        let mut raw = zeroed();
+        raw.FlashEEPROMDetection = 0x41;
+        raw.BatteryChargingGPIOConfig = 0xE4;
+        assert_eq!(config, ChipConfiguration::new(raw).unwrap());
+
+        let mut other = ChipConfiguration::new(zeroed()).unwrap();
+        other.set_interrupt_latency(4);
+        assert_ne!(config, other);
+
+        let mut raw = zeroed();
+        raw.FIFOClock = 1;
+        let other = ChipConfiguration::new(raw).unwrap();
+        assert_ne!(config, other);
+        assert_ne!(config.data_transfer(), other.data_transfer());
+        assert_eq!(config.power_config(), other.power_config());
+
+        let mut raw = zeroed();
+        raw.MSIO_Control = 0x0000_0003;
+        let other = ChipConfiguration::new(raw).unwrap();
+        assert_ne!(config, other);
+        assert_ne!(config.pin_drive_strengths(), other.pin_drive_strengths());
+        assert_eq!(config.string_descriptor(), other.string_descriptor());
+    }
+
+    #[test]
+    fn set_membership() {
+        let mut other = ChipConfiguration::new(zeroed()).unwrap();
+        other
+            .string_descriptor_mut()
+            .set_serial_number("000000000002");
+        let set: std::collections::HashSet<_> = [
+            ChipConfiguration::new(zeroed()).unwrap(),
+            ChipConfiguration::new(zeroed()).unwrap(),
+            other,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn debug() {
+        let mut raw = zeroed();
+        raw.VendorID = 0x0403;
+        raw.ProductID = 0x601F;
+        raw.PowerAttributes = 0xE0;
+        raw.PowerConsumption = 0x60;
+        raw.OptionalFeatureSupport = 0x0001;
+        raw.BatteryChargingGPIOConfig = 0xE4;
+        let debug = format!("{:?}", ChipConfiguration::new(raw).unwrap());
+        assert!(debug.contains("vid: 0x0403, pid: 0x601f"));
+        assert!(debug.contains("product: \"FT601\""));
+        assert!(debug.contains("self_powered: true, remote_wakeup: true, max_power: 192"));
+        assert!(debug.contains("fifo_clock: Clock100Mhz"));
+        assert!(debug.contains("flags: 0x0001"));
+        assert!(debug.contains("dcp: 3, cdp: 2, sdp: 1, default_output: 0"));
+        assert!(!debug.contains("raw"));
+    }
+
+    #[test]
+    fn interrupt_latency_duration() {
+        let mut config = ChipConfiguration::new(zeroed()).unwrap();
+        config.set_interrupt_latency(1);
+        assert_eq!(
+            config.interrupt_latency_duration(),
+            Duration::from_micros(125)
+        );
+        config.set_interrupt_latency(4);
+        assert_eq!(
+            config.interrupt_latency_duration(),
+            Duration::from_millis(1)
+        );
+        config.set_interrupt_latency(16);
+        assert_eq!(
+            config.interrupt_latency_duration(),
+            Duration::from_millis(4096)
+        );
+    }
+
+    #[test]
+    fn set_chip_configuration() {
+        let api = Arc::new(MockApi::default());
+        api.set_chip_config(zeroed());
+        let device = mock::device(&api);
+
+        let mut config = device.chip_configuration().unwrap();
+        config.set_interrupt_latency(4);
+        device.set_chip_configuration(&config).unwrap();
+
+        assert_eq!(
+            api.calls(),
+            [Call::GetChipConfiguration, Call::SetChipConfiguration]
+        );
+        assert_ffi_eq(&api.chip_config().unwrap(), &config.to_ffi());
+        assert_eq!(api.chip_config().unwrap().bInterval, 4);
+    }
+
+    #[test]
+    fn power_config_mut() {
+        let mut config = ChipConfiguration::new(zeroed()).unwrap();
+        let power = config.power_config_mut();
+        power.set_self_powered(true);
+        power.set_remote_wakeup(true);
+        power.set_max_power(200);
+        let raw = config.to_ffi();
+        assert_eq!(raw.PowerAttributes, 0x60);
+        assert_eq!(raw.PowerConsumption, 100);
+    }
+}
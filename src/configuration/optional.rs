@@ -1,3 +1,5 @@
+use std::fmt::Debug;
+
 use crate::Pipe;
 
 const FLAG_BATTERY_CHARGING_ENABLE: u16 = 0b0000_0001;
@@ -12,8 +14,10 @@ const FLAG_CHARGING_MODE_SDP: u8 = 0x0C;
 const OFFSET_CHARGING_MODE_DCP: usize = 6;
 const OFFSET_CHARGING_MODE_CDP: usize = 4;
 const OFFSET_CHARGING_MODE_SDP: usize = 2;
+const FLAG_CHARGING_MODE_DEF: u8 = 0x03;
 
 /// Optional features.
+#[derive(PartialEq, Eq, Hash)]
 pub struct OptionalFeatures {
     flags: u16,
     battery_charging: Option<BatteryChargingModes>,
@@ -30,6 +34,16 @@ impl OptionalFeatures {
         }
     }
 
+    /// Raw `OptionalFeatureSupport` flags.
+    pub(crate) fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Raw `BatteryChargingGPIOConfig` value, if battery charging is enabled.
+    pub(crate) fn battery_flags(&self) -> Option<u8> {
+        self.battery_charging.as_ref().map(|modes| modes.0)
+    }
+
     /// Check if all optional features are disabled.
     #[must_use]
     pub fn all_disabled(&self) -> bool {
@@ -61,6 +75,7 @@ impl OptionalFeatures {
         self.flags & (FLAG_NOTIFICATION_ENABLE_PIPE0 << in_pipe as u16) != 0
     }
 
+    /// Check if underrun condition checks are enabled.
     #[must_use]
     pub fn underrun_check_enabled(&self) -> bool {
         self.flags & FLAG_UNDERRUN_DISABLE == 0
@@ -85,15 +100,29 @@ impl OptionalFeatures {
     }
 }
 
-/// Battery charging mode.
+impl Debug for OptionalFeatures {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OptionalFeatures")
+            .field("flags", &format_args!("{:#06x}", self.flags))
+            .field("battery_charging", &self.battery_charging)
+            .finish()
+    }
+}
+
+/// Battery charging GPIO configuration.
 ///
-/// This indicates the type of power source the device is connected to.
-/// This is only available for configurations in whichs the GPIO pins are
-/// properly configured.
+/// When battery charging is enabled the chip detects the type of USB port it is
+/// connected to, and drives the GPIO pins to a configured value to report it.
+/// This struct holds that mapping: for each port type, the 2-bit value driven
+/// onto GPIO1 and GPIO0 (bit 1 and bit 0, respectively). It does not describe
+/// the port which is currently detected; to determine that, read the GPIO pins
+/// (see [`Gpios::read`](crate::Gpios::read)) and pass the value to
+/// [`BatteryChargingModes::port_for_gpio`].
 ///
 /// # Further Reading
 ///
 /// See <https://www.analog.com/en/technical-articles/the-basics-of-usb-battery-charging.html>
+#[derive(PartialEq, Eq, Hash)]
 pub struct BatteryChargingModes(u8);
 
 impl BatteryChargingModes {
@@ -123,4 +152,104 @@ impl BatteryChargingModes {
     pub fn sdp(&self) -> u8 {
         (self.0 & FLAG_CHARGING_MODE_SDP) >> OFFSET_CHARGING_MODE_SDP
     }
+
+    /// GPIO value driven when no charging port has been detected.
+    #[must_use]
+    pub fn default_output(&self) -> u8 {
+        self.0 & FLAG_CHARGING_MODE_DEF
+    }
+
+    /// GPIO value driven when the given port type is detected.
+    #[must_use]
+    pub fn gpio_output(&self, port: ChargingPort) -> u8 {
+        match port {
+            ChargingPort::Dcp => self.dcp(),
+            ChargingPort::Cdp => self.cdp(),
+            ChargingPort::Sdp => self.sdp(),
+        }
+    }
+
+    /// Determine the detected port type from the value of the GPIO pins.
+    ///
+    /// Only the lower two bits of `gpio` are used. Returns `None` if the value
+    /// corresponds to no port type, or to the [default output](Self::default_output).
+    /// If several port types share the same value, the first of DCP, CDP, and SDP
+    /// is returned.
+    #[must_use]
+    pub fn port_for_gpio(&self, gpio: u8) -> Option<ChargingPort> {
+        let gpio = gpio & FLAG_CHARGING_MODE_DEF;
+        if gpio == self.default_output() {
+            return None;
+        }
+        ChargingPort::all()
+            .into_iter()
+            .find(|&port| self.gpio_output(port) == gpio)
+    }
+}
+
+impl Debug for BatteryChargingModes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatteryChargingModes")
+            .field("dcp", &self.dcp())
+            .field("cdp", &self.cdp())
+            .field("sdp", &self.sdp())
+            .field("default_output", &self.default_output())
+            .finish()
+    }
+}
+
+/// Type of USB port detected for battery charging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChargingPort {
+    /// Dedicated charging port (DCP).
+    Dcp,
+    /// Charging downstream port (CDP).
+    Cdp,
+    /// Standard downstream port (SDP).
+    Sdp,
+}
+
+impl ChargingPort {
+    /// All port types.
+    #[must_use]
+    pub const fn all() -> [Self; 3] {
+        [Self::Dcp, Self::Cdp, Self::Sdp]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_charging_default_config() {
+        // default configuration from the D3XX headers
+        let modes = BatteryChargingModes(0xE4);
+        assert_eq!(modes.gpio_output(ChargingPort::Dcp), 0b11);
+        assert_eq!(modes.gpio_output(ChargingPort::Cdp), 0b10);
+        assert_eq!(modes.gpio_output(ChargingPort::Sdp), 0b01);
+        assert_eq!(modes.default_output(), 0b00);
+        assert_eq!(modes.port_for_gpio(0b11), Some(ChargingPort::Dcp));
+        assert_eq!(modes.port_for_gpio(0b10), Some(ChargingPort::Cdp));
+        assert_eq!(modes.port_for_gpio(0b01), Some(ChargingPort::Sdp));
+        assert_eq!(modes.port_for_gpio(0b00), None);
+        assert_eq!(modes.port_for_gpio(0b111), Some(ChargingPort::Dcp));
+    }
+
+    #[test]
+    fn battery_charging_custom_config() {
+        // DCP=01, CDP=00, SDP=11, DEF=10
+        let modes = BatteryChargingModes(0b01_00_11_10);
+        assert_eq!(modes.port_for_gpio(0b01), Some(ChargingPort::Dcp));
+        assert_eq!(modes.port_for_gpio(0b00), Some(ChargingPort::Cdp));
+        assert_eq!(modes.port_for_gpio(0b11), Some(ChargingPort::Sdp));
+        assert_eq!(modes.port_for_gpio(0b10), None);
+    }
+
+    #[test]
+    fn battery_charging_shared_value() {
+        let modes = BatteryChargingModes(0b01_01_01_00);
+        assert_eq!(modes.port_for_gpio(0b01), Some(ChargingPort::Dcp));
+        assert_eq!(modes.port_for_gpio(0b11), None);
+    }
 }
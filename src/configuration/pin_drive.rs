@@ -2,28 +2,51 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::{D3xxError, Result};
 
+const DRIVE_STRENGTH_MASK: u32 = 0b11;
+const OFFSET_FIFO_DATA: u32 = 0;
+const OFFSET_FIFO_CLOCK: u32 = 4;
+const OFFSET_GPIO0: u32 = 8;
+const OFFSET_GPIO1: u32 = 10;
+
 /// Drive strength configuration for a GPIO/FIFO.
 ///
 /// The drive strength configuration indicates the load driven by the GPIO/FIFO.
 /// This should be appropriately configured to avoid voltage deviation.
-#[derive(IntoPrimitive, TryFromPrimitive)]
+///
+/// The raw values `0..=3` select 50, 35, 25, and 18 Ohm respectively, as
+/// described in the FT60X datasheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum DriveStrength {
     /// 50-Ohm drive strength.
     Ohm50,
-    /// 25-Ohm drive strength.
+    /// 35-Ohm drive strength.
     Ohm35,
-    /// 18-Ohm drive strength.
+    /// 25-Ohm drive strength.
     Ohm25,
-    /// 15-Ohm drive strength.
+    /// 18-Ohm drive strength.
     Ohm18,
 }
 
+impl DriveStrength {
+    /// Get the drive strength in Ohms.
+    #[must_use]
+    pub const fn ohms(self) -> u8 {
+        match self {
+            Self::Ohm50 => 50,
+            Self::Ohm35 => 35,
+            Self::Ohm25 => 25,
+            Self::Ohm18 => 18,
+        }
+    }
+}
+
 /// Pin drive strengths.
 ///
 /// The pin drive strengths contain:
 /// - GPIO drive strengths
 /// - MSIO (FIFO) clock/data drive strengths
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct PinDriveStrengths {
     fifo_data: DriveStrength,
     fifo_clock: DriveStrength,
@@ -34,17 +57,23 @@ pub struct PinDriveStrengths {
 impl PinDriveStrengths {
     pub(crate) fn new(msio: u32, gpio: u32) -> Result<Self> {
         Ok(Self {
-            fifo_data: DriveStrength::try_from((msio & 0b11) as u8)
-                .or(Err(D3xxError::OtherError))?,
-            fifo_clock: DriveStrength::try_from(((msio >> 4) & 0b11) as u8)
-                .or(Err(D3xxError::OtherError))?,
-            gpio0: DriveStrength::try_from(((gpio >> 8) & 0b11) as u8)
-                .or(Err(D3xxError::OtherError))?,
-            gpio1: DriveStrength::try_from(((gpio >> 10) & 0b11) as u8)
-                .or(Err(D3xxError::OtherError))?,
+            fifo_data: extract(msio, OFFSET_FIFO_DATA)?,
+            fifo_clock: extract(msio, OFFSET_FIFO_CLOCK)?,
+            gpio0: extract(gpio, OFFSET_GPIO0)?,
+            gpio1: extract(gpio, OFFSET_GPIO1)?,
         })
     }
 
+    /// Write the drive strengths into the given `MSIO_Control` and `GPIO_Control`
+    /// values, leaving all other bits untouched.
+    pub(crate) fn pack(&self, msio: u32, gpio: u32) -> (u32, u32) {
+        let msio = insert(msio, OFFSET_FIFO_DATA, self.fifo_data);
+        let msio = insert(msio, OFFSET_FIFO_CLOCK, self.fifo_clock);
+        let gpio = insert(gpio, OFFSET_GPIO0, self.gpio0);
+        let gpio = insert(gpio, OFFSET_GPIO1, self.gpio1);
+        (msio, gpio)
+    }
+
     /// Get the FIFO data drive strength.
     #[must_use]
     pub fn fifo_data(&self) -> &DriveStrength {
@@ -69,3 +98,51 @@ impl PinDriveStrengths {
         &self.gpio1
     }
 }
+
+fn extract(value: u32, offset: u32) -> Result<DriveStrength> {
+    // the mask guarantees the value fits in a u8
+    #[allow(clippy::cast_possible_truncation)]
+    DriveStrength::try_from(((value >> offset) & DRIVE_STRENGTH_MASK) as u8)
+        .or(Err(D3xxError::OtherError))
+}
+
+fn insert(value: u32, offset: u32, strength: DriveStrength) -> u32 {
+    (value & !(DRIVE_STRENGTH_MASK << offset)) | (u32::from(u8::from(strength)) << offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drive_strength_ohms() {
+        let ohms: Vec<u8> = (0..4u8)
+            .map(|raw| DriveStrength::try_from(raw).unwrap().ohms())
+            .collect();
+        assert_eq!(ohms, [50, 35, 25, 18]);
+        assert!(DriveStrength::try_from(4).is_err());
+    }
+
+    #[test]
+    fn pin_drive_strengths_fields() {
+        let strengths = PinDriveStrengths::new((0b01 << 4) | 0b10, 0b11 << 10).unwrap();
+        assert_eq!(*strengths.fifo_data(), DriveStrength::Ohm25);
+        assert_eq!(*strengths.fifo_clock(), DriveStrength::Ohm35);
+        assert_eq!(*strengths.gpio0(), DriveStrength::Ohm50);
+        assert_eq!(*strengths.gpio1(), DriveStrength::Ohm18);
+    }
+
+    #[test]
+    fn pack_inverts_new() {
+        for raw in 0..=u8::MAX {
+            let msio = 0xFFFF_FF00 | u32::from(raw & 0b11) | (u32::from((raw >> 2) & 0b11) << 4);
+            let gpio =
+                0xFFFF_F0FF | (u32::from((raw >> 4) & 0b11) << 8) | (u32::from(raw >> 6) << 10);
+            // bits not belonging to a drive strength are preserved as well
+            let msio = msio | 0b1100_1100;
+            let strengths = PinDriveStrengths::new(msio, gpio).unwrap();
+            assert_eq!(strengths.pack(msio, gpio), (msio, gpio));
+            assert_eq!(strengths.pack(0, 0), (msio & 0x33, gpio & 0xF00));
+        }
+    }
+}
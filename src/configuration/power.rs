@@ -1,7 +1,10 @@
+use std::fmt::Debug;
+
 const FLAG_REMOTE_WAKEUP: u8 = 0x20;
 const FLAG_SELF_POWERED: u8 = 0x40;
 
 /// Power configuration contained in the configuration descriptor.
+#[derive(PartialEq, Eq, Hash)]
 pub struct PowerConfig {
     flags: u8,
     max_power: u16,
@@ -12,6 +15,11 @@ impl PowerConfig {
         Self { flags, max_power }
     }
 
+    /// Raw `PowerAttributes` and `PowerConsumption` values.
+    pub(crate) fn raw(&self) -> (u8, u16) {
+        (self.flags, self.max_power)
+    }
+
     /// Check if the device is bus-powered.
     #[must_use]
     pub fn bus_powered(&self) -> bool {
@@ -24,6 +32,13 @@ impl PowerConfig {
         self.flags & FLAG_SELF_POWERED != 0
     }
 
+    /// Set whether the device is self-powered.
+    ///
+    /// A device which is not self-powered is bus-powered.
+    pub fn set_self_powered(&mut self, self_powered: bool) {
+        self.set_flag(FLAG_SELF_POWERED, self_powered);
+    }
+
     /// Check if the device supports remote wakeup.
     ///
     /// Remote wakeup is a feature of some USB devices that allows them to
@@ -34,9 +49,77 @@ impl PowerConfig {
         self.flags & FLAG_REMOTE_WAKEUP != 0
     }
 
+    /// Set whether the device supports remote wakeup.
+    pub fn set_remote_wakeup(&mut self, remote_wakeup: bool) {
+        self.set_flag(FLAG_REMOTE_WAKEUP, remote_wakeup);
+    }
+
     /// Get the maximum power consumption in milliamps.
     #[must_use]
     pub fn max_power(&self) -> u16 {
         self.max_power * 2 // 2mA units
     }
+
+    /// Set the maximum power consumption in milliamps.
+    ///
+    /// The value is stored in units of 2mA, so an odd value is rounded down.
+    pub fn set_max_power(&mut self, milliamps: u16) {
+        self.max_power = milliamps / 2;
+    }
+
+    /// Set or clear a bit in the power attributes, leaving the others unchanged.
+    fn set_flag(&mut self, flag: u8, value: bool) {
+        if value {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+}
+
+impl Debug for PowerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PowerConfig")
+            .field("self_powered", &self.self_powered())
+            .field("remote_wakeup", &self.remote_wakeup())
+            .field("max_power", &self.max_power())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setters_round_trip() {
+        // Bit 7 is reserved and must be preserved.
+        let mut config = PowerConfig::new(0x80, 50);
+        config.set_self_powered(true);
+        assert!(config.self_powered());
+        assert!(!config.bus_powered());
+        assert_eq!(config.raw(), (0xC0, 50));
+
+        config.set_remote_wakeup(true);
+        assert!(config.remote_wakeup());
+        assert_eq!(config.raw(), (0xE0, 50));
+
+        config.set_self_powered(false);
+        assert!(config.bus_powered());
+        assert!(config.remote_wakeup());
+        assert_eq!(config.raw(), (0xA0, 50));
+
+        config.set_remote_wakeup(false);
+        assert_eq!(config.raw(), (0x80, 50));
+    }
+
+    #[test]
+    fn max_power_units() {
+        let mut config = PowerConfig::new(0x80, 0);
+        config.set_max_power(500);
+        assert_eq!(config.max_power(), 500);
+        assert_eq!(config.raw(), (0x80, 250));
+        config.set_max_power(97);
+        assert_eq!(config.max_power(), 96);
+    }
 }
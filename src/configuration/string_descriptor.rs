@@ -1,3 +1,10 @@
+/// Maximum length of the manufacturer name, in UTF-16 code units.
+const MAX_MANUFACTURER_LEN: usize = 15;
+/// Maximum length of the product name, in UTF-16 code units.
+const MAX_PRODUCT_LEN: usize = 31;
+/// Maximum length of the serial number, in UTF-16 code units.
+const MAX_SERIAL_NUMBER_LEN: usize = 15;
+
 /// Container for the string descriptors of a [`Device`](crate::Device).
 ///
 /// The D3XX API provides access to the string descriptor as a single
@@ -12,7 +19,10 @@
 /// - Serial number
 ///
 /// Each descriptor is prefixed with a header containing the length of the
-/// descriptor (including the header) and the descriptor type.
+/// descriptor (including the header) and the descriptor type. The three
+/// descriptors share a 128-byte buffer, allowing up to 15, 31, and 15 UTF-16
+/// code units respectively.
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct StringDescriptor {
     manufacturer: String,
     product: String,
@@ -22,14 +32,15 @@ pub struct StringDescriptor {
 impl StringDescriptor {
     /// Extract the string descriptors from the given byte array.
     ///
-    /// # Panics
-    ///
-    /// Panics if the byte array contains any malformed headers.
+    /// The data comes from the device and is not trusted. A descriptor with a
+    /// malformed header is read as an empty string, as are any descriptors
+    /// following it, since their position can no longer be determined.
     pub(crate) fn new(descriptor: [u8; 128]) -> Self {
+        let mut rest = &descriptor[..];
         Self {
-            manufacturer: Self::extract_part(&descriptor, 0),
-            product: Self::extract_part(&descriptor, 1),
-            serial_number: Self::extract_part(&descriptor, 2),
+            manufacturer: Self::extract_part(&mut rest),
+            product: Self::extract_part(&mut rest),
+            serial_number: Self::extract_part(&mut rest),
         }
     }
 
@@ -41,11 +52,10 @@ impl StringDescriptor {
 
     /// Set the manufacturer name.
     ///
-    /// The string will be converted to UTF-16 and truncated to 30 characters
-    /// when written to the device.
-    #[allow(unused)]
-    pub(crate) fn set_manufacturer(&mut self, manufacturer: &str) {
-        self.manufacturer = manufacturer.to_owned();
+    /// The string is truncated to 15 UTF-16 code units (30 bytes), which is the
+    /// space available for it in the chip configuration.
+    pub fn set_manufacturer(&mut self, manufacturer: &str) {
+        truncate_utf16(manufacturer, MAX_MANUFACTURER_LEN).clone_into(&mut self.manufacturer);
     }
 
     /// Product name.
@@ -56,11 +66,10 @@ impl StringDescriptor {
 
     /// Set the product name.
     ///
-    /// The string will be converted to UTF-16 and truncated to 62 characters
-    /// when written to the device.
-    #[allow(unused)]
-    pub(crate) fn set_product(&mut self, product: &str) {
-        self.product = product.to_owned();
+    /// The string is truncated to 31 UTF-16 code units (62 bytes), which is the
+    /// space available for it in the chip configuration.
+    pub fn set_product(&mut self, product: &str) {
+        truncate_utf16(product, MAX_PRODUCT_LEN).clone_into(&mut self.product);
     }
 
     /// Serial number.
@@ -71,29 +80,27 @@ impl StringDescriptor {
 
     /// Set the serial number.
     ///
-    /// The string will be converted to UTF-16 and truncated to 30 characters
-    /// when written to the device.
-    #[allow(unused)]
-    pub(crate) fn set_serial_number(&mut self, serial_number: &str) {
-        self.serial_number = serial_number.to_owned();
+    /// The string is truncated to 15 UTF-16 code units (30 bytes), which is the
+    /// space available for it in the chip configuration.
+    pub fn set_serial_number(&mut self, serial_number: &str) {
+        truncate_utf16(serial_number, MAX_SERIAL_NUMBER_LEN).clone_into(&mut self.serial_number);
     }
 
-    /// Extract a single part of the string descriptor.
-    ///
-    /// # Panics
+    /// Extract the next part of the string descriptor, advancing `rest` past it.
     ///
-    /// Panics if any of the headers are malformed or if `index` is
-    /// greater than `2`.
-    fn extract_part(descriptors: &[u8], index: usize) -> String {
+    /// If the header is malformed an empty string is returned and `rest` is
+    /// emptied. A trailing odd byte in the payload is ignored.
+    fn extract_part(rest: &mut &[u8]) -> String {
         const HEADER_SIZE: usize = 2;
-        assert!(index < 3);
-        let mut descriptor_start = 0;
-        for _ in 0..index {
-            // first byte is the length of the descriptor (including the header)
-            descriptor_start += descriptors[descriptor_start] as usize;
+        // first byte is the length of the descriptor (including the header)
+        let len = rest.first().map_or(0, |&len| len as usize);
+        if len < HEADER_SIZE || len > rest.len() {
+            *rest = &[];
+            return String::new();
         }
-        let len = descriptors[descriptor_start] as usize - HEADER_SIZE;
-        let wide_chars = descriptors[descriptor_start + HEADER_SIZE..][..len]
+        let (part, remaining) = rest.split_at(len);
+        *rest = remaining;
+        let wide_chars = part[HEADER_SIZE..]
             .chunks_exact(2)
             .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
             .collect::<Vec<_>>();
@@ -101,20 +108,17 @@ impl StringDescriptor {
     }
 
     /// Convert this struct back into a byte array suitable for writing to the device.
-    ///
-    /// This is currently unused, but may be useful in the future.
-    #[allow(unused)]
     pub(crate) fn as_ffi_descriptor(&self) -> [u8; 128] {
         fn set_part(slice: &mut [u8], s: &[u8]) {
-            // unwrap is safe because the length of the string is always <= 62
+            // unwrap is safe because the length of the string is always <= 62 bytes
             slice[0] = u8::try_from(s.len() + 2).unwrap();
             slice[1] = 0x03;
             slice[2..][..s.len()].copy_from_slice(s);
         }
 
-        let manufacturer = str_to_utf16(&self.manufacturer, 30);
-        let product = str_to_utf16(&self.product, 62);
-        let serial_number = str_to_utf16(&self.serial_number, 30);
+        let manufacturer = str_to_utf16(&self.manufacturer, MAX_MANUFACTURER_LEN);
+        let product = str_to_utf16(&self.product, MAX_PRODUCT_LEN);
+        let serial_number = str_to_utf16(&self.serial_number, MAX_SERIAL_NUMBER_LEN);
         let mut descriptor = [0u8; 128];
         let mut offset = 0;
 
@@ -126,10 +130,131 @@ impl StringDescriptor {
     }
 }
 
+/// Encode a string as little-endian UTF-16, keeping at most `max` code units.
 fn str_to_utf16(string: &str, max: usize) -> Vec<u8> {
-    string
+    truncate_utf16(string, max)
         .encode_utf16()
-        .take(max)
         .flat_map(u16::to_le_bytes)
         .collect()
 }
+
+/// Truncate a string to at most `max` UTF-16 code units without splitting a character.
+fn truncate_utf16(string: &str, max: usize) -> &str {
+    let mut units = 0;
+    for (i, c) in string.char_indices() {
+        units += c.len_utf16();
+        if units > max {
+            return &string[..i];
+        }
+    }
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(manufacturer: &str, product: &str, serial_number: &str) -> StringDescriptor {
+        StringDescriptor {
+            manufacturer: manufacturer.to_owned(),
+            product: product.to_owned(),
+            serial_number: serial_number.to_owned(),
+        }
+    }
+
+    #[test]
+    fn setters_truncate() {
+        let mut desc = descriptor("", "", "");
+        desc.set_manufacturer(&"m".repeat(40));
+        desc.set_product(&"p".repeat(80));
+        desc.set_serial_number(&"s".repeat(40));
+        assert_eq!(desc.manufacturer(), "m".repeat(15));
+        assert_eq!(desc.product(), "p".repeat(31));
+        assert_eq!(desc.serial_number(), "s".repeat(15));
+    }
+
+    #[test]
+    fn setters_truncate_on_char_boundary() {
+        let mut desc = descriptor("", "", "");
+        // each emoji is a surrogate pair (two code units)
+        desc.set_manufacturer(&"\u{1F600}".repeat(8));
+        assert_eq!(desc.manufacturer(), "\u{1F600}".repeat(7));
+    }
+
+    #[test]
+    fn over_length_fields_fit() {
+        let desc = descriptor(&"m".repeat(100), &"p".repeat(100), &"s".repeat(100));
+        let bytes = desc.as_ffi_descriptor();
+        let parsed = StringDescriptor::new(bytes);
+        assert_eq!(parsed.manufacturer(), "m".repeat(15));
+        assert_eq!(parsed.product(), "p".repeat(31));
+        assert_eq!(parsed.serial_number(), "s".repeat(15));
+    }
+
+    #[test]
+    fn malformed_zeroed() {
+        let desc = StringDescriptor::new([0; 128]);
+        assert_eq!(desc.manufacturer(), "");
+        assert_eq!(desc.product(), "");
+        assert_eq!(desc.serial_number(), "");
+    }
+
+    #[test]
+    fn malformed_zero_length_middle() {
+        let mut bytes = [0; 128];
+        bytes[..4].copy_from_slice(&[4, 0x03, b'A', 0]);
+        // product header has a length of zero; the serial number cannot be located
+        bytes[6] = b'B';
+        let desc = StringDescriptor::new(bytes);
+        assert_eq!(desc.manufacturer(), "A");
+        assert_eq!(desc.product(), "");
+        assert_eq!(desc.serial_number(), "");
+    }
+
+    #[test]
+    fn malformed_length_one() {
+        let mut bytes = [0; 128];
+        bytes[0] = 1;
+        let desc = StringDescriptor::new(bytes);
+        assert_eq!(desc.manufacturer(), "");
+        assert_eq!(desc.product(), "");
+    }
+
+    #[test]
+    fn malformed_length_past_end() {
+        let mut bytes = [0; 128];
+        bytes[..4].copy_from_slice(&[4, 0x03, b'A', 0]);
+        bytes[4] = 0xFF;
+        let desc = StringDescriptor::new(bytes);
+        assert_eq!(desc.manufacturer(), "A");
+        assert_eq!(desc.product(), "");
+        assert_eq!(desc.serial_number(), "");
+
+        // a length which exactly fills the buffer is valid
+        let mut bytes = [0; 128];
+        bytes[0] = 128;
+        bytes[1] = 0x03;
+        let desc = StringDescriptor::new(bytes);
+        assert_eq!(desc.manufacturer().encode_utf16().count(), 63);
+        assert_eq!(desc.product(), "");
+    }
+
+    #[test]
+    fn malformed_odd_payload() {
+        let mut bytes = [0; 128];
+        bytes[..5].copy_from_slice(&[5, 0x03, b'A', 0, b'B']);
+        bytes[5..9].copy_from_slice(&[4, 0x03, b'C', 0]);
+        let desc = StringDescriptor::new(bytes);
+        assert_eq!(desc.manufacturer(), "A");
+        assert_eq!(desc.product(), "C");
+    }
+
+    #[test]
+    fn round_trip() {
+        let desc = descriptor("FTDI", "FT601 32-bit FIFO", "000000000001");
+        let parsed = StringDescriptor::new(desc.as_ffi_descriptor());
+        assert_eq!(parsed.manufacturer(), "FTDI");
+        assert_eq!(parsed.product(), "FT601 32-bit FIFO");
+        assert_eq!(parsed.serial_number(), "000000000001");
+    }
+}
@@ -19,15 +19,19 @@
 //! - <https://www.keil.com/pack/doc/mw/USB/html/_u_s_b__descriptors.html>
 //! - <https://ftdichip.com/wp-content/uploads/2020/08/TN_113_Simplified-Description-of-USB-Device-Enumeration.pdf>
 
-use std::ptr::addr_of_mut;
+use std::{
+    fmt::{Debug, Display},
+    time::Duration,
+};
 
-use crate::{ffi, try_d3xx, D3xxError, Pipe, PipeType, Result};
+use crate::{ffi, util::microframe_interval, D3xxError, Device, Pipe, PipeType, Result};
 
 /// A USB device descriptor.
 ///
 /// There is one device descriptor provided by a D3XX device.
 /// This descriptor holds very basic information about the device, such as
 /// its identification, USB version, and function.
+#[derive(Clone)]
 pub struct DeviceDescriptor {
     /// The inner descriptor struct.
     ///
@@ -50,18 +54,37 @@ impl DeviceDescriptor {
     /// Panics in debug builds if the descriptor returned by the driver is invalid.
     /// This is intended for debugging purposes, and the behavior is likely to change
     /// in the future.
-    pub(crate) fn new(handle: ffi::FT_HANDLE) -> Result<Self> {
-        let mut inner = ffi::FT_DEVICE_DESCRIPTOR::default();
-        try_d3xx!(unsafe { ffi::FT_GetDeviceDescriptor(handle, addr_of_mut!(inner)) })?;
+    pub(crate) fn new(device: &Device) -> Result<Self> {
+        let inner = device.api().device_descriptor(device.ffi_handle())?;
         // The device descriptor has a particular format, so we can perform a sanity check here
         // to avoid reading from potentially invalid memory.
         // See pg. 5: https://ftdichip.com/wp-content/uploads/2020/08/TN_113_Simplified-Description-of-USB-Device-Enumeration.pdf
-        Ok(Self {
+        Ok(Self::from_parts(
             inner,
-            serial_number: descriptor_string(handle, inner.iSerialNumber)?,
-            manufacturer: descriptor_string(handle, inner.iManufacturer)?,
-            product: descriptor_string(handle, inner.iProduct)?,
-        })
+            descriptor_string(device, inner.iSerialNumber)?,
+            descriptor_string(device, inner.iManufacturer)?,
+            descriptor_string(device, inner.iProduct)?,
+        ))
+    }
+
+    /// Build a `DeviceDescriptor` from a raw descriptor and its strings.
+    pub(crate) fn from_parts(
+        inner: ffi::FT_DEVICE_DESCRIPTOR,
+        serial_number: String,
+        manufacturer: String,
+        product: String,
+    ) -> Self {
+        Self {
+            inner,
+            serial_number,
+            manufacturer,
+            product,
+        }
+    }
+
+    /// The raw descriptor returned by the driver.
+    pub(crate) fn inner(&self) -> &ffi::FT_DEVICE_DESCRIPTOR {
+        &self.inner
     }
 
     /// The device serial number.
@@ -121,11 +144,27 @@ impl DeviceDescriptor {
     }
 }
 
+impl Debug for DeviceDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceDescriptor")
+            .field("serial_number", &self.serial_number)
+            .field("manufacturer", &self.manufacturer)
+            .field("product", &self.product)
+            .field("vendor_id", &format_args!("{:#06x}", self.vendor_id()))
+            .field("product_id", &format_args!("{:#06x}", self.product_id()))
+            .field("usb_version", &self.usb_version())
+            .field("max_packet_size", &self.max_packet_size())
+            .field("class_codes", &self.class_codes())
+            .finish_non_exhaustive()
+    }
+}
+
 /// A USB interface descriptor for a [`Device`](crate::Device).
 ///
 /// There is one interface descriptor per interface. This descriptor holds
 /// information about the interface, such as its class codes, and information
 /// about the endpoints used by the interface.
+#[derive(Clone)]
 pub struct InterfaceDescriptor {
     /// The inner descriptor struct.
     ///
@@ -146,16 +185,17 @@ impl InterfaceDescriptor {
     /// Panics in debug builds if the descriptor returned by the driver is invalid.
     /// This is intended for debugging purposes, and the behavior is likely to change
     /// in the future.
-    pub(crate) fn new(handle: ffi::FT_HANDLE, index: u8) -> Result<Self> {
-        let mut inner = ffi::FT_INTERFACE_DESCRIPTOR::default();
-        try_d3xx!(unsafe { ffi::FT_GetInterfaceDescriptor(handle, index, addr_of_mut!(inner)) })?;
+    pub(crate) fn new(device: &Device, index: u8) -> Result<Self> {
+        let inner = device
+            .api()
+            .interface_descriptor(device.ffi_handle(), index)?;
         // The device descriptor has a particular format, so we can perform a sanity check here
         // to avoid reading from potentially invalid memory.
         //
         // See pg. 8: https://ftdichip.com/wp-content/uploads/2020/08/TN_113_Simplified-Description-of-USB-Device-Enumeration.pdf
         Ok(Self {
             inner,
-            description: descriptor_string(handle, inner.iInterface)?,
+            description: descriptor_string(device, inner.iInterface)?,
         })
     }
 
@@ -201,6 +241,18 @@ impl InterfaceDescriptor {
     }
 }
 
+impl Debug for InterfaceDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InterfaceDescriptor")
+            .field("interface_number", &self.interface_number())
+            .field("alternate_setting", &self.alternate_setting())
+            .field("class_codes", &self.class_codes())
+            .field("endpoints", &self.endpoints())
+            .field("description", &self.description)
+            .finish_non_exhaustive()
+    }
+}
+
 /// A USB configuration descriptor for a [`Device`](crate::Device)
 ///
 /// There is one configuration descriptor per configuration. This descriptor holds
@@ -210,6 +262,7 @@ impl InterfaceDescriptor {
 /// # Resources
 /// - <https://www.keil.com/pack/doc/mw/USB/html/_u_s_b__configuration__descriptor.html>
 /// - Page 7 of <https://ftdichip.com/wp-content/uploads/2020/08/TN_113_Simplified-Description-of-USB-Device-Enumeration.pdf>
+#[derive(Clone)]
 pub struct ConfigurationDescriptor {
     /// The inner descriptor struct.
     ///
@@ -230,13 +283,12 @@ impl ConfigurationDescriptor {
     /// Panics in debug builds if the descriptor returned by the driver is invalid.
     /// This is intended for debugging purposes, and the behavior is likely to change
     /// in the future.
-    pub(crate) fn new(handle: ffi::FT_HANDLE) -> Result<Self> {
-        let mut inner = ffi::FT_CONFIGURATION_DESCRIPTOR::default();
-        try_d3xx!(unsafe { ffi::FT_GetConfigurationDescriptor(handle, addr_of_mut!(inner)) })?;
+    pub(crate) fn new(device: &Device) -> Result<Self> {
+        let inner = device.api().configuration_descriptor(device.ffi_handle())?;
         // See pg. 7: https://ftdichip.com/wp-content/uploads/2020/08/TN_113_Simplified-Description-of-USB-Device-Enumeration.pdf
         Ok(Self {
             inner,
-            description: descriptor_string(handle, inner.iConfiguration)?,
+            description: descriptor_string(device, inner.iConfiguration)?,
         })
     }
 
@@ -280,6 +332,69 @@ impl ConfigurationDescriptor {
     }
 }
 
+impl Debug for ConfigurationDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigurationDescriptor")
+            .field("configuration_value", &self.configuration_value())
+            .field("interfaces", &self.interfaces())
+            .field("description", &self.description)
+            .field("max_power", &self.max_power())
+            .field("self_powered", &self.self_powered())
+            .field("remote_wakeup", &self.remote_wakeup())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The device, configuration, and interface descriptors of a [`Device`](crate::Device).
+///
+/// This is returned by [`Device::descriptors`](crate::Device::descriptors).
+#[derive(Debug, Clone)]
+pub struct DeviceDescriptors {
+    device: DeviceDescriptor,
+    configuration: ConfigurationDescriptor,
+    interfaces: Vec<InterfaceDescriptor>,
+}
+
+impl DeviceDescriptors {
+    /// Read every descriptor of the device.
+    ///
+    /// One interface descriptor is read for each interface reported by the
+    /// configuration descriptor.
+    pub(crate) fn new(
+        device: &Device,
+        device_descriptor: DeviceDescriptor,
+        configuration: ConfigurationDescriptor,
+    ) -> Result<Self> {
+        let interfaces = (0..configuration.inner.bNumInterfaces)
+            .map(|index| InterfaceDescriptor::new(device, index))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            device: device_descriptor,
+            configuration,
+            interfaces,
+        })
+    }
+
+    /// The device descriptor.
+    #[must_use]
+    pub fn device(&self) -> &DeviceDescriptor {
+        &self.device
+    }
+
+    /// The descriptor of the active configuration.
+    #[must_use]
+    pub fn configuration(&self) -> &ConfigurationDescriptor {
+        &self.configuration
+    }
+
+    /// The descriptors of every interface in the active configuration, ordered by
+    /// interface index.
+    #[must_use]
+    pub fn interfaces(&self) -> &[InterfaceDescriptor] {
+        &self.interfaces
+    }
+}
+
 // Bit flags for the `bmAttributes` field of a configuration descriptor.
 const CONFIGURATION_ATTRIBUTE_SELF_POWERED: u8 = 0b0100_0000;
 const CONFIGURATION_ATTRIBUTE_REMOTE_WAKEUP: u8 = 0b0010_0000;
@@ -287,6 +402,26 @@ const CONFIGURATION_ATTRIBUTE_REMOTE_WAKEUP: u8 = 0b0010_0000;
 /// Indicates the USB protocol version (e.g. USB 3.1)
 pub struct UsbVersion(usize);
 
+impl Debug for UsbVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UsbVersion({:#06x})", self.0)
+    }
+}
+
+impl Display for UsbVersion {
+    /// Formats the version as e.g. `3.1`, or `2.0.1` if there is a sub-minor version.
+    ///
+    /// The version is binary-coded decimal, with the minor and sub-minor versions in the
+    /// upper and lower nibbles of [`minor`](Self::minor).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:x}.{:x}", self.major(), self.minor() >> 4)?;
+        match self.minor() & 0xF {
+            0 => Ok(()),
+            sub_minor => write!(f, ".{sub_minor:x}"),
+        }
+    }
+}
+
 impl UsbVersion {
     /// Major version number.
     #[must_use]
@@ -311,6 +446,7 @@ impl UsbVersion {
 ///
 /// See for more information:
 /// <https://www.keil.com/pack/doc/mw/USB/html/_u_s_b__endpoint__descriptor.html>
+#[derive(Debug)]
 pub struct PipeInfo {
     pipe: Pipe,
     pipe_type: PipeType,
@@ -360,11 +496,27 @@ impl PipeInfo {
     pub fn interval(&self) -> u8 {
         self.interval
     }
+
+    /// The polling interval as a duration of time, if applicable to the `pipe_type`.
+    ///
+    /// For interrupt and isochronous pipes the interval is `2**(interval-1) * 125us`,
+    /// as is the case for high speed and super speed devices such as the FT60X. Raw values
+    /// outside of `1..=16` are clamped to that range.
+    ///
+    /// Control and bulk pipes are not polled periodically, so `None` is returned.
+    #[must_use]
+    pub fn interval_duration(&self) -> Option<Duration> {
+        match self.pipe_type {
+            PipeType::Interrupt | PipeType::Isochronous => Some(microframe_interval(self.interval)),
+            PipeType::Control | PipeType::Bulk => None,
+        }
+    }
 }
 
 /// Class code triple for a device or interface descriptor.
 ///
 /// Contains the class, subclass, and protocol codes.
+#[derive(Debug)]
 pub struct ClassCodes {
     class: u8,
     subclass: u8,
@@ -404,17 +556,25 @@ impl ClassCodes {
 ///
 /// It is important that `index` is valid, as unknown behavior may occur from
 /// attempting to read past the end of the descriptor table.
-fn descriptor_string(handle: ffi::FT_HANDLE, index: u8) -> Result<String> {
-    let mut descriptor = ffi::FT_STRING_DESCRIPTOR::default();
-    try_d3xx!(unsafe { ffi::FT_GetStringDescriptor(handle, index, addr_of_mut!(descriptor)) })?;
-    Ok(widestring::U16CStr::from_slice(&descriptor.szString)
-        .or(Err(D3xxError::OtherError))?
-        .to_string_lossy())
+fn descriptor_string(device: &Device, index: u8) -> Result<String> {
+    let descriptor = device.api().string_descriptor(device.ffi_handle(), index)?;
+    Ok(
+        widestring::U16CStr::from_slice_truncate(&descriptor.szString)
+            .or(Err(D3xxError::OtherError))?
+            .to_string_lossy(),
+    )
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{descriptor::PipeInfo, ffi, Pipe, PipeType};
+    use std::sync::Arc;
+
+    use crate::{
+        descriptor::{InterfaceDescriptor, PipeInfo},
+        ffi,
+        mock::{self, Call, MockApi},
+        D3xxError, Pipe, PipeType,
+    };
 
     #[test]
     fn pipe_info_try_from() {
@@ -429,6 +589,117 @@ mod test {
         assert_eq!(info.id(), Pipe::In0);
         assert_eq!(info.max_packet_size(), 64);
         assert_eq!(info.interval(), 0);
+        assert_eq!(info.interval_duration(), None);
+    }
+
+    #[test]
+    fn pipe_info_interval_duration() {
+        let info = ffi::FT_PIPE_INFORMATION {
+            PipeType: ffi::FT_PIPE_TYPE::FTPipeTypeInterrupt,
+            PipeId: 0x81,
+            MaximumPacketSize: 64,
+            Interval: 9,
+        };
+        let info = PipeInfo::new(ffi::FT_PIPE_INFORMATION {
+            PipeId: 0x82,
+            ..info
+        })
+        .unwrap();
+        assert_eq!(
+            info.interval_duration(),
+            Some(std::time::Duration::from_millis(32))
+        );
+    }
+
+    #[test]
+    fn pipe_info_debug() {
+        let info = PipeInfo::new(ffi::FT_PIPE_INFORMATION {
+            PipeType: ffi::FT_PIPE_TYPE::FTPipeTypeBulk,
+            PipeId: 0x02,
+            MaximumPacketSize: 1024,
+            Interval: 0,
+        })
+        .unwrap();
+        let debug = format!("{info:?}");
+        assert!(debug.contains("pipe: Out0"));
+        assert!(debug.contains("pipe_type: Bulk"));
+        assert!(debug.contains("max_packet_size: 1024"));
+    }
+
+    #[test]
+    fn device_descriptor_debug() {
+        let mut inner: ffi::FT_DEVICE_DESCRIPTOR = unsafe { std::mem::zeroed() };
+        inner.idVendor = 0x0403;
+        inner.idProduct = 0x601F;
+        inner.bcdUSB = 0x0310;
+        let descriptor = super::DeviceDescriptor::from_parts(
+            inner,
+            "000000000001".to_owned(),
+            "FTDI".to_owned(),
+            "FT601".to_owned(),
+        );
+        let debug = format!("{descriptor:?}");
+        assert!(debug.contains("serial_number: \"000000000001\""));
+        assert!(debug.contains("vendor_id: 0x0403, product_id: 0x601f"));
+        assert!(debug.contains("usb_version: UsbVersion(0x0310)"));
+        assert!(!debug.contains("inner"));
+    }
+
+    #[test]
+    fn device_descriptors() {
+        let api = Arc::new(MockApi::default());
+        api.set_device_descriptor(ffi::FT_DEVICE_DESCRIPTOR {
+            idVendor: 0x0403,
+            iSerialNumber: 3,
+            ..Default::default()
+        });
+        api.set_configuration_descriptor(ffi::FT_CONFIGURATION_DESCRIPTOR {
+            bNumInterfaces: 2,
+            ..Default::default()
+        });
+        api.set_string(3, "000000000001");
+        let device = mock::device(&api);
+
+        let descriptors = device.descriptors().unwrap();
+        assert_eq!(descriptors.device().serial_number(), "000000000001");
+        assert_eq!(descriptors.device().vendor_id(), 0x0403);
+        assert_eq!(descriptors.configuration().interfaces(), 2);
+        let numbers: Vec<_> = descriptors
+            .interfaces()
+            .iter()
+            .map(InterfaceDescriptor::interface_number)
+            .collect();
+        assert_eq!(numbers, [0, 1]);
+        let interface_calls: Vec<_> = api
+            .calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::InterfaceDescriptor { .. }))
+            .collect();
+        assert_eq!(
+            interface_calls,
+            [
+                Call::InterfaceDescriptor { index: 0 },
+                Call::InterfaceDescriptor { index: 1 }
+            ]
+        );
+    }
+
+    #[test]
+    fn device_descriptors_error() {
+        let api = Arc::new(MockApi::default());
+        api.set_configuration_descriptor(ffi::FT_CONFIGURATION_DESCRIPTOR {
+            bNumInterfaces: 2,
+            ..Default::default()
+        });
+        api.fail_when(
+            |call| *call == Call::InterfaceDescriptor { index: 1 },
+            D3xxError::DeviceNotConnected,
+        );
+        let device = mock::device(&api);
+        assert_eq!(
+            device.descriptors().err(),
+            Some(D3xxError::DeviceNotConnected)
+        );
     }
 
     #[test]
@@ -453,5 +724,10 @@ mod test {
         let version = super::UsbVersion(0x0210);
         assert_eq!(version.major(), 2);
         assert_eq!(version.minor(), 16);
+        assert_eq!(version.to_string(), "2.1");
+
+        assert_eq!(super::UsbVersion(0x0200).to_string(), "2.0");
+        assert_eq!(super::UsbVersion(0x0310).to_string(), "3.1");
+        assert_eq!(super::UsbVersion(0x0201).to_string(), "2.0.1");
     }
 }
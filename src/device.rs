@@ -1,22 +1,47 @@
 use std::{
+    cell::{Cell, OnceCell},
     ffi::{c_void, CString},
     fmt::Debug,
     marker::PhantomData,
     mem::ManuallyDrop,
-    panic::UnwindSafe,
-    ptr::addr_of_mut,
+    panic::{AssertUnwindSafe, UnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
 };
 
 use crate::{
-    descriptor::{ConfigurationDescriptor, DeviceDescriptor, InterfaceDescriptor},
-    ffi::{self, with_global_lock},
-    gpio::{Gpio, GpioPin},
-    notification::{clear_notification_callback, set_notification_callback, Notification},
-    try_d3xx,
-    util::PhantomUnsync,
-    Pipe, PipeIo, Result, Version,
+    descriptor::{
+        ConfigurationDescriptor, DeviceDescriptor, DeviceDescriptors, InterfaceDescriptor,
+    },
+    ffi::{
+        self,
+        api::{instrument, D3xxApi, Ffi},
+        guarded::Guarded,
+        with_global_lock,
+    },
+    gpio::{Gpio, GpioPin, GpioState, Gpios},
+    notification::{set_notification_callback, Notification, NotificationHandle},
+    pipe::PipeState,
+    scan::list_devices_with,
+    util::{timeout_millis, PhantomUnsync},
+    AbortPolicy, D3xxError, DeviceInfo, Pipe, PipeIo, Result, Version, Versions,
 };
 
+/// How a device is identified when it is opened with [`Device::open_with`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OpenBy {
+    /// The device's serial number. UTF-8 is supported.
+    Serial(String),
+    /// The device's description (product string).
+    Description(String),
+    /// The device's index in the driver's device list, which corresponds to its position
+    /// in the list returned by [`list_devices`](crate::list_devices).
+    Index(usize),
+}
+
 /// This struct acts as a handle to a D3XX device, and the primary interface for all operations.
 ///
 /// Once a device is opened with [`Device::open`], it is possible to perform operations such as
@@ -57,10 +82,26 @@ use crate::{
 ///     .write(&buf)
 ///     .unwrap();
 /// ```
-#[derive(Debug)]
 pub struct Device {
     /// Handle returned by the D3XX driver when the device is opened.
     handle: ffi::FT_HANDLE,
+    /// Driver calls made on behalf of the device are dispatched through this.
+    api: Arc<dyn D3xxApi>,
+    /// Whether pending transfers are aborted before the device is closed.
+    abort_on_close: bool,
+    /// Which transfer errors abort a pipe, unless overridden by the [`PipeIo`].
+    abort_policy: AbortPolicy,
+    /// Device descriptor, read on first use.
+    descriptor: OnceCell<DeviceDescriptor>,
+    /// Configuration descriptor, read on first use.
+    configuration_descriptor: OnceCell<ConfigurationDescriptor>,
+    /// Context of the installed notification callback, owned by its `NotificationHandle`.
+    notification_context: Cell<*mut c_void>,
+    /// State kept between calls for each pipe, indexed by [`Pipe::index`].
+    pipe_states: Cell<[PipeState; 8]>,
+    /// Whether a driver call has reported the handle as unusable; shared with the
+    /// [`Guarded`] wrapper around `api`.
+    poisoned: Arc<AtomicBool>,
     /// Used to force `!Sync` since the driver may or may not be thread-safe.
     _unsync: PhantomUnsync,
 }
@@ -74,7 +115,9 @@ impl Device {
     /// method.
     ///
     /// The serial number must be convertible to a [`CString`], and must not contain
-    /// any internal null bytes.
+    /// any internal null bytes. Otherwise [`D3xxError::InvalidArgs`] is returned.
+    ///
+    /// This is equivalent to [`Device::open_with`] with [`OpenBy::Serial`].
     ///
     /// # Example
     ///
@@ -83,31 +126,99 @@ impl Device {
     ///
     /// let device = Device::open("ABC123").unwrap();
     /// ```
+    pub fn open(serial_number: &str) -> Result<Self> {
+        Self::open_with(OpenBy::Serial(serial_number.to_owned()))
+    }
+
+    /// Open a device identified in one of the ways supported by the driver.
     ///
-    /// # Panics
+    /// Fails with [`D3xxError::InvalidArgs`] if a serial number or description contains
+    /// an internal null byte.
     ///
-    /// Panics if `serial_number` contains an internal null byte.
-    pub fn open(serial_number: &str) -> Result<Self> {
-        let serial_cstr =
-            CString::new(serial_number).expect("serial number is not a valid C string");
-        let handle = with_global_lock(|| {
-            let mut handle: ffi::FT_HANDLE = std::ptr::null_mut();
-            try_d3xx!(unsafe {
-                ffi::FT_Create(
-                    serial_cstr.as_ptr() as *mut c_void,
+    /// # Example
+    ///
+    /// ```no_run
+    /// use d3xx::{Device, OpenBy};
+    ///
+    /// let device = Device::open_with(OpenBy::Description("FTDI SuperSpeed-FIFO Bridge".into()))?;
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    pub fn open_with(open_by: OpenBy) -> Result<Self> {
+        Self::open_by(Arc::new(Ffi), open_by)
+    }
+
+    /// Open the first connected device which is not already open.
+    ///
+    /// This is a convenience for tools which work with a single device. The devices are
+    /// enumerated like [`list_devices`](crate::list_devices), with the
+    /// [global lock](crate::ffi::with_global_lock) held, and the first one not reported
+    /// as open (by this process or another) is opened by its serial number. Devices
+    /// which are already open are skipped, since opening them would fail.
+    ///
+    /// Returns [`D3xxError::DeviceNotFound`] if no device is available.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use d3xx::Device;
+    ///
+    /// let device = Device::open_first()?;
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    pub fn open_first() -> Result<Self> {
+        Self::open_first_with(Arc::new(Ffi))
+    }
+
+    /// Open the first available device using the given driver API.
+    pub(crate) fn open_first_with(api: Arc<dyn D3xxApi>) -> Result<Self> {
+        let info = first_available(list_devices_with(&*instrument(Arc::clone(&api)))?)?;
+        Self::open_by(api, OpenBy::Serial(info.serial_number().to_owned()))
+    }
+
+    /// Open a device using the given driver API.
+    pub(crate) fn open_by(api: Arc<dyn D3xxApi>, open_by: OpenBy) -> Result<Self> {
+        #[cfg(feature = "log")]
+        log::trace!("opening device by {open_by:?}");
+        let to_cstring = |s: &str| CString::new(s).or(Err(D3xxError::InvalidArgs));
+        match open_by {
+            OpenBy::Serial(serial_number) => {
+                let serial_number = to_cstring(&serial_number)?;
+                Self::create(
+                    api,
+                    serial_number.as_ptr() as *mut c_void,
                     ffi::FT_OPEN_BY_SERIAL_NUMBER,
-                    &mut handle,
                 )
-            })
-            .and(Ok(handle))
-        })?;
+            }
+            OpenBy::Description(description) => {
+                let description = to_cstring(&description)?;
+                Self::create(
+                    api,
+                    description.as_ptr() as *mut c_void,
+                    ffi::FT_OPEN_BY_DESCRIPTION,
+                )
+            }
+            // The driver expects the index in place of the pointer.
+            OpenBy::Index(index) => Self::create(api, index as ffi::PVOID, ffi::FT_OPEN_BY_INDEX),
+        }
+    }
+
+    /// Open a device with `FT_Create` using the given driver API.
+    ///
+    /// `arg` is interpreted according to the `FT_OPEN_BY_*` value in `flags`.
+    pub(crate) fn create(
+        api: Arc<dyn D3xxApi>,
+        arg: ffi::PVOID,
+        flags: ffi::ULONG,
+    ) -> Result<Self> {
+        let api = instrument(api);
+        let handle = with_global_lock(AssertUnwindSafe(|| api.create(arg, flags)))?;
 
         if handle.is_null() {
             Err(crate::D3xxError::DeviceNotFound)
         } else {
             // SAFETY: the handle is logically valid if the device was opened
             // successfully, and is not in use elsewhere.
-            Ok(unsafe { Self::with_handle(handle) })
+            Ok(unsafe { Self::from_parts(handle, api) })
         }
     }
 
@@ -118,8 +229,31 @@ impl Device {
     /// The handle must be valid, already opened, and not in use elsewhere for the duration
     /// of the `Device` instance's lifetime.
     pub unsafe fn with_handle(handle: ffi::FT_HANDLE) -> Self {
+        Self::with_api(handle, Arc::new(Ffi))
+    }
+
+    /// Wrap a handle obtained from the given driver API.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Device::with_handle`], with the handle belonging to `api`.
+    pub(crate) unsafe fn with_api(handle: ffi::FT_HANDLE, api: Arc<dyn D3xxApi>) -> Self {
+        Self::from_parts(handle, instrument(api))
+    }
+
+    /// Wrap a handle obtained from an already [instrumented](instrument) driver API.
+    unsafe fn from_parts(handle: ffi::FT_HANDLE, api: Arc<dyn D3xxApi>) -> Self {
+        let poisoned = Arc::new(AtomicBool::new(false));
         Self {
             handle,
+            api: Arc::new(Guarded::new(api, Arc::clone(&poisoned))),
+            abort_on_close: true,
+            abort_policy: AbortPolicy::default(),
+            descriptor: OnceCell::new(),
+            configuration_descriptor: OnceCell::new(),
+            notification_context: Cell::new(std::ptr::null_mut()),
+            pipe_states: Cell::new([PipeState::default(); 8]),
+            poisoned,
             _unsync: PhantomData,
         }
     }
@@ -127,9 +261,19 @@ impl Device {
     /// Get the device's handle.
     ///
     /// The handle is fairly useless on its own. Although not recommended for typical
-    /// users, it may be used with the raw D3XX bindings in the [ffi] module.
+    /// users, it may be used with the raw D3XX bindings in the [ffi] module. The returned
+    /// [`DeviceHandle`] borrows the device, so it cannot be used after the device is
+    /// closed; use [`into_handle`](Self::into_handle) to take ownership of the handle.
     #[must_use]
-    pub fn handle(&self) -> ffi::FT_HANDLE {
+    pub fn handle(&self) -> DeviceHandle<'_> {
+        DeviceHandle {
+            handle: self.handle,
+            _device: PhantomData,
+        }
+    }
+
+    /// The raw handle, for passing to the driver API within this crate.
+    pub(crate) fn ffi_handle(&self) -> ffi::FT_HANDLE {
         self.handle
     }
 
@@ -143,24 +287,255 @@ impl Device {
     /// users, it may be used with the raw D3XX bindings in the [ffi] module.
     #[must_use]
     pub fn into_handle(self) -> ffi::FT_HANDLE {
+        self.into_parts().0
+    }
+
+    /// Close the device, consuming `self`.
+    ///
+    /// Dropping a device also closes it, but ignores any error. This method instead
+    /// returns the status reported by the driver. The device is not closed a second
+    /// time when `self` goes out of scope, even if closing fails.
+    ///
+    /// Unless disabled with [`Device::set_abort_on_close`], pending transfers on every
+    /// pipe are aborted before the device is closed. Errors from aborting are ignored,
+    /// since not every pipe exists under every channel configuration.
+    pub fn close(self) -> Result<()> {
+        let abort = self.abort_on_close;
+        let (handle, api) = self.into_parts();
+        close_handle(&*api, handle, abort)
+    }
+
+    /// Set whether pending transfers are aborted before the device is closed.
+    ///
+    /// This is enabled by default, as recommended by the D3XX Programmer's Guide, and
+    /// applies both to [`Device::close`] and to dropping the device. When enabled,
+    /// [`FT_AbortPipe`](ffi::FT_AbortPipe) is called for every [`Pipe`] and only then
+    /// is the device closed, all while holding the [global lock](ffi::with_global_lock).
+    pub fn set_abort_on_close(&mut self, abort: bool) {
+        self.abort_on_close = abort;
+    }
+
+    /// Set which transfer errors abort the pipe they occurred on.
+    ///
+    /// This applies to every [`PipeIo`] created afterwards, unless overridden with
+    /// [`PipeIo::with_abort_policy`]. By default every error aborts the pipe; see
+    /// [`AbortPolicy`].
+    pub fn set_abort_policy(&mut self, policy: AbortPolicy) {
+        self.abort_policy = policy;
+    }
+
+    /// Get the policy deciding which transfer errors abort a pipe.
+    #[must_use]
+    pub fn abort_policy(&self) -> AbortPolicy {
+        self.abort_policy
+    }
+
+    /// Check whether the device handle is still usable.
+    ///
+    /// Once a driver call fails with [`D3xxError::InvalidHandle`] or
+    /// [`D3xxError::DeviceNotConnected`], issuing further calls on the handle is
+    /// pointless at best. The device is then considered unhealthy, and every later
+    /// operation fails with [`D3xxError::DeviceNotConnected`] without calling the driver,
+    /// except for those releasing resources: closing the device, releasing overlapped
+    /// structures, and clearing the notification callback.
+    ///
+    /// Typically the device should be closed and opened again. If the handle is known
+    /// to be usable again, e.g. before a [`reset`](Self::reset), use
+    /// [`clear_poison`](Self::clear_poison).
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        !self.poisoned.load(Ordering::Acquire)
+    }
+
+    /// Mark the device as healthy again, allowing driver calls to be made on its handle.
+    ///
+    /// See [`is_healthy`](Self::is_healthy).
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Release);
+    }
+
+    /// Split the device into its handle and API without running the destructor.
+    fn into_parts(self) -> (ffi::FT_HANDLE, Arc<dyn D3xxApi>) {
         let device = ManuallyDrop::new(self);
-        device.handle
+        // SAFETY: `device` is not used again, so the API is moved out exactly once.
+        let api = unsafe { std::ptr::read(&device.api) };
+        // SAFETY: as above.
+        drop(unsafe { std::ptr::read(&device.descriptor) });
+        // SAFETY: as above.
+        drop(unsafe { std::ptr::read(&device.configuration_descriptor) });
+        // SAFETY: as above.
+        drop(unsafe { std::ptr::read(&device.poisoned) });
+        (device.handle, api)
+    }
+
+    /// Get the device's handle as a [`RawHandle`].
+    ///
+    /// Ownership of the handle is retained by the device.
+    #[must_use]
+    pub fn as_raw_handle(&self) -> RawHandle {
+        RawHandle(self.handle)
+    }
+
+    /// Release ownership of the device's handle, consuming `self`.
+    ///
+    /// The device is not closed. Ownership may be handed back using
+    /// [`Device::from_raw_handle`], or the handle may be closed manually using
+    /// [`ffi::FT_Close`].
+    #[must_use]
+    pub fn into_raw_handle(self) -> RawHandle {
+        RawHandle(self.into_handle())
+    }
+
+    /// Take ownership of a handle, such as one obtained from [`Device::into_raw_handle`]
+    /// or from foreign code.
+    ///
+    /// This is equivalent to [`Device::with_handle`]. The device will be closed when
+    /// the returned `Device` is dropped.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be valid, already opened, and not in use elsewhere for the duration
+    /// of the `Device` instance's lifetime. Ownership must not be taken more than once.
+    #[must_use]
+    pub unsafe fn from_raw_handle(handle: RawHandle) -> Self {
+        Self::with_handle(handle.0)
+    }
+
+    /// The driver API used by this device.
+    pub(crate) fn api(&self) -> &dyn D3xxApi {
+        &*self.api
+    }
+
+    /// Update the state kept for `pipe`, returning the result of `f`.
+    pub(crate) fn update_pipe_state<R>(
+        &self,
+        pipe: Pipe,
+        f: impl FnOnce(&mut PipeState) -> R,
+    ) -> R {
+        let mut states = self.pipe_states.get();
+        let result = f(&mut states[pipe.index()]);
+        self.pipe_states.set(states);
+        result
     }
 
     /// Get the USB device descriptor.
     ///
     /// The device descriptor contains information such as identifiers, device class,
     /// versions, and more.
+    ///
+    /// The descriptor is read from the device on first use and cached afterwards, so it
+    /// is a snapshot. Changes made with `set_chip_configuration` only take effect once the
+    /// device is re-enumerated. Call [`refresh_descriptors`](Self::refresh_descriptors)
+    /// to read it again.
     pub fn device_descriptor(&self) -> Result<DeviceDescriptor> {
-        DeviceDescriptor::new(self.handle)
+        self.cached_device_descriptor().cloned()
+    }
+
+    /// Get the vendor and product IDs of the device from the driver.
+    ///
+    /// This calls `FT_GetVIDPID`, which is available on every platform and always queries
+    /// the driver, unlike [`vendor_id`](Self::vendor_id) and
+    /// [`product_id`](Self::product_id) which use the cached device descriptor.
+    ///
+    /// There is no corresponding setter: the D3XX library this crate links against does
+    /// not export `FT_SetVIDPID` on any platform, so the identifiers a device enumerates
+    /// with can only be changed through its chip configuration.
+    pub fn vid_pid(&self) -> Result<(u16, u16)> {
+        self.api.vid_pid(self.handle)
+    }
+
+    /// Get the vendor ID of the device.
+    ///
+    /// This is read from the cached [device descriptor](Self::device_descriptor).
+    pub fn vendor_id(&self) -> Result<u16> {
+        Ok(self.cached_device_descriptor()?.inner().idVendor)
+    }
+
+    /// Get the product ID of the device.
+    ///
+    /// This is read from the cached [device descriptor](Self::device_descriptor).
+    pub fn product_id(&self) -> Result<u16> {
+        Ok(self.cached_device_descriptor()?.inner().idProduct)
+    }
+
+    /// Get the serial number of the device.
+    ///
+    /// This is read from the cached [device descriptor](Self::device_descriptor).
+    pub fn serial_number(&self) -> Result<&str> {
+        Ok(self.cached_device_descriptor()?.serial_number())
+    }
+
+    /// Get a key identifying the device, e.g. for use in a `HashMap`.
+    ///
+    /// The key is based on the [serial number](Self::serial_number), so it is only read
+    /// from the device once, and keys for the same device compare equal across handles
+    /// and reconnections. It also matches the key of the device's [`DeviceInfo`], see
+    /// [`DeviceInfo::key`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::collections::HashMap;
+    ///
+    /// use d3xx::Device;
+    ///
+    /// let device = Device::open("ABC123")?;
+    /// let mut transferred = HashMap::new();
+    /// *transferred.entry(device.key()?).or_insert(0) += 1024;
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    pub fn key(&self) -> Result<DeviceKey> {
+        Ok(DeviceKey::new(self.serial_number()?))
+    }
+
+    /// Check whether the device is connected at USB 3 (super speed).
+    ///
+    /// The USB version in the [device descriptor](Self::device_descriptor) reflects the
+    /// link that was negotiated; a device on a USB 2 port reports version 2.x. This gives
+    /// a confirmation after opening that the device is running at super speed.
+    pub fn is_usb3(&self) -> Result<bool> {
+        Ok(self.cached_device_descriptor()?.usb_version().major() >= 3)
+    }
+
+    /// Discard the cached device and configuration descriptors.
+    ///
+    /// They are read from the device again the next time they are needed.
+    pub fn refresh_descriptors(&mut self) {
+        self.descriptor.take();
+        self.configuration_descriptor.take();
+    }
+
+    fn cached_device_descriptor(&self) -> Result<&DeviceDescriptor> {
+        cached(&self.descriptor, || DeviceDescriptor::new(self))
+    }
+
+    /// Get the [`DeviceInfo`] for this device.
+    ///
+    /// The information is built from the [device descriptor](Self::device_descriptor)
+    /// rather than by re-enumerating every connected device. The descriptor does not
+    /// identify the chip type or location, so [`DeviceInfo::device_type`] is always
+    /// [`DeviceType::Unknown`](crate::DeviceType::Unknown) and
+    /// [`DeviceInfo::location_id`] is zero. Use [`list_devices`](crate::list_devices) if
+    /// either is needed.
+    pub fn info(&self) -> Result<DeviceInfo> {
+        Ok(DeviceInfo::from_descriptor(
+            &self.device_descriptor()?,
+            self.handle,
+        ))
     }
 
     /// Get the configuration descriptor.
     ///
     /// The configuration descriptor contains information about the device's configuration,
     /// power requirements, and more.
+    ///
+    /// Like the [device descriptor](Self::device_descriptor), this is cached after it is
+    /// first read.
     pub fn configuration_descriptor(&self) -> Result<ConfigurationDescriptor> {
-        ConfigurationDescriptor::new(self.handle)
+        cached(&self.configuration_descriptor, || {
+            ConfigurationDescriptor::new(self)
+        })
+        .cloned()
     }
 
     /// Get the interface descriptor for the given interface.
@@ -171,7 +546,22 @@ impl Device {
     /// The interface number must correspond to a valid interface under the current
     /// configuration.
     pub fn interface_descriptor(&self, interface: u8) -> Result<InterfaceDescriptor> {
-        InterfaceDescriptor::new(self.handle, interface)
+        InterfaceDescriptor::new(self, interface)
+    }
+
+    /// Get the device descriptor, the configuration descriptor, and the descriptor of
+    /// every interface in the configuration.
+    ///
+    /// The number of interfaces is taken from the configuration descriptor. The device
+    /// and configuration descriptors are cached as described for
+    /// [`device_descriptor`](Self::device_descriptor), while the interface descriptors
+    /// are always read from the device.
+    pub fn descriptors(&self) -> Result<DeviceDescriptors> {
+        DeviceDescriptors::new(
+            self,
+            self.device_descriptor()?,
+            self.configuration_descriptor()?,
+        )
     }
 
     /// Get the chip configuration.
@@ -181,7 +571,54 @@ impl Device {
     /// the various descriptors.
     #[cfg(feature = "config")]
     pub fn chip_configuration(&self) -> Result<crate::configuration::ChipConfiguration> {
-        crate::configuration::ChipConfiguration::new(self.handle)
+        crate::configuration::ChipConfiguration::new(self.api.chip_configuration(self.handle)?)
+    }
+
+    /// Read the chip configuration as the exact bytes reported by the device.
+    ///
+    /// This calls `FT_GetChipConfiguration` like [`chip_configuration`](Self::chip_configuration),
+    /// but returns the `FT_60XCONFIGURATION` structure without decoding it, which is useful
+    /// for storing a fingerprint of the configuration. The size and layout follow the
+    /// structure in the D3XX headers; see
+    /// [`ChipConfiguration::from_bytes`](crate::configuration::ChipConfiguration::from_bytes).
+    #[cfg(feature = "config")]
+    pub fn chip_configuration_raw(
+        &self,
+    ) -> Result<[u8; crate::configuration::CHIP_CONFIGURATION_SIZE]> {
+        Ok(crate::configuration::encode(
+            &self.api.chip_configuration(self.handle)?,
+        ))
+    }
+
+    /// Write a chip configuration to the device.
+    ///
+    /// The configuration is typically obtained from [`Device::chip_configuration`] and then
+    /// modified. Fields which are not exposed by [`ChipConfiguration`](crate::configuration::ChipConfiguration)
+    /// are written back unchanged.
+    ///
+    /// The device re-enumerates after its configuration is changed, so this handle will
+    /// no longer be usable. The device should be dropped and opened again.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use d3xx::Device;
+    ///
+    /// let device = Device::open("ABC123").unwrap();
+    /// let mut config = device.chip_configuration().unwrap();
+    /// config.set_interrupt_latency(4);
+    /// device.set_chip_configuration(&config).unwrap();
+    /// drop(device);
+    ///
+    /// let device = Device::open("ABC123").unwrap();
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn set_chip_configuration(
+        &self,
+        config: &crate::configuration::ChipConfiguration,
+    ) -> Result<()> {
+        self.api
+            .set_chip_configuration(self.handle, &config.to_ffi())
     }
 
     /// Returns a [`Pipe`] for pipe I/O and configuration.
@@ -202,10 +639,91 @@ impl Device {
     ///     .unwrap();
     /// ```
     #[must_use]
-    pub fn pipe(&self, id: Pipe) -> PipeIo {
+    pub fn pipe(&self, id: Pipe) -> PipeIo<'_> {
         PipeIo::new(self, id)
     }
 
+    /// Returns a [`PipeIo`] for pipe I/O which rejects transfers in the wrong direction.
+    ///
+    /// Reading from an OUT pipe or writing to an IN pipe fails with
+    /// [`D3xxError::InvalidArgs`] instead of being passed to the driver. See
+    /// [`PipeIo::new_checked`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::io::Read;
+    /// use d3xx::{Device, Pipe};
+    ///
+    /// let device = Device::open("ABC123").unwrap();
+    ///
+    /// // Reading from an output pipe is rejected
+    /// let mut buf = vec![0u8; 1024];
+    /// assert!(device.pipe_checked(Pipe::Out1).read(&mut buf).is_err());
+    /// ```
+    #[must_use]
+    pub fn pipe_checked(&self, id: Pipe) -> PipeIo<'_> {
+        PipeIo::new_checked(self, id)
+    }
+
+    /// Set the timeout of all eight pipes.
+    ///
+    /// The driver's default timeout blocks transfers almost indefinitely, so this is
+    /// useful to call right after opening a device. See [`PipeIo::set_timeout`].
+    ///
+    /// The timeout is rounded up to a whole number of milliseconds, saturating at
+    /// `u32::MAX`. A zero duration is passed through as zero, which the driver treats
+    /// as no timeout.
+    ///
+    /// Every pipe is attempted even if setting an earlier one fails, and the first
+    /// error is returned.
+    pub fn set_all_pipe_timeouts(&self, timeout: Duration) -> Result<()> {
+        let millis = timeout_millis(timeout);
+        Pipe::all()
+            .into_iter()
+            .map(|pipe| self.pipe(pipe).set_timeout(millis))
+            .fold(Ok(()), Result::and)
+    }
+
+    /// List the pipes which are usable under the device's current configuration.
+    ///
+    /// With the `config` feature, the pipes are derived from the
+    /// [`ChannelConfiguration`](crate::configuration::ChannelConfiguration) in the chip
+    /// configuration. Otherwise each pipe is probed by reading its
+    /// [descriptor](PipeIo::descriptor), and is considered usable if that succeeds. Since
+    /// every configuration has at least one pipe, the error of the last probe is returned
+    /// if none succeed.
+    ///
+    /// The pipes are returned in the order of [`Pipe::all`].
+    pub fn active_pipes(&self) -> Result<Vec<Pipe>> {
+        #[cfg(feature = "config")]
+        {
+            let config = self.chip_configuration()?;
+            let channels = config.data_transfer().channel_config();
+            let active = [channels.in_pipes(), channels.out_pipes()].concat();
+            Ok(Pipe::all()
+                .into_iter()
+                .filter(|pipe| active.contains(pipe))
+                .collect())
+        }
+        #[cfg(not(feature = "config"))]
+        {
+            let mut active = Vec::new();
+            let mut error = D3xxError::OtherError;
+            for pipe in Pipe::all() {
+                match self.pipe(pipe).descriptor() {
+                    Ok(_) => active.push(pipe),
+                    Err(e) => error = e,
+                }
+            }
+            if active.is_empty() {
+                Err(error)
+            } else {
+                Ok(active)
+            }
+        }
+    }
+
     /// Returns a [`Gpio`] for GPIO pin I/O and configuration.
     ///
     /// # Example
@@ -222,27 +740,74 @@ impl Device {
     ///    .unwrap()
     /// ```
     #[must_use]
-    pub fn gpio(&self, pin: GpioPin) -> Gpio {
+    pub fn gpio(&self, pin: GpioPin) -> Gpio<'_> {
         Gpio::new(self, pin)
     }
 
+    /// Returns a [`Gpios`] for I/O and configuration of both GPIO pins at once.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use d3xx::Device;
+    ///
+    /// let device = Device::open("ABC123").unwrap();
+    ///
+    /// // Drive both GPIO pins high
+    /// device
+    ///    .gpios()
+    ///    .write(0b11, 0b11)
+    ///    .unwrap()
+    /// ```
+    #[must_use]
+    pub fn gpios(&self) -> Gpios<'_> {
+        Gpios::new(self)
+    }
+
+    /// Read the levels of both GPIO pins with a single driver call.
+    ///
+    /// See [`GpioState`] for details.
+    pub fn gpio_state(&self) -> Result<GpioState> {
+        self.gpios().state()
+    }
+
     /// Get the D3XX driver version.
+    ///
+    /// Unlike [`library_version`](crate::library_version), this requires an open device:
+    /// `FT_GetDriverVersion` queries the kernel driver bound to a particular device, so
+    /// there is no version to report without a handle.
     pub fn driver_version(&self) -> Result<Version> {
-        let mut version: u32 = 0;
-        try_d3xx!(unsafe { ffi::FT_GetDriverVersion(self.handle, addr_of_mut!(version)) })?;
-        Ok(Version(version))
+        self.api.driver_version(self.handle).map(Version)
+    }
+
+    /// Get the D3XX library version together with the driver version of this device.
+    pub fn versions(&self) -> Result<Versions> {
+        Ok(Versions {
+            library: Version(self.api.library_version()?),
+            driver: self.driver_version()?,
+        })
+    }
+
+    /// Reset the device port without closing the device.
+    ///
+    /// This issues a USB port reset through `FT_ResetDevicePort`. Unlike
+    /// [`power_cycle_port`](Self::power_cycle_port), the device is not re-enumerated and
+    /// the handle remains valid afterwards, making this a lighter way to recover from a
+    /// stalled device. Any data queued in the driver should be considered lost.
+    pub fn reset(&self) -> Result<()> {
+        self.api.reset_device_port(self.handle)
     }
 
     /// Power cycle the device port, causing the device to be re-enumerated by the host.
     ///
     /// This method consumes `self`, as the device is closed when the port is cycled.
     /// After a short delay the device will be re-enumerated and may be opened again.
+    /// Use [`reset`](Self::reset) to reset the port while keeping the device open.
     pub fn power_cycle_port(self) -> Result<()> {
         // No need to run the destructor since the device will be closed when
         // the port is cycled.
-        let handle = self.into_handle();
-        try_d3xx!(unsafe { ffi::FT_CycleDevicePort(handle) })?;
-        Ok(())
+        let (handle, api) = self.into_parts();
+        api.cycle_device_port(handle)
     }
 
     /// Get the USB selective suspend timeout in milliseconds.
@@ -250,11 +815,11 @@ impl Device {
     /// Selective suspend is a power-saving feature that allows the host to power-down
     /// individual USB devices if no I/O requests have been made for a certain period
     /// of time. The device will be powered-up when an I/O request is made.
-    #[cfg(windows)]
+    ///
+    /// Selective suspend is managed by the Windows driver only. On other platforms this
+    /// fails with [`D3xxError::NotSupported`](crate::D3xxError::NotSupported).
     pub fn suspend_timeout(&self) -> Result<u32> {
-        let mut timeout: u32 = 0;
-        try_d3xx!(unsafe { ffi::FT_GetSuspendTimeout(self.handle, addr_of_mut!(timeout)) })?;
-        Ok(timeout)
+        self.api.suspend_timeout(self.handle)
     }
 
     /// Set the USB selective suspend timeout.
@@ -265,11 +830,12 @@ impl Device {
     /// Selective suspend is a power-saving feature that allows the host to power-down
     /// individual USB devices if no I/O requests have been made for a certain period
     /// of time. The device will be powered-up when an I/O request is made.
-    #[cfg(windows)]
+    ///
+    /// Selective suspend is managed by the Windows driver only. On other platforms this
+    /// fails with [`D3xxError::NotSupported`](crate::D3xxError::NotSupported).
     pub fn set_suspend_timeout(&self, timeout: Option<u32>) -> Result<()> {
-        let timeout = timeout.unwrap_or(0);
-        try_d3xx!(unsafe { ffi::FT_SetSuspendTimeout(self.handle, timeout) })?;
-        Ok(())
+        self.api
+            .set_suspend_timeout(self.handle, timeout.unwrap_or(0))
     }
 
     /// Set the notification callback.
@@ -288,45 +854,258 @@ impl Device {
     /// If the closure panics it will be caught and printed to stderr. It is still recommended
     /// to avoid panicking in the closure if possible.
     ///
-    /// # Memory Leaks
+    /// # Lifetime
     ///
-    /// It is unknown whether the D3XX driver is responsible for freeing the memory allocated
-    /// for the context when the callback is cleared because the documentation does not specify this.
-    /// For now it is assumed that the memory is released when the callback is cleared/changed.
-    /// Until this is confirmed, it is recommended to only set the callback a small number of times,
-    /// and with a `T` that is small enough to not cause memory issues.
+    /// The callback stays installed for as long as the returned [`NotificationHandle`] is
+    /// alive. Dropping the handle clears the callback and frees the closure and context,
+    /// unless another callback has been set since. Use [`NotificationHandle::leak`] to
+    /// keep the callback installed until the device is closed.
     ///
     /// # References
     /// See page 42 for more information:
     /// <https://ftdichip.com/wp-content/uploads/2020/07/AN_379-D3xx-Programmers-Guide-1.pdf>
-    pub fn set_notification_callback<F, T>(&self, callback: F, context: Option<T>) -> Result<()>
+    pub fn set_notification_callback<F, T>(
+        &self,
+        callback: F,
+        context: Option<T>,
+    ) -> Result<NotificationHandle<'_>>
     where
         T: Sync + UnwindSafe,
         F: Fn(Notification<T>) + UnwindSafe,
     {
-        set_notification_callback(self.handle, callback, context)
+        set_notification_callback(self, callback, context)
+    }
+
+    /// Set a notification callback which may mutate its own state.
+    ///
+    /// This behaves like [`set_notification_callback`](Self::set_notification_callback),
+    /// but accepts an [`FnMut`] so that a callback can keep state such as a counter
+    /// without placing it in a synchronized context.
+    ///
+    /// # Threading
+    ///
+    /// The driver may invoke the callback from any thread, including one of its own.
+    /// The callback is kept behind an internal [`Mutex`](std::sync::Mutex), so calls
+    /// never overlap: a notification arriving while the callback is running waits for
+    /// it to return. For this reason the callback must be [`Send`], but not [`Sync`].
+    /// As with any notification callback, it should return quickly.
+    ///
+    /// If the callback panics, the panic is caught and printed to stderr, and the
+    /// callback is still invoked for later notifications.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use d3xx::{notification::Notification, Device};
+    ///
+    /// let device = Device::open("ABC123")?;
+    /// let mut count = 0;
+    /// let _handle = device.set_notification_callback_mut(
+    ///     move |_: Notification<()>| {
+    ///         count += 1;
+    ///         println!("notification {count}");
+    ///     },
+    ///     None,
+    /// )?;
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    pub fn set_notification_callback_mut<F, T>(
+        &self,
+        callback: F,
+        context: Option<T>,
+    ) -> Result<NotificationHandle<'_>>
+    where
+        T: Sync + UnwindSafe,
+        F: FnMut(Notification<T>) + Send,
+    {
+        let callback = Mutex::new(callback);
+        set_notification_callback(
+            self,
+            move |notification| {
+                // A panic in an earlier call does not invalidate the callback.
+                let mut callback = callback.lock().unwrap_or_else(PoisonError::into_inner);
+                callback(notification);
+            },
+            context,
+        )
     }
 
     /// Clear a previously-set notification callback.
     ///
-    /// Note that this function is infallible, and it is unclear why due to conflicting
-    /// documentation. On one hand the documentation says that the foreign function returns
-    /// `FT_STATUS`, but on the other hand the header/bindings indicate that nothing is returned.
-    /// It is therefore not possible to check the result of the operation.
-    pub fn clear_notification_callback(&self) {
-        // SAFETY: the handle exists
-        unsafe {
-            clear_notification_callback(self.handle);
+    /// The closure and context of the callback are freed once its [`NotificationHandle`]
+    /// is dropped.
+    ///
+    /// The D3XX bindings declare `FT_ClearNotificationCallback` as returning nothing, so
+    /// with a real device this always returns `Ok(())`. An error is only reported by
+    /// drivers from the `mock` module that are set up to fail.
+    pub fn clear_notification_callback(&self) -> Result<()> {
+        self.api.clear_notification_callback(self.handle)?;
+        self.notification_context.set(std::ptr::null_mut());
+        Ok(())
+    }
+
+    /// Context of the notification callback currently installed, or null if there is none.
+    pub(crate) fn notification_context(&self) -> *mut c_void {
+        self.notification_context.get()
+    }
+
+    /// Record the context of a newly installed notification callback.
+    pub(crate) fn set_notification_context(&self, context: *mut c_void) {
+        self.notification_context.set(context);
+    }
+}
+
+impl Debug for Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Device")
+            .field("handle", &self.handle)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A D3XX device handle borrowed from a [`Device`].
+///
+/// This is returned by [`Device::handle`] for use with the raw bindings in the [ffi]
+/// module. It borrows the device, so it cannot outlive it:
+///
+/// ```compile_fail
+/// use d3xx::Device;
+///
+/// let device = Device::open("ABC123").unwrap();
+/// let handle = device.handle();
+/// drop(device);
+/// let _ = handle.as_ptr();
+/// ```
+///
+/// Like `&Device`, it cannot be sent to another thread, since the device may not be
+/// used from several threads at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceHandle<'a> {
+    handle: ffi::FT_HANDLE,
+    _device: PhantomData<&'a Device>,
+}
+
+impl DeviceHandle<'_> {
+    /// Get the underlying handle.
+    ///
+    /// The pointer is only valid while the device is open. It must not be closed, as it
+    /// is still owned by the device.
+    #[must_use]
+    pub fn as_ptr(self) -> ffi::FT_HANDLE {
+        self.handle
+    }
+}
+
+/// Identity of a device, based on its serial number.
+///
+/// Returned by [`Device::key`] and [`DeviceInfo::key`]. Unlike a [`DeviceHandle`], a
+/// key does not borrow the device and stays the same across handles of one device.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeviceKey {
+    serial_number: String,
+}
+
+impl DeviceKey {
+    /// Create the key of the device with the given serial number.
+    #[must_use]
+    pub fn new(serial_number: &str) -> Self {
+        Self {
+            serial_number: serial_number.to_owned(),
         }
     }
+
+    /// Serial number of the device.
+    #[must_use]
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+}
+
+impl std::fmt::Display for DeviceKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.serial_number)
+    }
+}
+
+/// An opaque, non-owning D3XX device handle.
+///
+/// This is used to hand ownership of a device across an FFI boundary. Holding a
+/// `RawHandle` does not keep the device open, nor does dropping one close it; whoever
+/// owns the handle is responsible for closing it exactly once, either with
+/// [`ffi::FT_Close`] or by converting it back into a [`Device`] with
+/// [`Device::from_raw_handle`].
+///
+/// # Example
+///
+/// ```no_run
+/// use d3xx::{ffi, Device, RawHandle};
+///
+/// let device = Device::open("ABC123").unwrap();
+///
+/// // Hand the handle to foreign code...
+/// let handle: ffi::FT_HANDLE = device.into_raw_handle().into();
+///
+/// // ...and later take it back.
+/// let device = unsafe { Device::from_raw_handle(RawHandle::from(handle)) };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawHandle(ffi::FT_HANDLE);
+
+impl RawHandle {
+    /// Get the underlying handle.
+    #[must_use]
+    pub fn as_ptr(self) -> ffi::FT_HANDLE {
+        self.0
+    }
 }
 
+impl From<ffi::FT_HANDLE> for RawHandle {
+    fn from(handle: ffi::FT_HANDLE) -> Self {
+        Self(handle)
+    }
+}
+
+impl From<RawHandle> for ffi::FT_HANDLE {
+    fn from(handle: RawHandle) -> Self {
+        handle.0
+    }
+}
+
+/// A `RawHandle` is only an identifier; using it requires `unsafe` code regardless
+/// of which thread it is on.
+unsafe impl Send for RawHandle {}
+
 impl Drop for Device {
+    /// Closes the device with the [global lock](crate::ffi::with_global_lock) held.
+    ///
+    /// Since the lock is not reentrant, a device must not be dropped from within
+    /// [`with_global_lock`].
     fn drop(&mut self) {
-        unsafe {
-            let _ = ffi::FT_Close(self.handle);
+        let _ = close_handle(&*self.api, self.handle, self.abort_on_close);
+    }
+}
+
+/// Close a handle with the global lock held, optionally aborting every pipe first.
+fn close_handle(api: &dyn D3xxApi, handle: ffi::FT_HANDLE, abort: bool) -> Result<()> {
+    with_global_lock(AssertUnwindSafe(|| {
+        if abort {
+            for pipe in Pipe::all() {
+                let _ = api.abort_pipe(handle, pipe.into());
+            }
         }
+        api.close(handle)
+    }))
+}
+
+/// Get the value in `cell`, initializing it with `read` if it is empty.
+///
+/// The cell is left empty if `read` fails.
+fn cached<T>(cell: &OnceCell<T>, read: impl FnOnce() -> Result<T>) -> Result<&T> {
+    if let Some(value) = cell.get() {
+        return Ok(value);
     }
+    let value = read()?;
+    Ok(cell.get_or_init(|| value))
 }
 
 /// While a device is [`!Sync`](Sync), it is perfectly fine for it to be [`Send`]
@@ -334,3 +1113,558 @@ impl Drop for Device {
 /// the device is not protected by shared writes, but can be moved between threads
 /// as long as it is not used concurrently.
 unsafe impl Send for Device {}
+
+/// Pick the first device in `devices` which is not already open.
+fn first_available(devices: Vec<DeviceInfo>) -> Result<DeviceInfo> {
+    devices
+        .into_iter()
+        .find(|info| !info.is_open())
+        .ok_or(D3xxError::DeviceNotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, sync::Arc, thread};
+
+    use super::*;
+    use crate::mock::{self, Call, MockApi};
+
+    #[test]
+    fn open_by_flags() {
+        let cases = [
+            (
+                OpenBy::Serial("ABC123".into()),
+                ffi::FT_OPEN_BY_SERIAL_NUMBER,
+            ),
+            (
+                OpenBy::Description("FT601".into()),
+                ffi::FT_OPEN_BY_DESCRIPTION,
+            ),
+            (OpenBy::Index(1), ffi::FT_OPEN_BY_INDEX),
+        ];
+        for (open_by, flags) in cases {
+            let api = Arc::new(MockApi::default());
+            drop(Device::open_by(api.clone(), open_by).unwrap());
+            assert_eq!(api.calls()[0], Call::Create { flags });
+        }
+    }
+
+    /// Device list entry with the given serial number and open state.
+    #[allow(clippy::cast_possible_wrap)]
+    fn device_info(serial_number: &str, open: bool) -> DeviceInfo {
+        let mut node = ffi::FT_DEVICE_LIST_INFO_NODE {
+            Flags: if open {
+                ffi::FT_FLAGS::FT_FLAGS_OPENED as u32
+            } else {
+                0
+            },
+            Type: 601,
+            ID: 0,
+            LocId: 0,
+            SerialNumber: [0; 16],
+            Description: [0; 32],
+            ftHandle: std::ptr::null_mut(),
+        };
+        for (dst, &src) in node.SerialNumber.iter_mut().zip(serial_number.as_bytes()) {
+            *dst = src as std::ffi::c_char;
+        }
+        DeviceInfo::from(&node)
+    }
+
+    #[test]
+    fn first_available_skips_open_devices() {
+        let devices = vec![
+            device_info("OPEN1", true),
+            device_info("FREE1", false),
+            device_info("OPEN2", true),
+            device_info("FREE2", false),
+        ];
+        assert_eq!(first_available(devices).unwrap().serial_number(), "FREE1");
+
+        let devices = vec![device_info("OPEN1", true), device_info("OPEN2", true)];
+        assert_eq!(first_available(devices), Err(D3xxError::DeviceNotFound));
+        assert_eq!(first_available(vec![]), Err(D3xxError::DeviceNotFound));
+    }
+
+    #[test]
+    fn open_first() {
+        let api = Arc::new(MockApi::default());
+        api.set_device_count(2);
+        drop(Device::open_first_with(api.clone()).unwrap());
+        assert_eq!(
+            api.calls()[..3],
+            [
+                Call::CreateDeviceInfoList,
+                Call::DeviceInfoList,
+                Call::Create {
+                    flags: ffi::FT_OPEN_BY_SERIAL_NUMBER
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn open_first_none_available() {
+        let api = Arc::new(MockApi::default());
+        assert_eq!(
+            Device::open_first_with(api.clone()).err(),
+            Some(D3xxError::DeviceNotFound)
+        );
+
+        api.set_device_count(2);
+        api.set_device_flags(ffi::FT_FLAGS::FT_FLAGS_OPENED as u32);
+        assert_eq!(
+            Device::open_first_with(api.clone()).err(),
+            Some(D3xxError::DeviceNotFound)
+        );
+        assert!(!api
+            .calls()
+            .iter()
+            .any(|call| matches!(call, Call::Create { .. })));
+    }
+
+    #[test]
+    fn open_by_invalid_string() {
+        for open_by in [
+            OpenBy::Serial("A\0B".into()),
+            OpenBy::Description("A\0B".into()),
+        ] {
+            let api = Arc::new(MockApi::default());
+            assert_eq!(
+                Device::open_by(api.clone(), open_by).err(),
+                Some(D3xxError::InvalidArgs)
+            );
+            assert!(api.calls().is_empty());
+        }
+    }
+
+    #[test]
+    fn open_and_close() {
+        let api = Arc::new(MockApi::default());
+        drop(Device::open_by(api.clone(), OpenBy::Serial("ABC123".into())).unwrap());
+        let mut expected = vec![Call::Create {
+            flags: ffi::FT_OPEN_BY_SERIAL_NUMBER,
+        }];
+        expected.extend(abort_then_close());
+        assert_eq!(api.calls(), expected);
+    }
+
+    /// Calls expected when closing a device with `abort_on_close` enabled.
+    fn abort_then_close() -> Vec<Call> {
+        Pipe::all()
+            .into_iter()
+            .map(|pipe| Call::AbortPipe { pipe: pipe.into() })
+            .chain([Call::Close])
+            .collect()
+    }
+
+    #[test]
+    fn set_all_pipe_timeouts() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        device
+            .set_all_pipe_timeouts(Duration::from_micros(1500))
+            .unwrap();
+        let expected: Vec<_> = Pipe::all()
+            .into_iter()
+            .map(|pipe| Call::SetPipeTimeout {
+                pipe: pipe.into(),
+                timeout_ms: 2,
+            })
+            .collect();
+        assert_eq!(api.calls(), expected);
+        for pipe in Pipe::all() {
+            assert_eq!(device.pipe(pipe).timeout(), Ok(2));
+        }
+    }
+
+    #[test]
+    fn set_all_pipe_timeouts_continues_after_error() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| {
+                matches!(
+                    call,
+                    Call::SetPipeTimeout {
+                        pipe: 0x83 | 0x04,
+                        ..
+                    }
+                )
+            },
+            D3xxError::InvalidParameter,
+        );
+        let device = mock::device(&api);
+        assert_eq!(
+            device.set_all_pipe_timeouts(Duration::from_secs(1)),
+            Err(D3xxError::InvalidParameter)
+        );
+        let attempted = api
+            .calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::SetPipeTimeout { .. }))
+            .count();
+        assert_eq!(attempted, 8);
+        assert_eq!(device.pipe(Pipe::Out3).timeout(), Ok(1000));
+    }
+
+    #[test]
+    #[cfg(feature = "config")]
+    fn active_pipes_from_configuration() {
+        let api = Arc::new(MockApi::default());
+        let mut config: ffi::FT_60XCONFIGURATION = unsafe { std::mem::zeroed() };
+        let device = mock::device(&api);
+        for (channels, expected) in [
+            (0, Pipe::all().to_vec()),
+            (1, vec![Pipe::In0, Pipe::In1, Pipe::Out0, Pipe::Out1]),
+            (2, vec![Pipe::In0, Pipe::Out0]),
+            (3, vec![Pipe::Out0]),
+            (4, vec![Pipe::In0]),
+        ] {
+            config.ChannelConfig = channels;
+            api.set_chip_config(config);
+            assert_eq!(device.active_pipes(), Ok(expected));
+        }
+        assert!(!api
+            .calls()
+            .iter()
+            .any(|call| matches!(call, Call::PipeInformation { .. })));
+    }
+
+    #[test]
+    #[cfg(not(feature = "config"))]
+    fn active_pipes_by_probing() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| {
+                matches!(
+                    call,
+                    Call::PipeInformation {
+                        pipe: 0x84 | 0x85 | 0x04 | 0x05,
+                        ..
+                    }
+                )
+            },
+            D3xxError::InvalidParameter,
+        );
+        let device = mock::device(&api);
+        assert_eq!(
+            device.active_pipes(),
+            Ok(vec![Pipe::In0, Pipe::In1, Pipe::Out0, Pipe::Out1])
+        );
+        assert_eq!(api.calls().len(), 8);
+
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        api.fail_when(
+            |call| matches!(call, Call::PipeInformation { .. }),
+            D3xxError::DeviceNotConnected,
+        );
+        assert_eq!(device.active_pipes(), Err(D3xxError::DeviceNotConnected));
+    }
+
+    #[test]
+    fn disconnect_poisons_device() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        assert!(device.is_healthy());
+        api.fail_once_when(
+            |call| matches!(call, Call::WritePipe { .. }),
+            D3xxError::DeviceNotConnected,
+        );
+        assert!(device.pipe(Pipe::Out0).write(b"abc").is_err());
+        assert!(!device.is_healthy());
+        let calls = api.calls().len();
+
+        // Later calls fail without reaching the driver.
+        assert_eq!(
+            device.pipe(Pipe::In0).set_timeout(100),
+            Err(D3xxError::DeviceNotConnected)
+        );
+        assert_eq!(device.gpios().read(), Err(D3xxError::DeviceNotConnected));
+        assert_eq!(api.calls().len(), calls);
+
+        device.clear_poison();
+        assert!(device.is_healthy());
+        assert_eq!(device.pipe(Pipe::In0).set_timeout(100), Ok(()));
+        assert_eq!(api.calls().len(), calls + 1);
+    }
+
+    #[test]
+    fn poisoned_device_still_closes() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        api.fail_once_when(|_| true, D3xxError::InvalidHandle);
+        assert_eq!(device.reset(), Err(D3xxError::InvalidHandle));
+        assert!(!device.is_healthy());
+        device.close().unwrap();
+        assert_eq!(api.calls(), [Call::ResetDevicePort, Call::Close]);
+    }
+
+    #[test]
+    fn power_cycle_port() {
+        let api = Arc::new(MockApi::default());
+        mock::device(&api).power_cycle_port().unwrap();
+        // The driver closes the device when the port is cycled.
+        assert_eq!(api.calls(), [Call::CycleDevicePort]);
+    }
+
+    #[test]
+    fn poisoned_device_is_not_cycled() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        api.fail_once_when(|_| true, D3xxError::DeviceNotConnected);
+        assert_eq!(device.reset(), Err(D3xxError::DeviceNotConnected));
+        assert_eq!(
+            device.power_cycle_port(),
+            Err(D3xxError::DeviceNotConnected)
+        );
+        assert_eq!(api.calls(), [Call::ResetDevicePort]);
+    }
+
+    #[test]
+    fn poisoned_device_still_releases() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let overlapped = crate::overlapped::Overlapped::new(&device).unwrap();
+        let handle = device
+            .set_notification_callback(|_: Notification<()>| {}, None)
+            .unwrap();
+        api.fail_once_when(|_| true, D3xxError::DeviceNotConnected);
+        assert!(device.gpios().read().is_err());
+        assert!(!device.is_healthy());
+        let calls = api.calls().len();
+
+        drop(overlapped);
+        drop(handle);
+        assert_eq!(
+            api.calls()[calls..],
+            [Call::ReleaseOverlapped, Call::ClearNotificationCallback]
+        );
+    }
+
+    #[test]
+    fn close() {
+        let api = Arc::new(MockApi::default());
+        mock::device(&api).close().unwrap();
+        assert_eq!(api.calls(), abort_then_close());
+    }
+
+    #[test]
+    fn identification_is_cached() {
+        let api = Arc::new(MockApi::default());
+        api.set_device_descriptor(ffi::FT_DEVICE_DESCRIPTOR {
+            idVendor: 0x0403,
+            idProduct: 0x601f,
+            iSerialNumber: 3,
+            ..Default::default()
+        });
+        api.set_string(3, "ABC123");
+        let device = mock::device(&api);
+        assert_eq!(device.vendor_id(), Ok(0x0403));
+        assert_eq!(device.product_id(), Ok(0x601f));
+        assert_eq!(device.serial_number(), Ok("ABC123"));
+        let descriptor = device.device_descriptor().unwrap();
+        assert_eq!(descriptor.serial_number(), "ABC123");
+        let reads = |call: Call| api.calls().into_iter().filter(|c| *c == call).count();
+        assert_eq!(reads(Call::DeviceDescriptor), 1);
+        assert_eq!(reads(Call::StringDescriptor { index: 3 }), 1);
+    }
+
+    #[test]
+    fn key() {
+        let api = Arc::new(MockApi::default());
+        api.set_device_descriptor(ffi::FT_DEVICE_DESCRIPTOR {
+            iSerialNumber: 3,
+            ..Default::default()
+        });
+        api.set_string(3, "ABC123");
+        let first = mock::device(&api);
+        let second = mock::device(&api);
+        assert_eq!(first.key().unwrap(), second.key().unwrap());
+        assert_eq!(first.key().unwrap(), device_info("ABC123", true).key());
+        assert_ne!(first.key().unwrap(), DeviceKey::new("XYZ789"));
+        assert_eq!(first.key().unwrap().to_string(), "ABC123");
+
+        let mut states = std::collections::HashMap::new();
+        states.insert(first.key().unwrap(), 1);
+        assert_eq!(states.get(&second.key().unwrap()), Some(&1));
+        // The serial number is read once per device.
+        let reads = api
+            .calls()
+            .into_iter()
+            .filter(|c| *c == Call::StringDescriptor { index: 3 })
+            .count();
+        assert_eq!(reads, 2);
+    }
+
+    #[test]
+    fn vid_pid() {
+        let api = Arc::new(MockApi::default());
+        api.set_device_descriptor(ffi::FT_DEVICE_DESCRIPTOR {
+            idVendor: 0x0403,
+            idProduct: 0x601e,
+            ..Default::default()
+        });
+        let device = mock::device(&api);
+        assert_eq!(device.vid_pid(), Ok((0x0403, 0x601e)));
+        assert_eq!(device.vid_pid(), Ok((0x0403, 0x601e)));
+        assert_eq!(api.calls(), [Call::VidPid, Call::VidPid]);
+    }
+
+    #[test]
+    fn is_usb3() {
+        for (bcd, expected) in [
+            (0x0200, false),
+            (0x0210, false),
+            (0x0300, true),
+            (0x0320, true),
+        ] {
+            let api = Arc::new(MockApi::default());
+            api.set_device_descriptor(ffi::FT_DEVICE_DESCRIPTOR {
+                bcdUSB: bcd,
+                ..Default::default()
+            });
+            assert_eq!(
+                mock::device(&api).is_usb3(),
+                Ok(expected),
+                "bcdUSB {bcd:#06x}"
+            );
+        }
+    }
+
+    #[test]
+    fn descriptors_are_cached_until_refreshed() {
+        let api = Arc::new(MockApi::default());
+        let mut device = mock::device(&api);
+        device.device_descriptor().unwrap();
+        device.configuration_descriptor().unwrap();
+        let calls = api.calls().len();
+        device.device_descriptor().unwrap();
+        device.configuration_descriptor().unwrap();
+        assert_eq!(api.calls().len(), calls);
+
+        device.refresh_descriptors();
+        device.device_descriptor().unwrap();
+        device.configuration_descriptor().unwrap();
+        let reads = |call: Call| api.calls().into_iter().filter(|c| *c == call).count();
+        assert_eq!(reads(Call::DeviceDescriptor), 2);
+        assert_eq!(reads(Call::ConfigurationDescriptor), 2);
+    }
+
+    #[test]
+    fn identification_error_is_not_cached() {
+        let api = Arc::new(MockApi::default());
+        api.set_device_descriptor(ffi::FT_DEVICE_DESCRIPTOR {
+            idVendor: 0x0403,
+            ..Default::default()
+        });
+        api.fail_once_when(|call| *call == Call::DeviceDescriptor, D3xxError::IoError);
+        let device = mock::device(&api);
+        assert_eq!(device.vendor_id(), Err(D3xxError::IoError));
+        assert_eq!(device.vendor_id(), Ok(0x0403));
+    }
+
+    #[test]
+    fn suspend_timeout() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        assert_eq!(device.suspend_timeout(), Ok(10_000));
+        device.set_suspend_timeout(Some(2500)).unwrap();
+        assert_eq!(device.suspend_timeout(), Ok(2500));
+        device.set_suspend_timeout(None).unwrap();
+        assert_eq!(device.suspend_timeout(), Ok(0));
+    }
+
+    #[test]
+    fn driver_version() {
+        let api = Arc::new(MockApi::default());
+        api.set_driver_version(0x0001_0300);
+        let device = mock::device(&api);
+        let version = device.driver_version().unwrap();
+        assert_eq!(version.to_string(), "1.3.0");
+        assert_eq!(api.calls(), [Call::DriverVersion]);
+    }
+
+    #[test]
+    fn reset_keeps_device_open() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        device.reset().unwrap();
+        device.pipe(Pipe::Out0).write_all(b"data").unwrap();
+        assert_eq!(
+            api.calls()[..2],
+            [
+                Call::ResetDevicePort,
+                Call::WritePipe { pipe: 0x02, len: 4 }
+            ]
+        );
+        api.fail_once_when(|call| *call == Call::ResetDevicePort, D3xxError::IoError);
+        assert_eq!(device.reset(), Err(D3xxError::IoError));
+    }
+
+    #[test]
+    fn drop_aborts_then_closes() {
+        let api = Arc::new(MockApi::default());
+        // abort failures must not prevent the device from being closed
+        api.fail_when(
+            |call| matches!(call, Call::AbortPipe { .. }),
+            D3xxError::InvalidParameter,
+        );
+        drop(mock::device(&api));
+        assert_eq!(api.calls(), abort_then_close());
+    }
+
+    #[test]
+    fn close_without_abort() {
+        let api = Arc::new(MockApi::default());
+        let mut device = mock::device(&api);
+        device.set_abort_on_close(false);
+        device.close().unwrap();
+        assert_eq!(api.calls(), [Call::Close]);
+    }
+
+    #[test]
+    fn close_error() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(|call| *call == Call::Close, D3xxError::IoError);
+        let device = mock::device(&api);
+        assert_eq!(device.close(), Err(D3xxError::IoError));
+        // the destructor must not close the device a second time
+        assert_eq!(api.calls(), abort_then_close());
+    }
+
+    #[test]
+    fn raw_handle_round_trip() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let raw = device.as_raw_handle();
+        assert_eq!(raw.as_ptr(), device.handle().as_ptr());
+
+        let handle = device.into_raw_handle();
+        assert_eq!(handle, raw);
+        assert!(api.calls().is_empty());
+
+        let ptr: ffi::FT_HANDLE = handle.into();
+        let device = unsafe { Device::with_api(RawHandle::from(ptr).as_ptr(), api.clone()) };
+        drop(device);
+        assert_eq!(api.calls(), abort_then_close());
+    }
+
+    #[test]
+    fn open_and_close_serialized() {
+        let api = Arc::new(MockApi::default());
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..10 {
+                        drop(
+                            Device::open_by(api.clone(), OpenBy::Serial("ABC123".into())).unwrap(),
+                        );
+                    }
+                });
+            }
+        });
+        assert_eq!(api.calls().len(), 20 * (1 + abort_then_close().len()));
+        assert_eq!(api.max_concurrent(), 1);
+    }
+}
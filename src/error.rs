@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
 use crate::ffi;
 
@@ -86,6 +86,88 @@ impl D3xxError {
     pub fn code(&self) -> u8 {
         *self as u8
     }
+
+    /// The closest [`std::io::ErrorKind`] for this error.
+    fn io_error_kind(self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+        match self {
+            Self::Timeout => ErrorKind::TimedOut,
+            Self::DeviceNotConnected | Self::DeviceNotFound => ErrorKind::NotConnected,
+            Self::OperationAborted => ErrorKind::Interrupted,
+            Self::InvalidArgs | Self::InvalidParameter => ErrorKind::InvalidInput,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Attach the name of the failing operation to the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use d3xx::D3xxError;
+    ///
+    /// let err = D3xxError::Timeout.with_context("FT_ReadPipe");
+    /// assert_eq!(err.error(), D3xxError::Timeout);
+    /// assert_eq!(err.to_string(), "FT_ReadPipe: Timeout (error code 19)");
+    /// ```
+    #[must_use]
+    pub fn with_context(self, operation: &'static str) -> ErrorContext {
+        ErrorContext {
+            error: self,
+            operation,
+        }
+    }
+
+    /// Check if the error is likely to be temporary, such that retrying the
+    /// operation may succeed.
+    ///
+    /// This is the case for [`Timeout`](Self::Timeout), [`Busy`](Self::Busy),
+    /// [`DeviceListNotReady`](Self::DeviceListNotReady), [`IoPending`](Self::IoPending),
+    /// and [`IoIncomplete`](Self::IoIncomplete).
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Timeout
+                | Self::Busy
+                | Self::DeviceListNotReady
+                | Self::IoPending
+                | Self::IoIncomplete
+        )
+    }
+
+    /// Check if the error indicates that the device is no longer reachable,
+    /// for example because it was unplugged.
+    ///
+    /// This is the case for [`DeviceNotConnected`](Self::DeviceNotConnected),
+    /// [`DeviceNotFound`](Self::DeviceNotFound), and [`InvalidHandle`](Self::InvalidHandle).
+    #[must_use]
+    pub fn is_disconnect(&self) -> bool {
+        matches!(
+            self,
+            Self::DeviceNotConnected | Self::DeviceNotFound | Self::InvalidHandle
+        )
+    }
+
+    /// Check if the error occurred while transferring data.
+    ///
+    /// This is the case for [`IoError`](Self::IoError),
+    /// [`FailedToWriteDevice`](Self::FailedToWriteDevice), [`Timeout`](Self::Timeout),
+    /// [`OperationAborted`](Self::OperationAborted), [`IoPending`](Self::IoPending),
+    /// [`IoIncomplete`](Self::IoIncomplete), and [`HandleEof`](Self::HandleEof).
+    #[must_use]
+    pub fn is_io(&self) -> bool {
+        matches!(
+            self,
+            Self::IoError
+                | Self::FailedToWriteDevice
+                | Self::Timeout
+                | Self::OperationAborted
+                | Self::IoPending
+                | Self::IoIncomplete
+                | Self::HandleEof
+        )
+    }
 }
 
 impl Display for D3xxError {
@@ -141,8 +223,79 @@ impl From<D3xxError> for std::io::Error {
     ///
     /// This implementation is useful for functions which return [`io::Result<T>`](std::io::Result),
     /// such as the methods found in [`io::Write`](std::io::Write).
+    ///
+    /// The [`ErrorKind`](std::io::ErrorKind) is chosen based on the variant where a
+    /// sensible equivalent exists, otherwise [`Other`](std::io::ErrorKind::Other) is used.
+    /// The original error can be recovered through [`get_ref`](std::io::Error::get_ref).
     fn from(value: D3xxError) -> Self {
-        Self::new(std::io::ErrorKind::Other, value)
+        Self::new(value.io_error_kind(), value)
+    }
+}
+
+/// Call `f` up to `attempts` times until it succeeds, sleeping for `backoff`
+/// between attempts.
+///
+/// Only [transient](D3xxError::is_transient) errors are retried. Any other error,
+/// or the last transient error once all attempts are used up, is returned as-is.
+/// The closure is always called at least once, even if `attempts` is zero.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use d3xx::{list_devices, retry};
+///
+/// let devices = retry(3, Duration::from_millis(100), list_devices).unwrap();
+/// ```
+pub fn retry<T>(attempts: usize, backoff: Duration, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut remaining = attempts.max(1);
+    loop {
+        remaining -= 1;
+        match f() {
+            Err(e) if e.is_transient() && remaining > 0 => std::thread::sleep(backoff),
+            result => return result,
+        }
+    }
+}
+
+/// A [`D3xxError`] along with the name of the operation which failed.
+///
+/// This is created by [`D3xxError::with_context`], or by passing a label to
+/// [`try_d3xx`](crate::try_d3xx). The original error is available through [`ErrorContext::error`]
+/// and as the [source](std::error::Error::source) of this error.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[error("{operation}: {error}")]
+pub struct ErrorContext {
+    #[source]
+    error: D3xxError,
+    operation: &'static str,
+}
+
+impl ErrorContext {
+    /// The original error.
+    #[must_use]
+    pub fn error(&self) -> D3xxError {
+        self.error
+    }
+
+    /// Name of the operation which failed.
+    #[must_use]
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+}
+
+impl From<ErrorContext> for D3xxError {
+    /// Discards the context, keeping the original error.
+    fn from(value: ErrorContext) -> Self {
+        value.error
+    }
+}
+
+impl From<ErrorContext> for std::io::Error {
+    /// Allows propagation of D3XX errors with context as a [`std::io::Error`].
+    fn from(value: ErrorContext) -> Self {
+        Self::new(value.error.io_error_kind(), value)
     }
 }
 
@@ -156,20 +309,31 @@ impl From<D3xxError> for std::io::Error {
 /// The following example demonstrates how to use the macro. Normally you would
 /// use the macro to wrap a function call, rather than using a constant.
 ///
-/// ```ignore
-/// use d3xx::try_d3xx;
+/// ```
+/// use d3xx::{try_d3xx, D3xxError};
+///
+/// assert_eq!(try_d3xx!(0), Ok(()));
+/// assert_eq!(try_d3xx!(1), Err(D3xxError::InvalidHandle));
+/// ```
 ///
-/// try_d3xx!(0).unwrap(); // Ok
-/// try_d3xx!(1).unwrap(); // Error!
+/// An optional label naming the operation may be given, in which case the error
+/// is an [`ErrorContext`] instead:
+///
+/// ```
+/// let err = d3xx::try_d3xx!(19, "FT_ReadPipe").unwrap_err();
+/// assert_eq!(err.operation(), "FT_ReadPipe");
 /// ```
 #[macro_export]
 macro_rules! try_d3xx {
     ($expr:expr) => {
         match $expr {
             0 => Ok(()),
-            code => Err($crate::error::D3xxError::from(code)),
+            code => Err($crate::D3xxError::from(code)),
         }
     };
+    ($expr:expr, $operation:expr) => {
+        $crate::try_d3xx!($expr).map_err(|e| e.with_context($operation))
+    };
 }
 
 #[cfg(test)]
@@ -219,6 +383,136 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_io_error_kind() {
+        use std::io::ErrorKind;
+        let kind = |e: D3xxError| std::io::Error::from(e).kind();
+        assert_eq!(kind(D3xxError::Timeout), ErrorKind::TimedOut);
+        assert_eq!(kind(D3xxError::DeviceNotConnected), ErrorKind::NotConnected);
+        assert_eq!(kind(D3xxError::DeviceNotFound), ErrorKind::NotConnected);
+        assert_eq!(kind(D3xxError::OperationAborted), ErrorKind::Interrupted);
+        assert_eq!(kind(D3xxError::InvalidArgs), ErrorKind::InvalidInput);
+        assert_eq!(kind(D3xxError::InvalidParameter), ErrorKind::InvalidInput);
+        assert_eq!(kind(D3xxError::IoError), ErrorKind::Other);
+
+        let err = std::io::Error::from(D3xxError::Timeout.with_context("FT_ReadPipe"));
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+        let err = std::io::Error::from(D3xxError::Timeout);
+        let inner = err.get_ref().unwrap().downcast_ref::<D3xxError>();
+        assert_eq!(inner, Some(&D3xxError::Timeout));
+    }
+
+    #[test]
+    fn test_with_context() {
+        let err = D3xxError::IoError.with_context("FT_WritePipe");
+        assert_eq!(err.error(), D3xxError::IoError);
+        assert_eq!(err.operation(), "FT_WritePipe");
+        assert_eq!(err.to_string(), "FT_WritePipe: IoError (error code 4)");
+        assert_eq!(D3xxError::from(err), D3xxError::IoError);
+
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), D3xxError::IoError.to_string());
+    }
+
+    #[test]
+    fn test_retry_transient() {
+        let mut calls = 0;
+        let result = retry(3, Duration::ZERO, || {
+            calls += 1;
+            if calls < 3 {
+                Err(D3xxError::Busy)
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(3));
+
+        let mut calls = 0;
+        let result: Result<()> = retry(2, Duration::ZERO, || {
+            calls += 1;
+            Err(D3xxError::Timeout)
+        });
+        assert_eq!(result, Err(D3xxError::Timeout));
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_retry_stops_on_other_errors() {
+        let mut calls = 0;
+        let result: Result<()> = retry(5, Duration::ZERO, || {
+            calls += 1;
+            Err(D3xxError::DeviceNotConnected)
+        });
+        assert_eq!(result, Err(D3xxError::DeviceNotConnected));
+        assert_eq!(calls, 1);
+
+        let mut calls = 0;
+        let _ = retry(0, Duration::ZERO, || -> Result<()> {
+            calls += 1;
+            Err(D3xxError::Busy)
+        });
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_try_d3xx_macro_context() {
+        assert_eq!(try_d3xx!(0, "FT_Close"), Ok(()));
+        let err = try_d3xx!(19, "FT_ReadPipe").unwrap_err();
+        assert_eq!(err, D3xxError::Timeout.with_context("FT_ReadPipe"));
+    }
+
+    /// Variants for which `predicate` holds, out of every variant.
+    fn variants_where(predicate: fn(&D3xxError) -> bool) -> Vec<D3xxError> {
+        ERROR_CODE_MAP
+            .iter()
+            .map(|(variant, _)| *variant)
+            .filter(predicate)
+            .collect()
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert_eq!(
+            variants_where(D3xxError::is_transient),
+            [
+                D3xxError::Timeout,
+                D3xxError::IoPending,
+                D3xxError::IoIncomplete,
+                D3xxError::Busy,
+                D3xxError::DeviceListNotReady,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_disconnect() {
+        assert_eq!(
+            variants_where(D3xxError::is_disconnect),
+            [
+                D3xxError::InvalidHandle,
+                D3xxError::DeviceNotFound,
+                D3xxError::DeviceNotConnected,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_io() {
+        assert_eq!(
+            variants_where(D3xxError::is_io),
+            [
+                D3xxError::IoError,
+                D3xxError::FailedToWriteDevice,
+                D3xxError::Timeout,
+                D3xxError::OperationAborted,
+                D3xxError::IoPending,
+                D3xxError::IoIncomplete,
+                D3xxError::HandleEof,
+            ]
+        );
+    }
+
     #[test]
     fn test_try_d3xx_macro() {
         assert_eq!(try_d3xx!(0), Ok(()));
@@ -0,0 +1,582 @@
+//! Seam between the high-level abstractions and the D3XX driver.
+//!
+//! Calls made on behalf of a [`Device`](crate::Device) are dispatched through the
+//! [`D3xxApi`] trait rather than calling the bindings directly. The default implementation,
+//! [`Ffi`], forwards each call to the corresponding D3XX function. Substituting another
+//! implementation allows the logic built on top of the driver to be exercised without hardware.
+//! The `mock` feature provides such an implementation in `d3xx::mock`.
+
+use super::{
+    FT_AbortPipe, FT_ClearStreamPipe, FT_Close, FT_Create, FT_CreateDeviceInfoList,
+    FT_CycleDevicePort, FT_EnableGPIO, FT_FlushPipe, FT_GetConfigurationDescriptor,
+    FT_GetDeviceDescriptor, FT_GetDeviceInfoDetail, FT_GetDeviceInfoList, FT_GetDriverVersion,
+    FT_GetInterfaceDescriptor, FT_GetLibraryVersion, FT_GetOverlappedResult, FT_GetPipeInformation,
+    FT_GetStringDescriptor, FT_GetVIDPID, FT_InitializeOverlapped, FT_ReadGPIO,
+    FT_ReleaseOverlapped, FT_ResetDevicePort, FT_SetGPIOPull, FT_SetNotificationCallback,
+    FT_SetPipeTimeout, FT_SetStreamPipe, FT_WriteGPIO, _OVERLAPPED, DWORD,
+    FT_CONFIGURATION_DESCRIPTOR, FT_DEVICE_DESCRIPTOR, FT_DEVICE_LIST_INFO_NODE, FT_HANDLE,
+    FT_INTERFACE_DESCRIPTOR, FT_NOTIFICATION_CALLBACK, FT_PIPE_INFORMATION, FT_STRING_DESCRIPTOR,
+    PVOID, ULONG,
+};
+#[cfg(feature = "config")]
+use super::{FT_GetChipConfiguration, FT_SetChipConfiguration, FT_60XCONFIGURATION};
+use std::{mem::MaybeUninit, sync::Arc};
+
+use crate::{try_d3xx, Result, StreamPipeTarget};
+
+/// Driver entry points used by the high-level abstractions.
+///
+/// Each method corresponds to a single D3XX function, with the status code mapped to a
+/// [`Result`]. Implementations may assume that any handle passed to them was obtained
+/// from the same implementation.
+pub trait D3xxApi: Send + Sync {
+    /// `FT_Create`
+    fn create(&self, arg: PVOID, flags: ULONG) -> Result<FT_HANDLE>;
+
+    /// `FT_Close`
+    fn close(&self, handle: FT_HANDLE) -> Result<()>;
+
+    /// `FT_CreateDeviceInfoList`, returning the number of connected devices.
+    ///
+    /// The caller must hold the [global lock](super::with_global_lock).
+    fn create_device_info_list(&self) -> Result<u32>;
+
+    /// `FT_GetDeviceInfoDetail`, returning the entry at `index` in the device info list.
+    ///
+    /// The caller must hold the [global lock](super::with_global_lock).
+    fn device_info_detail(&self, index: u32) -> Result<FT_DEVICE_LIST_INFO_NODE>;
+
+    /// `FT_GetDeviceInfoList`, returning at most `capacity` entries of the device info list.
+    ///
+    /// `capacity` should be the count returned by
+    /// [`create_device_info_list`](Self::create_device_info_list). The caller must hold
+    /// the [global lock](super::with_global_lock).
+    fn device_info_list(&self, capacity: u32) -> Result<Vec<FT_DEVICE_LIST_INFO_NODE>>;
+
+    /// `FT_ResetDevicePort`
+    fn reset_device_port(&self, handle: FT_HANDLE) -> Result<()>;
+
+    /// `FT_CycleDevicePort`
+    ///
+    /// The device is closed by the driver when the port is cycled, so the handle must not
+    /// be used afterwards.
+    fn cycle_device_port(&self, handle: FT_HANDLE) -> Result<()>;
+
+    /// `FT_AbortPipe`
+    fn abort_pipe(&self, handle: FT_HANDLE, pipe: u8) -> Result<()>;
+
+    /// `FT_FlushPipe`
+    fn flush_pipe(&self, handle: FT_HANDLE, pipe: u8) -> Result<()>;
+
+    /// `FT_GetPipeInformation`
+    fn pipe_information(
+        &self,
+        handle: FT_HANDLE,
+        interface: u8,
+        pipe: u8,
+    ) -> Result<FT_PIPE_INFORMATION>;
+
+    /// `FT_GetVIDPID`, returning the vendor and product IDs.
+    fn vid_pid(&self, handle: FT_HANDLE) -> Result<(u16, u16)>;
+
+    /// `FT_GetDriverVersion`, returning the encoded version.
+    fn driver_version(&self, handle: FT_HANDLE) -> Result<u32>;
+
+    /// `FT_GetLibraryVersion`, returning the encoded version.
+    fn library_version(&self) -> Result<u32>;
+
+    /// `FT_GetDeviceDescriptor`
+    fn device_descriptor(&self, handle: FT_HANDLE) -> Result<FT_DEVICE_DESCRIPTOR>;
+
+    /// `FT_GetConfigurationDescriptor`
+    fn configuration_descriptor(&self, handle: FT_HANDLE) -> Result<FT_CONFIGURATION_DESCRIPTOR>;
+
+    /// `FT_GetInterfaceDescriptor`
+    fn interface_descriptor(&self, handle: FT_HANDLE, index: u8)
+        -> Result<FT_INTERFACE_DESCRIPTOR>;
+
+    /// `FT_GetStringDescriptor`
+    fn string_descriptor(&self, handle: FT_HANDLE, index: u8) -> Result<FT_STRING_DESCRIPTOR>;
+
+    /// `FT_ReadPipe`, synchronously. Returns the number of bytes read, which have been
+    /// initialized at the start of `buf`.
+    fn read_pipe(&self, handle: FT_HANDLE, pipe: u8, buf: &mut [MaybeUninit<u8>]) -> Result<usize>;
+
+    /// `FT_WritePipe`, synchronously. Returns the number of bytes written.
+    fn write_pipe(&self, handle: FT_HANDLE, pipe: u8, buf: &[u8]) -> Result<usize>;
+
+    /// `FT_ReadPipe` (or `FT_ReadPipeAsync`) in overlapped mode.
+    ///
+    /// The transfer is started and completes in the background, writing into `buf`.
+    /// Its outcome is retrieved with [`overlapped_result`](Self::overlapped_result).
+    fn read_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &mut [u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()>;
+
+    /// `FT_WritePipe` (or `FT_WritePipeAsync`) in overlapped mode.
+    ///
+    /// The transfer is started and completes in the background, reading from `buf`.
+    /// Its outcome is retrieved with [`overlapped_result`](Self::overlapped_result).
+    fn write_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &[u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()>;
+
+    /// `FT_InitializeOverlapped`
+    fn initialize_overlapped(&self, handle: FT_HANDLE) -> Result<_OVERLAPPED>;
+
+    /// `FT_ReleaseOverlapped`
+    fn release_overlapped(&self, handle: FT_HANDLE, overlapped: &mut _OVERLAPPED) -> Result<()>;
+
+    /// `FT_GetOverlappedResult`. Returns the number of bytes transferred.
+    ///
+    /// If `wait` is `false` and the transfer is still in progress, this fails with
+    /// [`IoIncomplete`](crate::D3xxError::IoIncomplete) or [`IoPending`](crate::D3xxError::IoPending).
+    fn overlapped_result(
+        &self,
+        handle: FT_HANDLE,
+        overlapped: &mut _OVERLAPPED,
+        wait: bool,
+    ) -> Result<usize>;
+
+    /// `FT_SetStreamPipe` for a single pipe, or `FT_ClearStreamPipe` if `size` is `None`.
+    ///
+    /// `target` selects the `bAllWritePipes` and `bAllReadPipes` flags.
+    fn set_stream_pipe(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        target: StreamPipeTarget,
+        size: Option<u32>,
+    ) -> Result<()>;
+
+    /// `FT_SetPipeTimeout`
+    fn set_pipe_timeout(&self, handle: FT_HANDLE, pipe: u8, timeout_ms: u32) -> Result<()>;
+
+    /// `FT_GetPipeTimeout`, returning the timeout in milliseconds.
+    ///
+    /// This function is only available on Windows; elsewhere this fails with
+    /// [`NotSupported`](crate::D3xxError::NotSupported).
+    fn pipe_timeout(&self, handle: FT_HANDLE, pipe: u8) -> Result<u32>;
+
+    /// `FT_GetSuspendTimeout`, returning the timeout in milliseconds.
+    ///
+    /// This function is only available on Windows; elsewhere this fails with
+    /// [`NotSupported`](crate::D3xxError::NotSupported).
+    fn suspend_timeout(&self, handle: FT_HANDLE) -> Result<u32>;
+
+    /// `FT_SetSuspendTimeout`
+    ///
+    /// This function is only available on Windows; elsewhere this fails with
+    /// [`NotSupported`](crate::D3xxError::NotSupported).
+    fn set_suspend_timeout(&self, handle: FT_HANDLE, timeout_ms: u32) -> Result<()>;
+
+    /// `FT_GetReadQueueStatus` for IN pipes, or `FT_GetWriteQueueStatus` for OUT pipes.
+    ///
+    /// These functions are only available on Linux and macOS; on Windows this fails with
+    /// [`NotSupported`](crate::D3xxError::NotSupported).
+    fn queue_status(&self, handle: FT_HANDLE, pipe: u8) -> Result<u32>;
+
+    /// `FT_EnableGPIO`
+    fn enable_gpio(&self, handle: FT_HANDLE, mask: u32, direction: u32) -> Result<()>;
+
+    /// `FT_SetGPIOPull`
+    fn set_gpio_pull(&self, handle: FT_HANDLE, mask: u32, pull: u32) -> Result<()>;
+
+    /// `FT_WriteGPIO`
+    fn write_gpio(&self, handle: FT_HANDLE, mask: u32, level: u32) -> Result<()>;
+
+    /// `FT_ReadGPIO`
+    fn read_gpio(&self, handle: FT_HANDLE) -> Result<u32>;
+
+    /// `FT_SetNotificationCallback`
+    ///
+    /// `context` is passed to `callback` with each notification, and must remain valid
+    /// until the callback is cleared or replaced.
+    fn set_notification_callback(
+        &self,
+        handle: FT_HANDLE,
+        callback: FT_NOTIFICATION_CALLBACK,
+        context: PVOID,
+    ) -> Result<()>;
+
+    /// `FT_ClearNotificationCallback`
+    fn clear_notification_callback(&self, handle: FT_HANDLE) -> Result<()>;
+
+    /// `FT_GetChipConfiguration`
+    #[cfg(feature = "config")]
+    fn chip_configuration(&self, handle: FT_HANDLE) -> Result<FT_60XCONFIGURATION>;
+
+    /// `FT_SetChipConfiguration`
+    #[cfg(feature = "config")]
+    fn set_chip_configuration(&self, handle: FT_HANDLE, config: &FT_60XCONFIGURATION)
+        -> Result<()>;
+}
+
+/// Wrap `api` so that calls through it are logged, if the `log` feature is enabled.
+pub(crate) fn instrument(api: Arc<dyn D3xxApi>) -> Arc<dyn D3xxApi> {
+    #[cfg(feature = "log")]
+    let api = Arc::new(super::logged::Logged(api));
+    api
+}
+
+/// [`D3xxApi`] implementation which calls into the D3XX driver.
+///
+/// The handle passed to each method must be valid; this is upheld by [`Device`](crate::Device),
+/// which is the only way to reach this implementation.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Ffi;
+
+impl D3xxApi for Ffi {
+    fn create(&self, arg: PVOID, flags: ULONG) -> Result<FT_HANDLE> {
+        let mut handle: FT_HANDLE = std::ptr::null_mut();
+        try_d3xx!(unsafe { FT_Create(arg, flags, &mut handle) })?;
+        Ok(handle)
+    }
+
+    fn close(&self, handle: FT_HANDLE) -> Result<()> {
+        try_d3xx!(unsafe { FT_Close(handle) })
+    }
+
+    fn create_device_info_list(&self) -> Result<u32> {
+        let mut count = 0;
+        try_d3xx!(unsafe { FT_CreateDeviceInfoList(&mut count) })?;
+        Ok(count)
+    }
+
+    fn device_info_detail(&self, index: u32) -> Result<FT_DEVICE_LIST_INFO_NODE> {
+        let mut node = FT_DEVICE_LIST_INFO_NODE {
+            Flags: 0,
+            Type: 0,
+            ID: 0,
+            LocId: 0,
+            SerialNumber: [0; 16],
+            Description: [0; 32],
+            ftHandle: std::ptr::null_mut(),
+        };
+        try_d3xx!(unsafe {
+            FT_GetDeviceInfoDetail(
+                index,
+                &mut node.Flags,
+                &mut node.Type,
+                &mut node.ID,
+                &mut node.LocId,
+                node.SerialNumber.as_mut_ptr().cast(),
+                node.Description.as_mut_ptr().cast(),
+                &mut node.ftHandle,
+            )
+        })?;
+        // The driver does not promise null termination of truncated strings.
+        *node.SerialNumber.last_mut().unwrap() = 0;
+        *node.Description.last_mut().unwrap() = 0;
+        Ok(node)
+    }
+
+    fn device_info_list(&self, capacity: u32) -> Result<Vec<FT_DEVICE_LIST_INFO_NODE>> {
+        let capacity = capacity as usize;
+        let mut len: DWORD = 0;
+        let mut nodes: Vec<FT_DEVICE_LIST_INFO_NODE> = Vec::with_capacity(capacity);
+        try_d3xx!(unsafe { FT_GetDeviceInfoList(nodes.as_mut_ptr(), &mut len) })?;
+        // SAFETY: the number of devices is less than or equal to the capacity
+        // the vector was created with.
+        unsafe { nodes.set_len(capacity.min(len as usize)) };
+        Ok(nodes)
+    }
+
+    fn reset_device_port(&self, handle: FT_HANDLE) -> Result<()> {
+        try_d3xx!(unsafe { FT_ResetDevicePort(handle) })
+    }
+
+    fn cycle_device_port(&self, handle: FT_HANDLE) -> Result<()> {
+        try_d3xx!(unsafe { FT_CycleDevicePort(handle) })
+    }
+
+    fn abort_pipe(&self, handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        try_d3xx!(unsafe { FT_AbortPipe(handle, pipe) })
+    }
+
+    fn flush_pipe(&self, handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        try_d3xx!(unsafe { FT_FlushPipe(handle, pipe) })
+    }
+
+    fn pipe_information(
+        &self,
+        handle: FT_HANDLE,
+        interface: u8,
+        pipe: u8,
+    ) -> Result<FT_PIPE_INFORMATION> {
+        let mut info = FT_PIPE_INFORMATION::default();
+        try_d3xx!(unsafe { FT_GetPipeInformation(handle, interface, pipe, &mut info) })?;
+        Ok(info)
+    }
+
+    fn vid_pid(&self, handle: FT_HANDLE) -> Result<(u16, u16)> {
+        let (mut vid, mut pid) = (0, 0);
+        try_d3xx!(unsafe { FT_GetVIDPID(handle, &mut vid, &mut pid) })?;
+        Ok((vid, pid))
+    }
+
+    fn driver_version(&self, handle: FT_HANDLE) -> Result<u32> {
+        let mut version = 0;
+        try_d3xx!(unsafe { FT_GetDriverVersion(handle, &mut version) })?;
+        Ok(version)
+    }
+
+    fn library_version(&self) -> Result<u32> {
+        let mut version = 0;
+        try_d3xx!(unsafe { FT_GetLibraryVersion(&mut version) })?;
+        Ok(version)
+    }
+
+    fn device_descriptor(&self, handle: FT_HANDLE) -> Result<FT_DEVICE_DESCRIPTOR> {
+        let mut descriptor = FT_DEVICE_DESCRIPTOR::default();
+        try_d3xx!(unsafe { FT_GetDeviceDescriptor(handle, &mut descriptor) })?;
+        Ok(descriptor)
+    }
+
+    fn configuration_descriptor(&self, handle: FT_HANDLE) -> Result<FT_CONFIGURATION_DESCRIPTOR> {
+        let mut descriptor = FT_CONFIGURATION_DESCRIPTOR::default();
+        try_d3xx!(unsafe { FT_GetConfigurationDescriptor(handle, &mut descriptor) })?;
+        Ok(descriptor)
+    }
+
+    fn interface_descriptor(
+        &self,
+        handle: FT_HANDLE,
+        index: u8,
+    ) -> Result<FT_INTERFACE_DESCRIPTOR> {
+        let mut descriptor = FT_INTERFACE_DESCRIPTOR::default();
+        try_d3xx!(unsafe { FT_GetInterfaceDescriptor(handle, index, &mut descriptor) })?;
+        Ok(descriptor)
+    }
+
+    fn string_descriptor(&self, handle: FT_HANDLE, index: u8) -> Result<FT_STRING_DESCRIPTOR> {
+        let mut descriptor = FT_STRING_DESCRIPTOR::default();
+        try_d3xx!(unsafe { FT_GetStringDescriptor(handle, index, &mut descriptor) })?;
+        Ok(descriptor)
+    }
+
+    fn read_pipe(&self, handle: FT_HANDLE, pipe: u8, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        super::util::read_pipe(handle, pipe, buf)
+    }
+
+    fn write_pipe(&self, handle: FT_HANDLE, pipe: u8, buf: &[u8]) -> Result<usize> {
+        super::util::write_pipe(handle, pipe, buf)
+    }
+
+    fn read_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &mut [u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        super::util::read_pipe_async(handle, pipe, buf, overlapped)
+    }
+
+    fn write_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &[u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        super::util::write_pipe_async(handle, pipe, buf, overlapped)
+    }
+
+    fn initialize_overlapped(&self, handle: FT_HANDLE) -> Result<_OVERLAPPED> {
+        let mut overlapped = MaybeUninit::<_OVERLAPPED>::uninit();
+        try_d3xx!(unsafe { FT_InitializeOverlapped(handle, overlapped.as_mut_ptr()) })?;
+        // SAFETY: `overlapped` is initialized by `FT_InitializeOverlapped` on success.
+        Ok(unsafe { overlapped.assume_init() })
+    }
+
+    fn release_overlapped(&self, handle: FT_HANDLE, overlapped: &mut _OVERLAPPED) -> Result<()> {
+        try_d3xx!(unsafe { FT_ReleaseOverlapped(handle, overlapped) })
+    }
+
+    fn overlapped_result(
+        &self,
+        handle: FT_HANDLE,
+        overlapped: &mut _OVERLAPPED,
+        wait: bool,
+    ) -> Result<usize> {
+        let mut transferred: ULONG = 0;
+        try_d3xx!(unsafe {
+            FT_GetOverlappedResult(
+                handle,
+                overlapped,
+                &mut transferred,
+                super::BOOL::from(wait),
+            )
+        })?;
+        Ok(transferred as usize)
+    }
+
+    fn set_stream_pipe(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        target: StreamPipeTarget,
+        size: Option<u32>,
+    ) -> Result<()> {
+        #[cfg(windows)]
+        type Bool = super::BOOLEAN;
+        #[cfg(not(windows))]
+        type Bool = super::BOOL;
+
+        let all_out = Bool::from(target.all_out());
+        let all_in = Bool::from(target.all_in());
+        match size {
+            Some(size) => {
+                try_d3xx!(unsafe { FT_SetStreamPipe(handle, all_out, all_in, pipe, size) })
+            }
+            None => try_d3xx!(unsafe { FT_ClearStreamPipe(handle, all_out, all_in, pipe) }),
+        }
+    }
+
+    fn set_pipe_timeout(&self, handle: FT_HANDLE, pipe: u8, timeout_ms: u32) -> Result<()> {
+        try_d3xx!(unsafe { FT_SetPipeTimeout(handle, pipe, timeout_ms) })
+    }
+
+    #[cfg(windows)]
+    fn pipe_timeout(&self, handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        let mut timeout = 0;
+        try_d3xx!(unsafe { super::FT_GetPipeTimeout(handle, pipe, &mut timeout) })?;
+        Ok(timeout)
+    }
+
+    #[cfg(not(windows))]
+    fn pipe_timeout(&self, _handle: FT_HANDLE, _pipe: u8) -> Result<u32> {
+        Err(crate::D3xxError::NotSupported)
+    }
+
+    #[cfg(windows)]
+    fn suspend_timeout(&self, handle: FT_HANDLE) -> Result<u32> {
+        let mut timeout = 0;
+        try_d3xx!(unsafe { super::FT_GetSuspendTimeout(handle, &mut timeout) })?;
+        Ok(timeout)
+    }
+
+    #[cfg(not(windows))]
+    fn suspend_timeout(&self, _handle: FT_HANDLE) -> Result<u32> {
+        Err(crate::D3xxError::NotSupported)
+    }
+
+    #[cfg(windows)]
+    fn set_suspend_timeout(&self, handle: FT_HANDLE, timeout_ms: u32) -> Result<()> {
+        try_d3xx!(unsafe { super::FT_SetSuspendTimeout(handle, timeout_ms) })
+    }
+
+    #[cfg(not(windows))]
+    fn set_suspend_timeout(&self, _handle: FT_HANDLE, _timeout_ms: u32) -> Result<()> {
+        Err(crate::D3xxError::NotSupported)
+    }
+
+    #[cfg(not(windows))]
+    fn queue_status(&self, handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        let mut queued = 0;
+        if pipe & 0x80 == 0 {
+            try_d3xx!(unsafe { super::FT_GetWriteQueueStatus(handle, pipe, &mut queued) })?;
+        } else {
+            try_d3xx!(unsafe { super::FT_GetReadQueueStatus(handle, pipe, &mut queued) })?;
+        }
+        Ok(queued)
+    }
+
+    #[cfg(windows)]
+    fn queue_status(&self, _handle: FT_HANDLE, _pipe: u8) -> Result<u32> {
+        Err(crate::D3xxError::NotSupported)
+    }
+
+    fn enable_gpio(&self, handle: FT_HANDLE, mask: u32, direction: u32) -> Result<()> {
+        try_d3xx!(unsafe { FT_EnableGPIO(handle, mask, direction) })
+    }
+
+    fn set_gpio_pull(&self, handle: FT_HANDLE, mask: u32, pull: u32) -> Result<()> {
+        try_d3xx!(unsafe { FT_SetGPIOPull(handle, mask, pull) })
+    }
+
+    fn write_gpio(&self, handle: FT_HANDLE, mask: u32, level: u32) -> Result<()> {
+        try_d3xx!(unsafe { FT_WriteGPIO(handle, mask, level) })
+    }
+
+    fn read_gpio(&self, handle: FT_HANDLE) -> Result<u32> {
+        let mut value = 0;
+        try_d3xx!(unsafe { FT_ReadGPIO(handle, &mut value) })?;
+        Ok(value)
+    }
+
+    fn set_notification_callback(
+        &self,
+        handle: FT_HANDLE,
+        callback: FT_NOTIFICATION_CALLBACK,
+        context: PVOID,
+    ) -> Result<()> {
+        try_d3xx!(unsafe { FT_SetNotificationCallback(handle, callback, context) })
+    }
+
+    /// The bindings declare the foreign function as returning nothing, so no error is
+    /// ever reported.
+    fn clear_notification_callback(&self, handle: FT_HANDLE) -> Result<()> {
+        unsafe { super::FT_ClearNotificationCallback(handle) };
+        Ok(())
+    }
+
+    #[cfg(feature = "config")]
+    fn chip_configuration(&self, handle: FT_HANDLE) -> Result<FT_60XCONFIGURATION> {
+        let mut config: FT_60XCONFIGURATION = unsafe { std::mem::zeroed() };
+        try_d3xx!(unsafe {
+            FT_GetChipConfiguration(handle, std::ptr::addr_of_mut!(config).cast())
+        })?;
+        Ok(config)
+    }
+
+    #[cfg(feature = "config")]
+    fn set_chip_configuration(
+        &self,
+        handle: FT_HANDLE,
+        config: &FT_60XCONFIGURATION,
+    ) -> Result<()> {
+        // The driver does not modify the configuration, despite taking a mutable pointer.
+        let mut config = *config;
+        try_d3xx!(unsafe { FT_SetChipConfiguration(handle, std::ptr::addr_of_mut!(config).cast()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Functions which only exist on some platforms report whether they are supported
+    /// without reaching the driver, so this holds without a device attached.
+    #[test]
+    #[cfg(not(windows))]
+    fn windows_only_functions_are_not_supported() {
+        use crate::D3xxError;
+
+        let handle = std::ptr::null_mut();
+        assert_eq!(Ffi.pipe_timeout(handle, 0x82), Err(D3xxError::NotSupported));
+        assert_eq!(Ffi.suspend_timeout(handle), Err(D3xxError::NotSupported));
+        assert_eq!(
+            Ffi.set_suspend_timeout(handle, 1000),
+            Err(D3xxError::NotSupported)
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn linux_only_functions_are_not_supported() {
+        use crate::D3xxError;
+
+        let handle = std::ptr::null_mut();
+        assert_eq!(Ffi.queue_status(handle, 0x82), Err(D3xxError::NotSupported));
+    }
+}
@@ -0,0 +1,254 @@
+//! Fail-fast guard against issuing calls on a dead device handle.
+//!
+//! Every [`Device`](crate::Device) wraps its [`D3xxApi`] in [`Guarded`]. Once a call
+//! reports that the handle is invalid or the device is no longer connected, the guard is
+//! poisoned and later calls fail with [`D3xxError::DeviceNotConnected`] without reaching
+//! the driver. Calls which do not use the handle are always forwarded, as are the calls
+//! which release resources: closing the handle, releasing overlapped structures, and
+//! clearing the notification callback.
+
+use std::{
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+#[cfg(feature = "config")]
+use super::FT_60XCONFIGURATION;
+use super::{
+    api::D3xxApi, _OVERLAPPED, FT_CONFIGURATION_DESCRIPTOR, FT_DEVICE_DESCRIPTOR,
+    FT_DEVICE_LIST_INFO_NODE, FT_HANDLE, FT_INTERFACE_DESCRIPTOR, FT_NOTIFICATION_CALLBACK,
+    FT_PIPE_INFORMATION, FT_STRING_DESCRIPTOR, PVOID, ULONG,
+};
+use crate::{D3xxError, Result, StreamPipeTarget};
+
+/// [`D3xxApi`] implementation which stops forwarding calls after the handle has failed.
+pub(crate) struct Guarded {
+    inner: Arc<dyn D3xxApi>,
+    /// Set once a call fails in a way which means the handle is no longer usable.
+    poisoned: Arc<AtomicBool>,
+}
+
+impl Guarded {
+    /// Guard calls to `inner`, recording failures in `poisoned`.
+    pub(crate) fn new(inner: Arc<dyn D3xxApi>, poisoned: Arc<AtomicBool>) -> Self {
+        Self { inner, poisoned }
+    }
+
+    /// Make a call using the handle, unless the guard is poisoned.
+    fn guard<T>(&self, call: impl FnOnce() -> Result<T>) -> Result<T> {
+        if self.poisoned.load(Ordering::Acquire) {
+            return Err(D3xxError::DeviceNotConnected);
+        }
+        let result = call();
+        if matches!(
+            result,
+            Err(D3xxError::InvalidHandle | D3xxError::DeviceNotConnected)
+        ) {
+            self.poisoned.store(true, Ordering::Release);
+        }
+        result
+    }
+}
+
+impl D3xxApi for Guarded {
+    fn create(&self, arg: PVOID, flags: ULONG) -> Result<FT_HANDLE> {
+        self.inner.create(arg, flags)
+    }
+
+    fn close(&self, handle: FT_HANDLE) -> Result<()> {
+        self.inner.close(handle)
+    }
+
+    fn create_device_info_list(&self) -> Result<u32> {
+        self.inner.create_device_info_list()
+    }
+
+    fn device_info_detail(&self, index: u32) -> Result<FT_DEVICE_LIST_INFO_NODE> {
+        self.inner.device_info_detail(index)
+    }
+
+    fn device_info_list(&self, capacity: u32) -> Result<Vec<FT_DEVICE_LIST_INFO_NODE>> {
+        self.inner.device_info_list(capacity)
+    }
+
+    fn reset_device_port(&self, handle: FT_HANDLE) -> Result<()> {
+        self.guard(|| self.inner.reset_device_port(handle))
+    }
+
+    fn cycle_device_port(&self, handle: FT_HANDLE) -> Result<()> {
+        self.guard(|| self.inner.cycle_device_port(handle))
+    }
+
+    fn abort_pipe(&self, handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        self.guard(|| self.inner.abort_pipe(handle, pipe))
+    }
+
+    fn flush_pipe(&self, handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        self.guard(|| self.inner.flush_pipe(handle, pipe))
+    }
+
+    fn pipe_information(
+        &self,
+        handle: FT_HANDLE,
+        interface: u8,
+        pipe: u8,
+    ) -> Result<FT_PIPE_INFORMATION> {
+        self.guard(|| self.inner.pipe_information(handle, interface, pipe))
+    }
+
+    fn vid_pid(&self, handle: FT_HANDLE) -> Result<(u16, u16)> {
+        self.guard(|| self.inner.vid_pid(handle))
+    }
+
+    fn driver_version(&self, handle: FT_HANDLE) -> Result<u32> {
+        self.guard(|| self.inner.driver_version(handle))
+    }
+
+    fn library_version(&self) -> Result<u32> {
+        self.inner.library_version()
+    }
+
+    fn device_descriptor(&self, handle: FT_HANDLE) -> Result<FT_DEVICE_DESCRIPTOR> {
+        self.guard(|| self.inner.device_descriptor(handle))
+    }
+
+    fn configuration_descriptor(&self, handle: FT_HANDLE) -> Result<FT_CONFIGURATION_DESCRIPTOR> {
+        self.guard(|| self.inner.configuration_descriptor(handle))
+    }
+
+    fn interface_descriptor(
+        &self,
+        handle: FT_HANDLE,
+        index: u8,
+    ) -> Result<FT_INTERFACE_DESCRIPTOR> {
+        self.guard(|| self.inner.interface_descriptor(handle, index))
+    }
+
+    fn string_descriptor(&self, handle: FT_HANDLE, index: u8) -> Result<FT_STRING_DESCRIPTOR> {
+        self.guard(|| self.inner.string_descriptor(handle, index))
+    }
+
+    fn read_pipe(&self, handle: FT_HANDLE, pipe: u8, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.guard(|| self.inner.read_pipe(handle, pipe, buf))
+    }
+
+    fn write_pipe(&self, handle: FT_HANDLE, pipe: u8, buf: &[u8]) -> Result<usize> {
+        self.guard(|| self.inner.write_pipe(handle, pipe, buf))
+    }
+
+    fn read_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &mut [u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        self.guard(|| self.inner.read_pipe_async(handle, pipe, buf, overlapped))
+    }
+
+    fn write_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &[u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        self.guard(|| self.inner.write_pipe_async(handle, pipe, buf, overlapped))
+    }
+
+    fn initialize_overlapped(&self, handle: FT_HANDLE) -> Result<_OVERLAPPED> {
+        self.guard(|| self.inner.initialize_overlapped(handle))
+    }
+
+    fn release_overlapped(&self, handle: FT_HANDLE, overlapped: &mut _OVERLAPPED) -> Result<()> {
+        self.inner.release_overlapped(handle, overlapped)
+    }
+
+    fn overlapped_result(
+        &self,
+        handle: FT_HANDLE,
+        overlapped: &mut _OVERLAPPED,
+        wait: bool,
+    ) -> Result<usize> {
+        self.guard(|| self.inner.overlapped_result(handle, overlapped, wait))
+    }
+
+    fn set_stream_pipe(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        target: StreamPipeTarget,
+        size: Option<u32>,
+    ) -> Result<()> {
+        self.guard(|| self.inner.set_stream_pipe(handle, pipe, target, size))
+    }
+
+    fn set_pipe_timeout(&self, handle: FT_HANDLE, pipe: u8, timeout_ms: u32) -> Result<()> {
+        self.guard(|| self.inner.set_pipe_timeout(handle, pipe, timeout_ms))
+    }
+
+    fn pipe_timeout(&self, handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        self.guard(|| self.inner.pipe_timeout(handle, pipe))
+    }
+
+    fn suspend_timeout(&self, handle: FT_HANDLE) -> Result<u32> {
+        self.guard(|| self.inner.suspend_timeout(handle))
+    }
+
+    fn set_suspend_timeout(&self, handle: FT_HANDLE, timeout_ms: u32) -> Result<()> {
+        self.guard(|| self.inner.set_suspend_timeout(handle, timeout_ms))
+    }
+
+    fn queue_status(&self, handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        self.guard(|| self.inner.queue_status(handle, pipe))
+    }
+
+    fn enable_gpio(&self, handle: FT_HANDLE, mask: u32, direction: u32) -> Result<()> {
+        self.guard(|| self.inner.enable_gpio(handle, mask, direction))
+    }
+
+    fn set_gpio_pull(&self, handle: FT_HANDLE, mask: u32, pull: u32) -> Result<()> {
+        self.guard(|| self.inner.set_gpio_pull(handle, mask, pull))
+    }
+
+    fn write_gpio(&self, handle: FT_HANDLE, mask: u32, level: u32) -> Result<()> {
+        self.guard(|| self.inner.write_gpio(handle, mask, level))
+    }
+
+    fn read_gpio(&self, handle: FT_HANDLE) -> Result<u32> {
+        self.guard(|| self.inner.read_gpio(handle))
+    }
+
+    fn set_notification_callback(
+        &self,
+        handle: FT_HANDLE,
+        callback: FT_NOTIFICATION_CALLBACK,
+        context: PVOID,
+    ) -> Result<()> {
+        self.guard(|| {
+            self.inner
+                .set_notification_callback(handle, callback, context)
+        })
+    }
+
+    fn clear_notification_callback(&self, handle: FT_HANDLE) -> Result<()> {
+        self.inner.clear_notification_callback(handle)
+    }
+
+    #[cfg(feature = "config")]
+    fn chip_configuration(&self, handle: FT_HANDLE) -> Result<FT_60XCONFIGURATION> {
+        self.guard(|| self.inner.chip_configuration(handle))
+    }
+
+    #[cfg(feature = "config")]
+    fn set_chip_configuration(
+        &self,
+        handle: FT_HANDLE,
+        config: &FT_60XCONFIGURATION,
+    ) -> Result<()> {
+        self.guard(|| self.inner.set_chip_configuration(handle, config))
+    }
+}
@@ -0,0 +1,395 @@
+//! Logging of driver calls, enabled by the `log` feature.
+//!
+//! Every [`D3xxApi`] given to a [`Device`](crate::Device) or used to enumerate devices is
+//! wrapped in [`Logged`], which logs each call along with its outcome. Calls which may happen many times per second
+//! (pipe I/O, GPIO, and pipe information) are logged at the `trace` level, and all others
+//! at the `debug` level.
+
+use std::{mem::MaybeUninit, sync::Arc};
+
+use log::{debug, trace};
+
+#[cfg(feature = "config")]
+use super::FT_60XCONFIGURATION;
+use super::{
+    api::D3xxApi, _OVERLAPPED, FT_CONFIGURATION_DESCRIPTOR, FT_DEVICE_DESCRIPTOR,
+    FT_DEVICE_LIST_INFO_NODE, FT_HANDLE, FT_INTERFACE_DESCRIPTOR, FT_NOTIFICATION_CALLBACK,
+    FT_PIPE_INFORMATION, FT_STRING_DESCRIPTOR, PVOID, ULONG,
+};
+use crate::{Result, StreamPipeTarget};
+
+/// [`D3xxApi`] implementation which logs calls before forwarding them to another.
+pub(crate) struct Logged(pub(crate) Arc<dyn D3xxApi>);
+
+impl D3xxApi for Logged {
+    fn create(&self, arg: PVOID, flags: ULONG) -> Result<FT_HANDLE> {
+        let result = self.0.create(arg, flags);
+        debug!("FT_Create(flags={flags:#x}) -> {result:?}");
+        result
+    }
+
+    fn close(&self, handle: FT_HANDLE) -> Result<()> {
+        let result = self.0.close(handle);
+        debug!("FT_Close({handle:?}) -> {result:?}");
+        result
+    }
+
+    fn create_device_info_list(&self) -> Result<u32> {
+        let result = self.0.create_device_info_list();
+        debug!("FT_CreateDeviceInfoList() -> {result:?}");
+        result
+    }
+
+    fn device_info_detail(&self, index: u32) -> Result<FT_DEVICE_LIST_INFO_NODE> {
+        let result = self.0.device_info_detail(index);
+        debug!("FT_GetDeviceInfoDetail({index}) -> {result:?}");
+        result
+    }
+
+    fn device_info_list(&self, capacity: u32) -> Result<Vec<FT_DEVICE_LIST_INFO_NODE>> {
+        let result = self.0.device_info_list(capacity);
+        debug!("FT_GetDeviceInfoList({capacity}) -> {result:?}");
+        result
+    }
+
+    fn reset_device_port(&self, handle: FT_HANDLE) -> Result<()> {
+        let result = self.0.reset_device_port(handle);
+        debug!("FT_ResetDevicePort({handle:?}) -> {result:?}");
+        result
+    }
+
+    fn cycle_device_port(&self, handle: FT_HANDLE) -> Result<()> {
+        let result = self.0.cycle_device_port(handle);
+        debug!("FT_CycleDevicePort({handle:?}) -> {result:?}");
+        result
+    }
+
+    fn abort_pipe(&self, handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        let result = self.0.abort_pipe(handle, pipe);
+        debug!("FT_AbortPipe(pipe={pipe:#04x}) -> {result:?}");
+        result
+    }
+
+    fn flush_pipe(&self, handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        let result = self.0.flush_pipe(handle, pipe);
+        debug!("FT_FlushPipe(pipe={pipe:#04x}) -> {result:?}");
+        result
+    }
+
+    fn pipe_information(
+        &self,
+        handle: FT_HANDLE,
+        interface: u8,
+        pipe: u8,
+    ) -> Result<FT_PIPE_INFORMATION> {
+        let result = self.0.pipe_information(handle, interface, pipe);
+        trace!(
+            "FT_GetPipeInformation(interface={interface}, pipe={pipe:#04x}) -> {:?}",
+            result.as_ref().map(|_| ())
+        );
+        result
+    }
+
+    fn vid_pid(&self, handle: FT_HANDLE) -> Result<(u16, u16)> {
+        let result = self.0.vid_pid(handle);
+        trace!("FT_GetVIDPID() -> {result:x?}");
+        result
+    }
+
+    fn driver_version(&self, handle: FT_HANDLE) -> Result<u32> {
+        let result = self.0.driver_version(handle);
+        debug!("FT_GetDriverVersion() -> {result:x?}");
+        result
+    }
+
+    fn library_version(&self) -> Result<u32> {
+        let result = self.0.library_version();
+        debug!("FT_GetLibraryVersion() -> {result:x?}");
+        result
+    }
+
+    fn device_descriptor(&self, handle: FT_HANDLE) -> Result<FT_DEVICE_DESCRIPTOR> {
+        let result = self.0.device_descriptor(handle);
+        trace!(
+            "FT_GetDeviceDescriptor() -> {:?}",
+            result.as_ref().map(|_| ())
+        );
+        result
+    }
+
+    fn configuration_descriptor(&self, handle: FT_HANDLE) -> Result<FT_CONFIGURATION_DESCRIPTOR> {
+        let result = self.0.configuration_descriptor(handle);
+        trace!(
+            "FT_GetConfigurationDescriptor() -> {:?}",
+            result.as_ref().map(|_| ())
+        );
+        result
+    }
+
+    fn interface_descriptor(
+        &self,
+        handle: FT_HANDLE,
+        index: u8,
+    ) -> Result<FT_INTERFACE_DESCRIPTOR> {
+        let result = self.0.interface_descriptor(handle, index);
+        trace!(
+            "FT_GetInterfaceDescriptor(index={index}) -> {:?}",
+            result.as_ref().map(|_| ())
+        );
+        result
+    }
+
+    fn string_descriptor(&self, handle: FT_HANDLE, index: u8) -> Result<FT_STRING_DESCRIPTOR> {
+        let result = self.0.string_descriptor(handle, index);
+        trace!(
+            "FT_GetStringDescriptor(index={index}) -> {:?}",
+            result.as_ref().map(|_| ())
+        );
+        result
+    }
+
+    fn read_pipe(&self, handle: FT_HANDLE, pipe: u8, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        let result = self.0.read_pipe(handle, pipe, buf);
+        trace!(
+            "FT_ReadPipe(pipe={pipe:#04x}, len={}) -> {result:?}",
+            buf.len()
+        );
+        result
+    }
+
+    fn write_pipe(&self, handle: FT_HANDLE, pipe: u8, buf: &[u8]) -> Result<usize> {
+        let result = self.0.write_pipe(handle, pipe, buf);
+        trace!(
+            "FT_WritePipe(pipe={pipe:#04x}, len={}) -> {result:?}",
+            buf.len()
+        );
+        result
+    }
+
+    fn read_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &mut [u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        let len = buf.len();
+        let result = self.0.read_pipe_async(handle, pipe, buf, overlapped);
+        trace!("FT_ReadPipe(pipe={pipe:#04x}, len={len}, overlapped) -> {result:?}");
+        result
+    }
+
+    fn write_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &[u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        let result = self.0.write_pipe_async(handle, pipe, buf, overlapped);
+        trace!(
+            "FT_WritePipe(pipe={pipe:#04x}, len={}, overlapped) -> {result:?}",
+            buf.len()
+        );
+        result
+    }
+
+    fn initialize_overlapped(&self, handle: FT_HANDLE) -> Result<_OVERLAPPED> {
+        let result = self.0.initialize_overlapped(handle);
+        trace!(
+            "FT_InitializeOverlapped() -> {:?}",
+            result.as_ref().map(|_| ())
+        );
+        result
+    }
+
+    fn release_overlapped(&self, handle: FT_HANDLE, overlapped: &mut _OVERLAPPED) -> Result<()> {
+        let result = self.0.release_overlapped(handle, overlapped);
+        trace!("FT_ReleaseOverlapped() -> {result:?}");
+        result
+    }
+
+    fn overlapped_result(
+        &self,
+        handle: FT_HANDLE,
+        overlapped: &mut _OVERLAPPED,
+        wait: bool,
+    ) -> Result<usize> {
+        let result = self.0.overlapped_result(handle, overlapped, wait);
+        trace!("FT_GetOverlappedResult(wait={wait}) -> {result:?}");
+        result
+    }
+
+    fn set_stream_pipe(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        target: StreamPipeTarget,
+        size: Option<u32>,
+    ) -> Result<()> {
+        let result = self.0.set_stream_pipe(handle, pipe, target, size);
+        debug!("FT_SetStreamPipe(pipe={pipe:#04x}, {target:?}, size={size:?}) -> {result:?}");
+        result
+    }
+
+    fn set_pipe_timeout(&self, handle: FT_HANDLE, pipe: u8, timeout_ms: u32) -> Result<()> {
+        let result = self.0.set_pipe_timeout(handle, pipe, timeout_ms);
+        debug!("FT_SetPipeTimeout(pipe={pipe:#04x}, timeout={timeout_ms}ms) -> {result:?}");
+        result
+    }
+
+    fn pipe_timeout(&self, handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        let result = self.0.pipe_timeout(handle, pipe);
+        debug!("FT_GetPipeTimeout(pipe={pipe:#04x}) -> {result:?}");
+        result
+    }
+
+    fn suspend_timeout(&self, handle: FT_HANDLE) -> Result<u32> {
+        let result = self.0.suspend_timeout(handle);
+        debug!("FT_GetSuspendTimeout() -> {result:?}");
+        result
+    }
+
+    fn set_suspend_timeout(&self, handle: FT_HANDLE, timeout_ms: u32) -> Result<()> {
+        let result = self.0.set_suspend_timeout(handle, timeout_ms);
+        debug!("FT_SetSuspendTimeout(timeout={timeout_ms}ms) -> {result:?}");
+        result
+    }
+
+    fn queue_status(&self, handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        let result = self.0.queue_status(handle, pipe);
+        trace!("queue status(pipe={pipe:#04x}) -> {result:?}");
+        result
+    }
+
+    fn enable_gpio(&self, handle: FT_HANDLE, mask: u32, direction: u32) -> Result<()> {
+        let result = self.0.enable_gpio(handle, mask, direction);
+        trace!("FT_EnableGPIO(mask={mask:#x}, direction={direction:#x}) -> {result:?}");
+        result
+    }
+
+    fn set_gpio_pull(&self, handle: FT_HANDLE, mask: u32, pull: u32) -> Result<()> {
+        let result = self.0.set_gpio_pull(handle, mask, pull);
+        trace!("FT_SetGPIOPull(mask={mask:#x}, pull={pull:#x}) -> {result:?}");
+        result
+    }
+
+    fn write_gpio(&self, handle: FT_HANDLE, mask: u32, level: u32) -> Result<()> {
+        let result = self.0.write_gpio(handle, mask, level);
+        trace!("FT_WriteGPIO(mask={mask:#x}, level={level:#x}) -> {result:?}");
+        result
+    }
+
+    fn read_gpio(&self, handle: FT_HANDLE) -> Result<u32> {
+        let result = self.0.read_gpio(handle);
+        trace!("FT_ReadGPIO() -> {result:?}");
+        result
+    }
+
+    fn set_notification_callback(
+        &self,
+        handle: FT_HANDLE,
+        callback: FT_NOTIFICATION_CALLBACK,
+        context: PVOID,
+    ) -> Result<()> {
+        let result = self.0.set_notification_callback(handle, callback, context);
+        debug!("FT_SetNotificationCallback() -> {result:?}");
+        result
+    }
+
+    fn clear_notification_callback(&self, handle: FT_HANDLE) -> Result<()> {
+        let result = self.0.clear_notification_callback(handle);
+        debug!("FT_ClearNotificationCallback() -> {result:?}");
+        result
+    }
+
+    #[cfg(feature = "config")]
+    fn chip_configuration(&self, handle: FT_HANDLE) -> Result<FT_60XCONFIGURATION> {
+        let result = self.0.chip_configuration(handle);
+        debug!(
+            "FT_GetChipConfiguration() -> {:?}",
+            result.as_ref().map(|_| ())
+        );
+        result
+    }
+
+    #[cfg(feature = "config")]
+    fn set_chip_configuration(
+        &self,
+        handle: FT_HANDLE,
+        config: &FT_60XCONFIGURATION,
+    ) -> Result<()> {
+        let result = self.0.set_chip_configuration(handle, config);
+        debug!("FT_SetChipConfiguration() -> {result:?}");
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex, Once},
+    };
+
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+
+    use crate::{mock::MockApi, Device, GpioPin, OpenBy, Pipe};
+
+    /// Logger which keeps every record logged by this crate.
+    struct Capture(Mutex<Vec<(Level, String)>>);
+
+    impl Log for Capture {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.target().starts_with("d3xx")
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                let entry = (record.level(), record.args().to_string());
+                self.0.lock().unwrap().push(entry);
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+    fn captured() -> Vec<(Level, String)> {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&CAPTURE).unwrap();
+            log::set_max_level(LevelFilter::Trace);
+        });
+        CAPTURE.0.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn calls_are_logged() {
+        captured();
+        let api = Arc::new(MockApi::default());
+        let device = Device::open_by(api, OpenBy::Serial("SECRET".into())).unwrap();
+        device.pipe(Pipe::Out0).write_all(b"hello").unwrap();
+        device.gpio(GpioPin::Pin0).read().unwrap();
+        drop(device);
+
+        let records = captured();
+        let expected = [
+            (Level::Debug, "FT_Create(flags=0x1) -> Ok(0x1)"),
+            (Level::Trace, "FT_WritePipe(pipe=0x02, len=5) -> Ok(5)"),
+            (Level::Trace, "FT_ReadGPIO() -> Ok(0)"),
+            (Level::Debug, "FT_AbortPipe(pipe=0x82) -> Ok(())"),
+        ];
+        for (level, message) in expected {
+            assert!(
+                records.contains(&(level, message.to_owned())),
+                "missing {message:?} in {records:?}"
+            );
+        }
+        // The serial number is only logged at the lowest level.
+        for (level, message) in records {
+            assert!(level == Level::Trace || !message.contains("SECRET"));
+        }
+    }
+}
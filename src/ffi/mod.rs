@@ -8,16 +8,23 @@
 //! important to ensure that the global lock is held when necessary. See [`with_global_lock`]
 //! for more information. Care should also be taken to ensure that the higher-level abstractions
 //! do not conflict with the use of the raw bindings.
+pub(crate) mod api;
+pub(crate) mod guarded;
+#[cfg(feature = "log")]
+mod logged;
 pub(crate) mod util;
 
-use std::{panic::catch_unwind, sync::Mutex};
+use std::{
+    panic::catch_unwind,
+    sync::{Mutex, PoisonError},
+};
 
 pub use libftd3xx_ffi::*;
 
 use crate::Result;
 
 /// Global lock is necessary for certain operations when working with the D3XX driver.
-static mut GLOBAL_LOCK: Mutex<()> = Mutex::new(()); // FIXME: is a reentrant mutex needed?
+static GLOBAL_LOCK: Mutex<()> = Mutex::new(()); // FIXME: is a reentrant mutex needed?
 
 /// Run the given closure with the global lock held.
 ///
@@ -34,15 +41,14 @@ pub fn with_global_lock<F, R>(f: F) -> R
 where
     F: FnOnce() -> R + std::panic::UnwindSafe,
 {
-    unsafe {
-        // unwrap() is safe because we ensure below that the lock is not poisoned.
-        let lock = GLOBAL_LOCK.lock().unwrap();
-        match catch_unwind(f) {
-            Ok(result) => result,
-            Err(e) => {
-                drop(lock);
-                panic!("panicked while holding global lock: {e:?}");
-            }
+    // The lock is released below before resuming a panic, so it is never poisoned
+    // by this function. Recover anyway in case it was poisoned elsewhere.
+    let lock = GLOBAL_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    match catch_unwind(f) {
+        Ok(result) => result,
+        Err(e) => {
+            drop(lock);
+            panic!("panicked while holding global lock: {e:?}");
         }
     }
 }
@@ -53,8 +59,8 @@ mod tests {
 
     #[test]
     fn test_global_lock() {
-        let _guard = unsafe { GLOBAL_LOCK.lock().unwrap() };
-        assert!(unsafe { GLOBAL_LOCK.try_lock() }.is_err());
+        let _guard = GLOBAL_LOCK.lock().unwrap();
+        assert!(GLOBAL_LOCK.try_lock().is_err());
     }
 
     #[test]
@@ -65,6 +71,13 @@ mod tests {
             });
         });
         assert!(result.is_err());
-        assert!(unsafe { GLOBAL_LOCK.try_lock().is_ok() });
+        assert!(!GLOBAL_LOCK.is_poisoned());
+    }
+
+    #[test]
+    fn test_global_lock_shared() {
+        // `Device::open` and `list_devices` both go through `with_global_lock`,
+        // so holding it must hold the one `GLOBAL_LOCK`.
+        with_global_lock(|| assert!(GLOBAL_LOCK.try_lock().is_err()));
     }
 }
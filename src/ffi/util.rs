@@ -2,9 +2,42 @@
 //!
 //! This module contains functions which are used internally by the crate, but are not
 //! part of the public API. These functions may be changed or removed at any time.
+//!
+//! # Overlapped I/O
+//!
+//! The driver exposes overlapped transfers differently on each platform:
+//!
+//! - On Windows, `FT_ReadPipe` and `FT_WritePipe` take an `LPOVERLAPPED` as their last
+//!   argument. Passing null performs a blocking transfer, while passing a structure
+//!   initialized with `FT_InitializeOverlapped` starts an overlapped one. There are no
+//!   `Async` variants.
+//! - On other platforms, `FT_ReadPipe` and `FT_WritePipe` take a timeout in milliseconds
+//!   instead, and overlapped transfers are started with `FT_ReadPipeAsync` and
+//!   `FT_WritePipeAsync`.
+//!
+//! In both cases the transfer is completed with `FT_GetOverlappedResult`. The
+//! [`read_pipe_async`] and [`write_pipe_async`] functions hide this difference.
 
-use super::*;
+#[cfg(windows)]
+use super::{FT_ReadPipe as FT_ReadPipeOverlapped, FT_WritePipe as FT_WritePipeOverlapped};
+use super::{FT_ReadPipe, FT_WritePipe, Result, _OVERLAPPED, FT_HANDLE, ULONG};
+#[cfg(not(windows))]
+use super::{
+    FT_ReadPipeAsync as FT_ReadPipeOverlapped, FT_WritePipeAsync as FT_WritePipeOverlapped,
+};
 use crate::{try_d3xx, D3xxError};
+use std::mem::MaybeUninit;
+
+/// View an initialized buffer as possibly uninitialized memory.
+///
+/// The returned slice must only ever be written with initialized bytes, such as by
+/// [`read_pipe`], since writing [`MaybeUninit::uninit`] through it would leave `buf`
+/// holding uninitialized bytes.
+pub(crate) fn as_uninit_mut(buf: &mut [u8]) -> &mut [MaybeUninit<u8>] {
+    // SAFETY: `MaybeUninit<u8>` has the same layout as `u8`, and every initialized
+    // byte is a valid `MaybeUninit<u8>`.
+    unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) }
+}
 
 /// Write to a pipe synchronously.
 ///
@@ -58,17 +91,17 @@ pub(crate) fn write_pipe(handle: FT_HANDLE, pipe: u8, buf: &[u8]) -> Result<usiz
     Ok(bytes_written as usize)
 }
 
-/// Asynchronous write to the specified pipe.
+/// Start an asynchronous write to the specified pipe.
 ///
 /// If the operation fails it is the responsibility of the user to
 /// abort any ongoing transfers for the pipe.
 ///
-/// On success the number of bytes written is returned.
+/// The transfer is in progress until `FT_GetOverlappedResult` reports that it has
+/// completed. See the [module documentation](self) for the driver function used.
 ///
 /// # Panics
 ///
 /// Panics if `buf.len()` exceeds [`ULONG::MAX`]
-#[cfg(windows)]
 pub(crate) fn write_pipe_async(
     handle: FT_HANDLE,
     pipe: u8,
@@ -77,7 +110,7 @@ pub(crate) fn write_pipe_async(
 ) -> Result<()> {
     let mut bytes_written: ULONG = 0;
     ignore_io_pending(try_d3xx!(unsafe {
-        FT_WritePipe(
+        FT_WritePipeOverlapped(
             handle,
             pipe,
             buf.as_ptr().cast_mut(),
@@ -88,48 +121,19 @@ pub(crate) fn write_pipe_async(
     }))
 }
 
-/// Asynchronous write to the specified pipe.
-///
-/// If the operation fails it is the responsibility of the user to
-/// abort any ongoing transfers for the pipe.
-///
-/// On success the number of bytes written is returned.
-///
-/// # Panics
-///
-/// Panics if `buf.len()` exceeds [`ULONG::MAX`]
-#[cfg(not(windows))]
-pub fn write_pipe_async(
-    handle: FT_HANDLE,
-    pipe: u8,
-    buf: &[u8],
-    overlapped: &mut _OVERLAPPED,
-) -> Result<()> {
-    let mut bytes_written: ULONG = 0;
-    ignore_io_pending(try_d3xx!(unsafe {
-        FT_WritePipeAsync(
-            handle,
-            pipe as u8,
-            buf.as_ptr().cast_mut(),
-            ULONG::try_from(buf.len()).expect("buffer length exceeds ULONG::MAX"),
-            std::ptr::addr_of_mut!(bytes_written),
-            overlapped as *mut _OVERLAPPED,
-        )
-    }))
-}
-
 /// Read from a pipe synchronously.
 ///
 /// If the operation fails it is the responsibility of the user to
 /// abort any ongoing transfers for the pipe.
 ///
-/// On success the number of bytes read is returned.
+/// On success the number of bytes read is returned, and that many bytes at the
+/// start of `buf` have been initialized.
 ///
 /// # Panics
 ///
 /// Panics if `buf.len()` exceeds [`ULONG::MAX`]
 #[cfg(windows)]
-pub(crate) fn read_pipe(handle: FT_HANDLE, pipe: u8, buf: &mut [u8]) -> Result<usize> {
+pub(crate) fn read_pipe(handle: FT_HANDLE, pipe: u8, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
     let mut bytes_read: ULONG = 0;
     try_d3xx!(unsafe {
         FT_ReadPipe(
@@ -149,18 +153,19 @@ pub(crate) fn read_pipe(handle: FT_HANDLE, pipe: u8, buf: &mut [u8]) -> Result<u
 /// If the operation fails it is the responsibility of the user to
 /// abort any ongoing transfers for the pipe.
 ///
-/// On success the number of bytes read is returned.
+/// On success the number of bytes read is returned, and that many bytes at the
+/// start of `buf` have been initialized.
 ///
 /// # Panics
 ///
 /// Panics if `buf.len()` exceeds [`ULONG::MAX`]
 #[cfg(not(windows))]
-pub(crate) fn read_pipe(handle: FT_HANDLE, pipe: u8, buf: &mut [u8]) -> Result<usize> {
+pub(crate) fn read_pipe(handle: FT_HANDLE, pipe: u8, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
     let mut bytes_read: ULONG = 0;
     try_d3xx!(unsafe {
         FT_ReadPipe(
             handle,
-            pipe as u8,
+            pipe,
             buf.as_mut_ptr().cast(),
             ULONG::try_from(buf.len()).expect("buffer length exceeds ULONG::MAX"),
             std::ptr::addr_of_mut!(bytes_read),
@@ -170,17 +175,17 @@ pub(crate) fn read_pipe(handle: FT_HANDLE, pipe: u8, buf: &mut [u8]) -> Result<u
     Ok(bytes_read as usize)
 }
 
-/// Asynchronous read from the specified pipe.
+/// Start an asynchronous read from the specified pipe.
 ///
 /// If the operation fails it is the responsibility of the user to
 /// abort any ongoing transfers for the pipe.
 ///
-/// On success the number of bytes read is returned.
+/// The transfer is in progress until `FT_GetOverlappedResult` reports that it has
+/// completed. See the [module documentation](self) for the driver function used.
 ///
 /// # Panics
 ///
 /// Panics if `buf.len()` exceeds [`ULONG::MAX`]
-#[cfg(windows)]
 pub(crate) fn read_pipe_async(
     handle: FT_HANDLE,
     pipe: u8,
@@ -189,37 +194,7 @@ pub(crate) fn read_pipe_async(
 ) -> Result<()> {
     let mut bytes_read: ULONG = 0;
     ignore_io_pending(try_d3xx!(unsafe {
-        FT_ReadPipe(
-            handle,
-            pipe,
-            buf.as_mut_ptr().cast(),
-            ULONG::try_from(buf.len()).expect("buffer length exceeds ULONG::MAX"),
-            std::ptr::addr_of_mut!(bytes_read),
-            overlapped as *mut _OVERLAPPED,
-        )
-    }))
-}
-
-/// Asynchronous read from the specified pipe.
-///
-/// If the operation fails it is the responsibility of the user to
-/// abort any ongoing transfers for the pipe.
-///
-/// On success the number of bytes read is returned.
-///
-/// # Panics
-///
-/// Panics if `buf.len()` exceeds [`ULONG::MAX`]
-#[cfg(not(windows))]
-pub fn read_pipe_async(
-    handle: FT_HANDLE,
-    pipe: u8,
-    buf: &mut [u8],
-    overlapped: &mut _OVERLAPPED,
-) -> Result<()> {
-    let mut bytes_read: ULONG = 0;
-    ignore_io_pending(try_d3xx!(unsafe {
-        FT_ReadPipeAsync(
+        FT_ReadPipeOverlapped(
             handle,
             pipe,
             buf.as_mut_ptr().cast(),
@@ -239,3 +214,31 @@ fn ignore_io_pending(res: Result<()>) -> Result<()> {
         x => x,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(windows))]
+    use super::super::DWORD;
+    use super::super::{FT_STATUS, LPOVERLAPPED, PUCHAR, PULONG, UCHAR};
+    use super::*;
+
+    /// Signature of a driver function which starts an overlapped transfer.
+    type StartOverlapped =
+        unsafe extern "C" fn(FT_HANDLE, UCHAR, PUCHAR, ULONG, PULONG, LPOVERLAPPED) -> FT_STATUS;
+
+    /// Signature of a driver function which performs a blocking transfer with a timeout.
+    #[cfg(not(windows))]
+    type Blocking =
+        unsafe extern "C" fn(FT_HANDLE, UCHAR, PUCHAR, ULONG, PULONG, DWORD) -> FT_STATUS;
+
+    /// These fail to compile if the per-platform contract described in the module
+    /// documentation no longer holds.
+    #[test]
+    fn overlapped_entry_points() {
+        let _: [StartOverlapped; 2] = [FT_ReadPipeOverlapped, FT_WritePipeOverlapped];
+        #[cfg(windows)]
+        let _: [StartOverlapped; 2] = [FT_ReadPipe, FT_WritePipe];
+        #[cfg(not(windows))]
+        let _: [Blocking; 2] = [FT_ReadPipe, FT_WritePipe];
+    }
+}
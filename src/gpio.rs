@@ -3,14 +3,25 @@
 //! A [`Gpio`] instance may be obtained using [`Device::gpio`].
 //! The `Gpio` struct provides methods to enable the GPIO pins, set the GPIO
 //! direction, set the GPIO pull resistors, and read/write the GPIO pins.
+//!
+//! A [`Gpios`] instance may be obtained using [`Device::gpios`] to operate on
+//! both pins with a single driver call.
 
-use std::marker::PhantomData;
+use std::{
+    ops::Not,
+    sync::{
+        mpsc::{self, Receiver},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-use crate::ffi;
-use crate::util::PhantomLifetime;
-use crate::{try_d3xx, Device, Result};
+use crate::{
+    notification::{Notification, NotificationData},
+    D3xxError, Device, Result,
+};
 
 /// Provides read/write access to GPIO pins of the chip.
 ///
@@ -22,20 +33,15 @@ use crate::{try_d3xx, Device, Result};
 /// The lifetime of the `Gpio` instance is tied to the lifetime of the `Device` instance;
 /// the device cannot be closed while the `Gpio` instance is in use.
 pub struct Gpio<'a> {
-    handle: ffi::FT_HANDLE,
+    /// The device the GPIO belongs to.
+    device: &'a Device,
     pin: GpioPin,
-    /// Ties the lifetime of this struct to the lifetime of the source [`Device`](crate::Device) instance.
-    _lifetime_marker: PhantomLifetime<'a>,
 }
 
 impl<'a> Gpio<'a> {
     /// Create a new `Gpio` instance using the given device and GPIO pin.
     pub(crate) fn new(device: &'a Device, pin: GpioPin) -> Self {
-        Self {
-            handle: device.handle(),
-            pin,
-            _lifetime_marker: PhantomData,
-        }
+        Self { device, pin }
     }
 
     /// Enable the GPIO in the given direction.
@@ -45,50 +51,274 @@ impl<'a> Gpio<'a> {
     /// may be set to [`Direction::Input`] to effectively prevent writing
     /// to the GPIO.
     pub fn enable(&self, direction: Direction) -> Result<()> {
-        try_d3xx!(unsafe {
-            ffi::FT_EnableGPIO(
-                self.handle,
-                1u32 << u8::from(self.pin),
-                u32::from(u8::from(direction) << u8::from(self.pin)),
-            )
-        })
+        self.gpios()
+            .enable_masked(self.pin.mask(), u8::from(direction) << u8::from(self.pin))
     }
 
     /// Set internal GPIO pull-up/pull-down resistors.
     ///
     /// Only available for Rev. B parts or later.
     pub fn set_pull(&self, pull: PullMode) -> Result<()> {
-        try_d3xx!(unsafe {
-            ffi::FT_SetGPIOPull(
-                self.handle,
-                1u32 << u8::from(self.pin),
-                u32::from(u8::from(pull) << u8::from(self.pin)),
-            )
-        })
+        self.gpios().set_pull(self.pin.mask(), [pull; 2])
     }
 
     /// Set the status of the GPIO.
     pub fn write(&self, level: Level) -> Result<()> {
-        try_d3xx!(unsafe {
-            ffi::FT_WriteGPIO(
-                self.handle,
-                1u32 << u8::from(self.pin),
-                u32::from(u8::from(level) << u8::from(self.pin)),
-            )
-        })
+        self.gpios()
+            .write(self.pin.mask(), u8::from(level) << u8::from(self.pin))
     }
 
     /// Read the status of the GPIO.
-    #[allow(clippy::missing_panics_doc)]
     pub fn read(&self) -> Result<Level> {
-        let mut value: u32 = 0;
-        try_d3xx!(unsafe { ffi::FT_ReadGPIO(self.handle, &mut value) })?;
-        let bit = ((value >> u8::from(self.pin)) & 1) as u8;
-        // unwrap(): value is guaranteed to be 0 or 1, so there is a matching `Level` variant.
-        Ok(Level::try_from(bit).unwrap())
+        Ok(self.gpios().state()?.level(self.pin))
+    }
+
+    /// Invert the level of the GPIO, returning the new level.
+    ///
+    /// This is a read-modify-write composed of [`read`](Self::read) followed by
+    /// [`write`](Self::write), so the GPIO should be enabled with [`Direction::Output`].
+    /// Reading back the level of an output pin may not be supported by the device; in
+    /// that case the driver error is returned and the GPIO is left unchanged.
+    pub fn toggle(&self) -> Result<Level> {
+        let level = !self.read()?;
+        self.write(level)?;
+        Ok(level)
+    }
+
+    /// Drive the GPIO to `level` for the given duration, then to the opposite level.
+    ///
+    /// This is useful for e.g. resetting downstream logic. The calling thread is blocked
+    /// for the duration of the pulse. The GPIO should be enabled with [`Direction::Output`].
+    ///
+    /// The level of the GPIO is read before it is driven. If driving it to `level` fails,
+    /// the GPIO is restored to that original level and the original error is returned.
+    /// Reading back the level of an output pin may not be supported by the device; in that
+    /// case the original level is assumed to be the opposite of `level`.
+    pub fn pulse(&self, level: Level, duration: Duration) -> Result<()> {
+        /// Drives the GPIO to the given level when dropped.
+        struct Restore<'g, 'a>(&'g Gpio<'a>, Level);
+
+        impl Drop for Restore<'_, '_> {
+            fn drop(&mut self) {
+                let _ = self.0.write(self.1);
+            }
+        }
+
+        let restore = Restore(self, self.read().unwrap_or(!level));
+        self.write(level)?;
+        std::thread::sleep(duration);
+        // Deassert explicitly on the success path so the result can be reported.
+        std::mem::forget(restore);
+        self.write(!level)
+    }
+
+    /// Block until the level of the GPIO changes, returning the new level.
+    ///
+    /// The change is detected through GPIO [notifications](crate::notification), so the
+    /// chip must be configured to send them. While waiting, this replaces any callback set
+    /// with [`Device::set_notification_callback`], and clears it before returning.
+    ///
+    /// Fails with [`D3xxError::Timeout`] if the level does not change within `timeout`.
+    pub fn wait_for_change_blocking(&self, timeout: Duration) -> Result<Level> {
+        let (sender, receiver) = mpsc::channel();
+        // The mutex makes the closure unwind safe; it is never contended.
+        let sender = Mutex::new(sender);
+        let callback = self.device.set_notification_callback(
+            move |notification: Notification<()>| {
+                if let NotificationData::Gpio { gpio0, gpio1 } = *notification.data() {
+                    if let Ok(sender) = sender.lock() {
+                        let _ = sender.send(GpioState::from_notification(gpio0, gpio1));
+                    }
+                }
+            },
+            None,
+        )?;
+        let result = self
+            .read()
+            .and_then(|initial| wait_for_change(&receiver, self.pin, initial, timeout));
+        let cleared = callback.clear();
+        let level = result?;
+        cleared?;
+        Ok(level)
+    }
+
+    /// Access to both GPIOs, used to implement the single-pin operations.
+    fn gpios(&self) -> Gpios<'a> {
+        Gpios::new(self.device)
+    }
+}
+
+/// Provides simultaneous read/write access to both GPIO pins of the chip.
+///
+/// Each method operates on a bitmask in which bit `n` corresponds to GPIO pin `n`
+/// (see [`GpioPin::mask`]), so both pins can be updated with a single driver call.
+/// Bits beyond the available pins are ignored.
+///
+/// The lifetime of the `Gpios` instance is tied to the lifetime of the `Device` instance;
+/// the device cannot be closed while the `Gpios` instance is in use.
+///
+/// # Example
+///
+/// ```no_run
+/// use d3xx::{Device, GpioPin};
+///
+/// let device = Device::open("ABC123").unwrap();
+/// let gpios = device.gpios();
+///
+/// // Enable both pins as outputs, then drive pin 0 high and pin 1 low.
+/// gpios.enable(0b11).unwrap();
+/// gpios
+///     .write(GpioPin::Pin0.mask() | GpioPin::Pin1.mask(), GpioPin::Pin0.mask())
+///     .unwrap();
+/// ```
+pub struct Gpios<'a> {
+    /// The device the GPIOs belong to.
+    device: &'a Device,
+}
+
+impl<'a> Gpios<'a> {
+    /// Create a new `Gpios` instance using the given device.
+    pub(crate) fn new(device: &'a Device) -> Self {
+        Self { device }
+    }
+
+    /// Enable both GPIOs in the given directions.
+    ///
+    /// A set bit in `directions` enables the corresponding pin as [`Direction::Output`],
+    /// and a cleared bit as [`Direction::Input`].
+    pub fn enable(&self, directions: u8) -> Result<()> {
+        self.enable_masked(GPIO_MASK, directions)
+    }
+
+    /// Enable the GPIOs selected by `mask` in the given directions.
+    pub(crate) fn enable_masked(&self, mask: u8, directions: u8) -> Result<()> {
+        self.device.api().enable_gpio(
+            self.device.ffi_handle(),
+            u32::from(mask & GPIO_MASK),
+            u32::from(directions & mask & GPIO_MASK),
+        )
+    }
+
+    /// Set the levels of the GPIOs selected by `mask`.
+    ///
+    /// A set bit in `values` drives the corresponding pin [`Level::High`], and a cleared
+    /// bit drives it [`Level::Low`]. Pins not selected by `mask` are left unchanged.
+    pub fn write(&self, mask: u8, values: u8) -> Result<()> {
+        self.device.api().write_gpio(
+            self.device.ffi_handle(),
+            u32::from(mask & GPIO_MASK),
+            u32::from(values & mask & GPIO_MASK),
+        )
+    }
+
+    /// Set the internal pull-up/pull-down resistors of the GPIOs selected by `mask`.
+    ///
+    /// `modes[n]` is the pull mode for GPIO pin `n`. Pins not selected by `mask` are left
+    /// unchanged. Only available for Rev. B parts or later.
+    pub fn set_pull(&self, mask: u8, modes: [PullMode; 2]) -> Result<()> {
+        let (mask, pull) = pull_bits(mask, modes);
+        self.device
+            .api()
+            .set_gpio_pull(self.device.ffi_handle(), mask, pull)
+    }
+
+    /// Read the levels of both GPIOs.
+    ///
+    /// A set bit indicates that the corresponding pin is [`Level::High`].
+    pub fn read(&self) -> Result<u8> {
+        Ok(self.state()?.bits())
+    }
+
+    /// Read the levels of both GPIOs as a [`GpioState`].
+    pub fn state(&self) -> Result<GpioState> {
+        let raw = self.device.api().read_gpio(self.device.ffi_handle())?;
+        Ok(GpioState::from_raw(raw))
+    }
+}
+
+/// Levels of both GPIO pins, read with a single driver call.
+///
+/// This is returned by [`Device::gpio_state`] and [`Gpios::state`]. The driver only
+/// reports pin levels; the directions and pull modes the pins were configured with
+/// cannot be read back.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct GpioState {
+    /// Bitmask of pins at [`Level::High`].
+    levels: u8,
+}
+
+impl GpioState {
+    /// Decode the value reported by `FT_ReadGPIO`, ignoring bits beyond the available pins.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn from_raw(raw: u32) -> Self {
+        Self {
+            levels: raw as u8 & GPIO_MASK,
+        }
+    }
+
+    /// Create a state from the values reported by a GPIO notification.
+    fn from_notification(gpio0: usize, gpio1: usize) -> Self {
+        Self {
+            levels: u8::from(gpio0 != 0) | u8::from(gpio1 != 0) << 1,
+        }
+    }
+
+    /// Get the level of the given pin.
+    #[must_use]
+    pub fn level(&self, pin: GpioPin) -> Level {
+        if self.levels & pin.mask() == 0 {
+            Level::Low
+        } else {
+            Level::High
+        }
+    }
+
+    /// Get the levels as a bitmask in which a set bit indicates that the corresponding
+    /// pin is [`Level::High`] (see [`GpioPin::mask`]).
+    #[must_use]
+    pub fn bits(&self) -> u8 {
+        self.levels
+    }
+}
+
+/// Encode pull modes as the mask and value arguments of `FT_SetGPIOPull`.
+///
+/// The mask has one bit per pin, while the value has two bits per pin.
+fn pull_bits(mask: u8, modes: [PullMode; 2]) -> (u32, u32) {
+    let mask = mask & GPIO_MASK;
+    let pull = [GpioPin::Pin0, GpioPin::Pin1]
+        .into_iter()
+        .filter(|pin| mask & pin.mask() != 0)
+        .fold(0, |pull, pin| {
+            let shift = 2 * u8::from(pin);
+            pull | u32::from(u8::from(modes[usize::from(u8::from(pin))])) << shift
+        });
+    (u32::from(mask), pull)
+}
+
+/// Wait for a GPIO state in which `pin` is no longer at the `initial` level.
+fn wait_for_change(
+    receiver: &Receiver<GpioState>,
+    pin: GpioPin,
+    initial: Level,
+    timeout: Duration,
+) -> Result<Level> {
+    let start = Instant::now();
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        let state = receiver
+            .recv_timeout(remaining)
+            .map_err(|_| D3xxError::Timeout)?;
+        let level = state.level(pin);
+        if level != initial {
+            return Ok(level);
+        }
     }
 }
 
+/// Mask covering all GPIO pins.
+const GPIO_MASK: u8 = 0b11;
+
 /// GPIO pin, either `Pin0` or `Pin1`.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
@@ -103,6 +333,20 @@ pub enum GpioPin {
     Pin1 = 1,
 }
 
+impl GpioPin {
+    /// All GPIO pins, in ascending order.
+    #[must_use]
+    pub const fn all() -> [Self; 2] {
+        [Self::Pin0, Self::Pin1]
+    }
+
+    /// The bit corresponding to this pin in the masks used by [`Gpios`].
+    #[must_use]
+    pub fn mask(self) -> u8 {
+        1 << u8::from(self)
+    }
+}
+
 /// GPIO direction.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
@@ -123,6 +367,17 @@ pub enum Level {
     High = 1,
 }
 
+impl Not for Level {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::Low => Self::High,
+            Self::High => Self::Low,
+        }
+    }
+}
+
 /// GPIO pull mode.
 ///
 /// This can be configured once opening a device.
@@ -136,3 +391,395 @@ pub enum PullMode {
     /// 50 kOhm pull-up.
     PullUp = 2,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        mock::{self, Call, MockApi},
+        D3xxError,
+    };
+
+    #[test]
+    fn level_not() {
+        assert_eq!(!Level::Low, Level::High);
+        assert_eq!(!Level::High, Level::Low);
+    }
+
+    #[test]
+    fn pin_all() {
+        assert_eq!(GpioPin::all(), [GpioPin::Pin0, GpioPin::Pin1]);
+    }
+
+    #[test]
+    fn pin_mask() {
+        assert_eq!(GpioPin::Pin0.mask(), 0b01);
+        assert_eq!(GpioPin::Pin1.mask(), 0b10);
+    }
+
+    #[test]
+    fn gpios_write() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let gpios = device.gpios();
+
+        gpios.write(0b11, 0b01).unwrap();
+        assert_eq!(api.gpio(), 0b01);
+        gpios.write(0b10, 0b11).unwrap();
+        assert_eq!(api.gpio(), 0b11);
+        // Bits outside of the mask or the available pins are ignored.
+        gpios.write(0b1101, 0b1110).unwrap();
+        assert_eq!(api.gpio(), 0b10);
+        assert_eq!(
+            api.calls(),
+            [
+                Call::WriteGpio {
+                    mask: 0b11,
+                    level: 0b01
+                },
+                Call::WriteGpio {
+                    mask: 0b10,
+                    level: 0b10
+                },
+                Call::WriteGpio {
+                    mask: 0b01,
+                    level: 0b00
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn gpios_read() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+
+        api.set_gpio(0b10);
+        assert_eq!(device.gpios().read(), Ok(0b10));
+        api.set_gpio(0xFFFF_FFFD);
+        assert_eq!(device.gpios().read(), Ok(0b01));
+    }
+
+    #[test]
+    fn gpio_state_from_raw() {
+        let state = GpioState::from_raw(0b10);
+        assert_eq!(state.level(GpioPin::Pin0), Level::Low);
+        assert_eq!(state.level(GpioPin::Pin1), Level::High);
+        assert_eq!(state.bits(), 0b10);
+
+        // Bits beyond the available pins are ignored.
+        let state = GpioState::from_raw(0xFFFF_FFFD);
+        assert_eq!(state.level(GpioPin::Pin0), Level::High);
+        assert_eq!(state.level(GpioPin::Pin1), Level::Low);
+        assert_eq!(state.bits(), 0b01);
+        assert_eq!(GpioState::from_raw(0xFFFF_FFFC).bits(), 0);
+    }
+
+    #[test]
+    fn gpio_state_single_call() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        api.set_gpio(0b11);
+        let state = device.gpio_state().unwrap();
+        assert_eq!(state.level(GpioPin::Pin0), Level::High);
+        assert_eq!(state.level(GpioPin::Pin1), Level::High);
+        assert_eq!(api.calls(), [Call::ReadGpio]);
+    }
+
+    #[test]
+    fn gpio_state_from_notification() {
+        assert_eq!(GpioState::from_notification(0, 0).bits(), 0b00);
+        assert_eq!(GpioState::from_notification(1, 0).bits(), 0b01);
+        assert_eq!(GpioState::from_notification(0, 1).bits(), 0b10);
+        assert_eq!(GpioState::from_notification(1, 1).bits(), 0b11);
+    }
+
+    #[test]
+    fn wait_for_change_resolves() {
+        let (sender, receiver) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            // A change on the other pin does not resolve the wait.
+            sender.send(GpioState::from_notification(0, 1)).unwrap();
+            sender.send(GpioState::from_notification(1, 1)).unwrap();
+        });
+        let level = wait_for_change(
+            &receiver,
+            GpioPin::Pin0,
+            Level::Low,
+            Duration::from_secs(10),
+        );
+        assert_eq!(level, Ok(Level::High));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn wait_for_change_timeout() {
+        let (sender, receiver) = mpsc::channel();
+        sender.send(GpioState::from_notification(0, 0)).unwrap();
+        let level = wait_for_change(
+            &receiver,
+            GpioPin::Pin1,
+            Level::Low,
+            Duration::from_millis(10),
+        );
+        assert_eq!(level, Err(D3xxError::Timeout));
+    }
+
+    #[test]
+    fn pull_bits_encoding() {
+        use PullMode::{HighImpedance, PullDown, PullUp};
+        assert_eq!(pull_bits(0b11, [PullUp, HighImpedance]), (0b11, 0b01_10));
+        assert_eq!(pull_bits(0b11, [PullDown, PullUp]), (0b11, 0b10_00));
+        // Pins not selected by the mask are left out of the value.
+        assert_eq!(pull_bits(0b10, [PullUp, HighImpedance]), (0b10, 0b01_00));
+        assert_eq!(pull_bits(0b01, [PullUp, HighImpedance]), (0b01, 0b00_10));
+        assert_eq!(pull_bits(0b1111_1100, [PullUp, PullUp]), (0, 0));
+    }
+
+    #[test]
+    fn gpios_set_pull_single_call() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        device
+            .gpios()
+            .set_pull(0b11, [PullMode::PullUp, PullMode::HighImpedance])
+            .unwrap();
+        assert_eq!(
+            api.calls(),
+            [Call::SetGpioPull {
+                mask: 0b11,
+                pull: 0b01_10
+            }]
+        );
+    }
+
+    #[test]
+    fn gpio_set_pull() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        device
+            .gpio(GpioPin::Pin1)
+            .set_pull(PullMode::PullUp)
+            .unwrap();
+        assert_eq!(
+            api.calls(),
+            [Call::SetGpioPull {
+                mask: 0b10,
+                pull: 0b10_00
+            }]
+        );
+    }
+
+    #[test]
+    fn gpios_enable() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+
+        device.gpios().enable(0b10).unwrap();
+        device.gpios().enable(0xFF).unwrap();
+        assert_eq!(
+            api.calls(),
+            [
+                Call::EnableGpio {
+                    mask: 0b11,
+                    direction: 0b10
+                },
+                Call::EnableGpio {
+                    mask: 0b11,
+                    direction: 0b11
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn gpio_single_pin() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let gpio = device.gpio(GpioPin::Pin1);
+
+        gpio.enable(Direction::Output).unwrap();
+        gpio.write(Level::High).unwrap();
+        assert_eq!(gpio.read(), Ok(Level::High));
+        assert_eq!(device.gpio(GpioPin::Pin0).read(), Ok(Level::Low));
+        assert_eq!(
+            api.calls()[..2],
+            [
+                Call::EnableGpio {
+                    mask: 0b10,
+                    direction: 0b10
+                },
+                Call::WriteGpio {
+                    mask: 0b10,
+                    level: 0b10
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn toggle() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let gpio = device.gpio(GpioPin::Pin1);
+
+        assert_eq!(gpio.toggle(), Ok(Level::High));
+        assert_eq!(api.gpio(), 0b10);
+        assert_eq!(gpio.toggle(), Ok(Level::Low));
+        assert_eq!(api.gpio(), 0b00);
+        assert_eq!(
+            api.calls(),
+            [
+                Call::ReadGpio,
+                Call::WriteGpio {
+                    mask: 0b10,
+                    level: 0b10
+                },
+                Call::ReadGpio,
+                Call::WriteGpio {
+                    mask: 0b10,
+                    level: 0b00
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn toggle_leaves_other_pin() {
+        let api = Arc::new(MockApi::default());
+        api.set_gpio(0b10);
+        let device = mock::device(&api);
+
+        assert_eq!(device.gpio(GpioPin::Pin0).toggle(), Ok(Level::High));
+        assert_eq!(api.gpio(), 0b11);
+    }
+
+    #[test]
+    fn pulse() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+
+        device
+            .gpio(GpioPin::Pin0)
+            .pulse(Level::High, Duration::from_millis(1))
+            .unwrap();
+        assert_eq!(api.gpio(), 0b00);
+        assert_eq!(
+            api.calls(),
+            [
+                Call::ReadGpio,
+                Call::WriteGpio {
+                    mask: 0b01,
+                    level: 0b01
+                },
+                Call::WriteGpio {
+                    mask: 0b01,
+                    level: 0b00
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pulse_restores_on_error() {
+        let api = Arc::new(MockApi::default());
+        api.set_gpio(0b10);
+        api.fail_once_when(|c| matches!(c, Call::WriteGpio { .. }), D3xxError::IoError);
+        let device = mock::device(&api);
+
+        assert_eq!(
+            device
+                .gpio(GpioPin::Pin1)
+                .pulse(Level::Low, Duration::from_millis(1)),
+            Err(D3xxError::IoError)
+        );
+        assert_eq!(api.gpio(), 0b10);
+        assert_eq!(
+            api.calls(),
+            [
+                Call::ReadGpio,
+                Call::WriteGpio {
+                    mask: 0b10,
+                    level: 0b00
+                },
+                Call::WriteGpio {
+                    mask: 0b10,
+                    level: 0b10
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pulse_restores_original_level() {
+        let api = Arc::new(MockApi::default());
+        // The pin is already at the level of the pulse.
+        api.set_gpio(0b01);
+        api.fail_once_when(|c| matches!(c, Call::WriteGpio { .. }), D3xxError::IoError);
+        let device = mock::device(&api);
+
+        assert_eq!(
+            device
+                .gpio(GpioPin::Pin0)
+                .pulse(Level::High, Duration::from_millis(1)),
+            Err(D3xxError::IoError)
+        );
+        assert_eq!(api.gpio(), 0b01);
+        assert_eq!(
+            api.calls().last(),
+            Some(&Call::WriteGpio {
+                mask: 0b01,
+                level: 0b01
+            })
+        );
+    }
+
+    #[test]
+    fn pulse_without_readback() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(|c| *c == Call::ReadGpio, D3xxError::NotSupported);
+        api.fail_once_when(
+            |c| matches!(c, Call::WriteGpio { level: 0b01, .. }),
+            D3xxError::IoError,
+        );
+        let device = mock::device(&api);
+        let gpio = device.gpio(GpioPin::Pin0);
+
+        // The original level is assumed to be the opposite of the pulse.
+        assert_eq!(
+            gpio.pulse(Level::High, Duration::from_millis(1)),
+            Err(D3xxError::IoError)
+        );
+        assert_eq!(
+            api.calls(),
+            [
+                Call::ReadGpio,
+                Call::WriteGpio {
+                    mask: 0b01,
+                    level: 0b01
+                },
+                Call::WriteGpio {
+                    mask: 0b01,
+                    level: 0b00
+                },
+            ]
+        );
+        assert_eq!(gpio.pulse(Level::Low, Duration::from_millis(1)), Ok(()));
+        assert_eq!(api.gpio(), 0b01);
+    }
+
+    #[test]
+    fn toggle_read_error() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(|c| *c == Call::ReadGpio, D3xxError::NotSupported);
+        let device = mock::device(&api);
+
+        assert_eq!(
+            device.gpio(GpioPin::Pin0).toggle(),
+            Err(D3xxError::NotSupported)
+        );
+        assert_eq!(api.calls(), [Call::ReadGpio]);
+    }
+}
@@ -129,7 +129,12 @@
 #![warn(clippy::all, clippy::pedantic, clippy::cargo, missing_docs)]
 // Allow missing error documentation since the D3XX documentation is vague about error conditions.
 #![allow(clippy::missing_errors_doc, clippy::module_name_repetitions)]
+// Duplicate transitive dependencies are outside of this crate's control.
+#![allow(clippy::multiple_crate_versions)]
 
+#[cfg(feature = "futures")]
+mod async_io;
+mod builder;
 #[cfg(feature = "config")]
 pub mod configuration;
 pub mod descriptor;
@@ -137,29 +142,44 @@ mod device;
 mod error;
 pub mod ffi;
 mod gpio;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod notification;
 mod overlapped;
 mod pipe;
 mod prelude;
 mod scan;
+mod sync_device;
 pub(crate) mod util;
 
-pub use device::Device;
-pub use error::{D3xxError, Result};
-pub use gpio::{Direction, Gpio, GpioPin, Level, PullMode};
-pub use pipe::{Pipe, PipeIo, PipeType};
-pub use scan::{list_devices, DeviceInfo, DeviceType};
+#[cfg(feature = "futures")]
+pub use async_io::AsyncPipe;
+pub use builder::DeviceBuilder;
+pub use device::{Device, DeviceHandle, DeviceKey, OpenBy, RawHandle};
+pub use error::{retry, D3xxError, ErrorContext, Result};
+pub use gpio::{Direction, Gpio, GpioPin, GpioState, Gpios, Level, PullMode};
+pub use overlapped::{block_on, CancelToken, OverlappedPool};
+pub use pipe::{
+    AbortPolicy, BufPipeReader, BufPipeWriter, Pipe, PipeIo, PipeType, StreamGuard,
+    StreamPipeTarget, Throughput,
+};
+pub use scan::{count_devices, device_info_detail, list_devices, DeviceInfo, DeviceType};
+pub use sync_device::{OwnedPipe, PipeReader, PipeWriter, ScopedPipe, SyncDevice};
+
+use ffi::api::{D3xxApi, Ffi};
 
 /// Get the version of the D3XX library.
 ///
-/// This is *not* the driver version.
+/// This is *not* the driver version. The driver version is reported per device, so it
+/// can only be read once a device is open; see [`Device::driver_version`].
 pub fn library_version() -> Result<Version> {
-    let mut version: u32 = 0;
-    try_d3xx!(unsafe { ffi::FT_GetLibraryVersion(&mut version) })?;
-    Ok(Version(version))
+    Ffi.library_version().map(Version)
 }
 
 /// D3XX library or driver version.
+///
+/// The version is encoded as `0x00MMmmbb` for major version `MM`, minor version `mm`,
+/// and build number `bb`, and is displayed as e.g. `1.3.0`.
 pub struct Version(u32);
 
 impl Version {
@@ -178,9 +198,96 @@ impl Version {
     }
 
     /// Build/subversion version number.
+    ///
+    /// This is the low 16 bits of the encoded version, so the upper byte holds the minor
+    /// version. Use [`build_number`](Self::build_number) for the build number alone.
     #[allow(clippy::cast_possible_truncation)]
     #[must_use]
     pub fn build(&self) -> u16 {
         self.0 as u16
     }
+
+    /// Build number, the low byte of the encoded version.
+    #[must_use]
+    pub fn build_number(&self) -> u8 {
+        self.0.to_le_bytes()[0]
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}",
+            self.major(),
+            self.minor(),
+            self.build_number()
+        )
+    }
+}
+
+/// The D3XX library version together with the driver version of a device.
+///
+/// This is returned by [`Device::versions`], and is useful for e.g. support requests.
+/// See [`library_version`] and [`Device::driver_version`] for the difference between them.
+pub struct Versions {
+    library: Version,
+    driver: Version,
+}
+
+impl Versions {
+    /// Version of the D3XX library.
+    #[must_use]
+    pub fn library(&self) -> &Version {
+        &self.library
+    }
+
+    /// Version of the D3XX driver.
+    #[must_use]
+    pub fn driver(&self) -> &Version {
+        &self.driver
+    }
+}
+
+impl std::fmt::Display for Versions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "library {}, driver {}", self.library, self.driver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions() {
+        use std::sync::Arc;
+
+        use mock::{Call, MockApi};
+
+        let api = Arc::new(MockApi::default());
+        api.set_library_version(0x0001_0211);
+        api.set_driver_version(0x0001_0300);
+        let device = mock::device(&api);
+        let versions = device.versions().unwrap();
+        assert_eq!(versions.library().to_string(), "1.2.17");
+        assert_eq!(versions.driver().to_string(), "1.3.0");
+        assert_eq!(versions.to_string(), "library 1.2.17, driver 1.3.0");
+        assert_eq!(api.calls(), [Call::LibraryVersion, Call::DriverVersion]);
+    }
+
+    #[test]
+    fn version() {
+        let version = Version(0x0001_0300);
+        assert_eq!(version.major(), 1);
+        assert_eq!(version.minor(), 3);
+        assert_eq!(version.build(), 0x0300);
+        assert_eq!(version.build_number(), 0);
+        assert_eq!(version.to_string(), "1.3.0");
+
+        let version = Version(0x0001_0211);
+        assert_eq!(version.build(), 0x0211);
+        assert_eq!(version.build_number(), 0x11);
+        assert_eq!(version.to_string(), "1.2.17");
+    }
 }
@@ -0,0 +1,530 @@
+//! In-memory device which echoes written data back to the host.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::c_void,
+    mem::MaybeUninit,
+    ptr::NonNull,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "config")]
+use crate::ffi::FT_60XCONFIGURATION;
+use crate::{
+    ffi::{
+        util::as_uninit_mut, _OVERLAPPED, FT_CONFIGURATION_DESCRIPTOR, FT_DEVICE_DESCRIPTOR,
+        FT_DEVICE_LIST_INFO_NODE, FT_HANDLE, FT_INTERFACE_DESCRIPTOR, FT_NOTIFICATION_CALLBACK,
+        FT_PIPE_INFORMATION, FT_PIPE_TYPE, FT_STRING_DESCRIPTOR, PVOID, ULONG,
+    },
+    D3xxError, Device, Pipe, Result, StreamPipeTarget,
+};
+
+use super::{lock, Completions, D3xxApi};
+
+/// Pipe timeout used until one is set, matching the driver's default.
+const DEFAULT_TIMEOUT_MS: u32 = 5000;
+
+/// Suspend timeout used until one is set, matching the driver's default.
+const DEFAULT_SUSPEND_TIMEOUT_MS: u32 = 10_000;
+
+/// Bit set in the IDs of IN pipes.
+const IN_PIPE_MASK: u8 = 0x80;
+
+/// A [`D3xxApi`] implementation in which data written to an OUT pipe becomes readable
+/// from the paired IN pipe (e.g. [`Out1`](crate::Pipe::Out1) and [`In1`](crate::Pipe::In1)).
+///
+/// Reads block until data is available or the pipe's timeout elapses, in which case they fail
+/// with [`D3xxError::Timeout`]. As with the driver, the timeout defaults to 5 seconds and a
+/// timeout of zero waits indefinitely. If a stream size is set on the IN pipe, a read waits
+/// until at least that many bytes (or enough to fill the buffer) are available.
+/// Flushing an IN pipe discards any data waiting to be read from it.
+///
+/// Asynchronous transfers are performed synchronously when they are started.
+///
+/// The GPIO register is simulated; the chip configuration is not supported.
+///
+/// # Example
+///
+/// ```
+/// use std::io::{Read, Write};
+/// use d3xx::{mock::LoopbackDevice, Pipe};
+///
+/// let device = LoopbackDevice::open();
+/// device.pipe(Pipe::Out1).write_all(b"ping").unwrap();
+///
+/// let mut buf = [0; 4];
+/// device.pipe(Pipe::In1).read_exact(&mut buf).unwrap();
+/// assert_eq!(&buf, b"ping");
+/// ```
+#[derive(Debug, Default)]
+pub struct LoopbackDevice {
+    state: Mutex<State>,
+    /// Notified whenever data is written.
+    written: Condvar,
+    completions: Completions,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Data waiting to be read from each IN pipe.
+    buffers: HashMap<u8, VecDeque<u8>>,
+    stream_sizes: HashMap<u8, u32>,
+    timeouts: HashMap<u8, u32>,
+    suspend_timeout: Option<u32>,
+    gpio: u32,
+}
+
+impl LoopbackDevice {
+    /// Create a [`Device`] backed by a new loopback device.
+    #[must_use]
+    pub fn open() -> Device {
+        super::device(&Arc::new(Self::default()))
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+}
+
+impl D3xxApi for LoopbackDevice {
+    fn create(&self, _arg: PVOID, _flags: ULONG) -> Result<FT_HANDLE> {
+        Ok(NonNull::<c_void>::dangling().as_ptr())
+    }
+
+    fn close(&self, _handle: FT_HANDLE) -> Result<()> {
+        Ok(())
+    }
+
+    /// Reports the loopback device as the only one connected.
+    fn create_device_info_list(&self) -> Result<u32> {
+        Ok(1)
+    }
+
+    fn device_info_detail(&self, index: u32) -> Result<FT_DEVICE_LIST_INFO_NODE> {
+        if index != 0 {
+            return Err(D3xxError::NoMoreItems);
+        }
+        Ok(FT_DEVICE_LIST_INFO_NODE {
+            Flags: 0,
+            Type: 601,
+            ID: 0,
+            LocId: 0,
+            SerialNumber: [0; 16],
+            Description: [0; 32],
+            ftHandle: std::ptr::null_mut(),
+        })
+    }
+
+    fn device_info_list(&self, capacity: u32) -> Result<Vec<FT_DEVICE_LIST_INFO_NODE>> {
+        let node = self.device_info_detail(0)?;
+        Ok(std::iter::repeat(node)
+            .take(capacity.min(1) as usize)
+            .collect())
+    }
+
+    fn reset_device_port(&self, _handle: FT_HANDLE) -> Result<()> {
+        self.state().buffers.clear();
+        Ok(())
+    }
+
+    fn cycle_device_port(&self, _handle: FT_HANDLE) -> Result<()> {
+        self.state().buffers.clear();
+        Ok(())
+    }
+
+    fn abort_pipe(&self, _handle: FT_HANDLE, _pipe: u8) -> Result<()> {
+        Ok(())
+    }
+
+    fn flush_pipe(&self, _handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        self.state().buffers.remove(&pipe);
+        Ok(())
+    }
+
+    /// Reports every pipe as a bulk pipe with a maximum packet size of 1024 bytes.
+    fn pipe_information(
+        &self,
+        _handle: FT_HANDLE,
+        _interface: u8,
+        pipe: u8,
+    ) -> Result<FT_PIPE_INFORMATION> {
+        Ok(FT_PIPE_INFORMATION {
+            PipeType: FT_PIPE_TYPE::FTPipeTypeBulk,
+            PipeId: pipe,
+            MaximumPacketSize: 1024,
+            Interval: 0,
+        })
+    }
+
+    fn vid_pid(&self, _handle: FT_HANDLE) -> Result<(u16, u16)> {
+        Ok((0, 0))
+    }
+
+    fn driver_version(&self, _handle: FT_HANDLE) -> Result<u32> {
+        Ok(0)
+    }
+
+    fn library_version(&self) -> Result<u32> {
+        Ok(0)
+    }
+
+    fn device_descriptor(&self, _handle: FT_HANDLE) -> Result<FT_DEVICE_DESCRIPTOR> {
+        Ok(FT_DEVICE_DESCRIPTOR::default())
+    }
+
+    fn configuration_descriptor(&self, _handle: FT_HANDLE) -> Result<FT_CONFIGURATION_DESCRIPTOR> {
+        Ok(FT_CONFIGURATION_DESCRIPTOR::default())
+    }
+
+    fn interface_descriptor(
+        &self,
+        _handle: FT_HANDLE,
+        index: u8,
+    ) -> Result<FT_INTERFACE_DESCRIPTOR> {
+        Ok(FT_INTERFACE_DESCRIPTOR {
+            bInterfaceNumber: index,
+            ..FT_INTERFACE_DESCRIPTOR::default()
+        })
+    }
+
+    fn string_descriptor(&self, _handle: FT_HANDLE, _index: u8) -> Result<FT_STRING_DESCRIPTOR> {
+        Ok(FT_STRING_DESCRIPTOR::default())
+    }
+
+    fn read_pipe(
+        &self,
+        _handle: FT_HANDLE,
+        pipe: u8,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Result<usize> {
+        if pipe & IN_PIPE_MASK == 0 {
+            return Err(D3xxError::InvalidParameter);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut state = self.state();
+        let stream_size = state.stream_sizes.get(&pipe).map_or(1, |&s| s as usize);
+        let wanted = buf.len().min(stream_size);
+        let timeout = state
+            .timeouts
+            .get(&pipe)
+            .copied()
+            .unwrap_or(DEFAULT_TIMEOUT_MS);
+        let deadline =
+            (timeout != 0).then(|| Instant::now() + Duration::from_millis(u64::from(timeout)));
+
+        while state.buffers.get(&pipe).map_or(0, VecDeque::len) < wanted {
+            state = match deadline {
+                None => self
+                    .written
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(D3xxError::Timeout);
+                    }
+                    self.written
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+            };
+        }
+
+        let buffer = state.buffers.entry(pipe).or_default();
+        let n = buffer.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(buffer.drain(..n)) {
+            dst.write(src);
+        }
+        Ok(n)
+    }
+
+    fn write_pipe(&self, _handle: FT_HANDLE, pipe: u8, buf: &[u8]) -> Result<usize> {
+        if pipe & IN_PIPE_MASK != 0 {
+            return Err(D3xxError::InvalidParameter);
+        }
+        self.state()
+            .buffers
+            .entry(pipe | IN_PIPE_MASK)
+            .or_default()
+            .extend(buf);
+        self.written.notify_all();
+        Ok(buf.len())
+    }
+
+    fn read_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &mut [u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        let result = self.read_pipe(handle, pipe, as_uninit_mut(buf));
+        self.completions.complete(overlapped, result);
+        Ok(())
+    }
+
+    fn write_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &[u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        let result = self.write_pipe(handle, pipe, buf);
+        self.completions.complete(overlapped, result);
+        Ok(())
+    }
+
+    fn initialize_overlapped(&self, _handle: FT_HANDLE) -> Result<_OVERLAPPED> {
+        Ok(self.completions.initialize())
+    }
+
+    fn release_overlapped(&self, _handle: FT_HANDLE, overlapped: &mut _OVERLAPPED) -> Result<()> {
+        self.completions.release(overlapped);
+        Ok(())
+    }
+
+    fn overlapped_result(
+        &self,
+        _handle: FT_HANDLE,
+        overlapped: &mut _OVERLAPPED,
+        _wait: bool,
+    ) -> Result<usize> {
+        self.completions.result(overlapped)
+    }
+
+    fn set_stream_pipe(
+        &self,
+        _handle: FT_HANDLE,
+        pipe: u8,
+        target: StreamPipeTarget,
+        size: Option<u32>,
+    ) -> Result<()> {
+        let mut state = self.state();
+        for id in Pipe::all().map(u8::from) {
+            let included = match target {
+                StreamPipeTarget::ThisPipe => id == pipe,
+                StreamPipeTarget::AllIn => id & IN_PIPE_MASK != 0,
+                StreamPipeTarget::AllOut => id & IN_PIPE_MASK == 0,
+            };
+            if included {
+                match size {
+                    Some(size) => state.stream_sizes.insert(id, size),
+                    None => state.stream_sizes.remove(&id),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn set_pipe_timeout(&self, _handle: FT_HANDLE, pipe: u8, timeout_ms: u32) -> Result<()> {
+        self.state().timeouts.insert(pipe, timeout_ms);
+        Ok(())
+    }
+
+    fn pipe_timeout(&self, _handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        Ok(self
+            .state()
+            .timeouts
+            .get(&pipe)
+            .copied()
+            .unwrap_or(DEFAULT_TIMEOUT_MS))
+    }
+
+    fn suspend_timeout(&self, _handle: FT_HANDLE) -> Result<u32> {
+        Ok(self
+            .state()
+            .suspend_timeout
+            .unwrap_or(DEFAULT_SUSPEND_TIMEOUT_MS))
+    }
+
+    fn set_suspend_timeout(&self, _handle: FT_HANDLE, timeout_ms: u32) -> Result<()> {
+        self.state().suspend_timeout = Some(timeout_ms);
+        Ok(())
+    }
+
+    fn queue_status(&self, _handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        let queued = self.state().buffers.get(&pipe).map_or(0, VecDeque::len);
+        Ok(u32::try_from(queued).unwrap_or(u32::MAX))
+    }
+
+    fn enable_gpio(&self, _handle: FT_HANDLE, _mask: u32, _direction: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_gpio_pull(&self, _handle: FT_HANDLE, _mask: u32, _pull: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_gpio(&self, _handle: FT_HANDLE, mask: u32, level: u32) -> Result<()> {
+        let mut state = self.state();
+        state.gpio = (state.gpio & !mask) | (level & mask);
+        Ok(())
+    }
+
+    fn read_gpio(&self, _handle: FT_HANDLE) -> Result<u32> {
+        Ok(self.state().gpio)
+    }
+
+    /// Accepts the callback, but never sends notifications.
+    fn set_notification_callback(
+        &self,
+        _handle: FT_HANDLE,
+        _callback: FT_NOTIFICATION_CALLBACK,
+        _context: PVOID,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn clear_notification_callback(&self, _handle: FT_HANDLE) -> Result<()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "config")]
+    fn chip_configuration(&self, _handle: FT_HANDLE) -> Result<FT_60XCONFIGURATION> {
+        Err(D3xxError::NotSupported)
+    }
+
+    #[cfg(feature = "config")]
+    fn set_chip_configuration(
+        &self,
+        _handle: FT_HANDLE,
+        _config: &FT_60XCONFIGURATION,
+    ) -> Result<()> {
+        Err(D3xxError::NotSupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        thread,
+    };
+
+    use super::*;
+    use crate::Pipe;
+
+    #[test]
+    fn out1_to_in1() {
+        let device = LoopbackDevice::open();
+        device.pipe(Pipe::Out1).write_all(&[1, 2, 3, 4]).unwrap();
+        device.pipe(Pipe::Out1).write_all(&[5, 6]).unwrap();
+
+        let mut buf = [0; 6];
+        device.pipe(Pipe::In1).read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn pipes_are_paired() {
+        let device = LoopbackDevice::open();
+        device.pipe(Pipe::In2).set_timeout(10).unwrap();
+        device.pipe(Pipe::Out1).write_all(&[1]).unwrap();
+
+        let mut buf = [0; 1];
+        let err = device.pipe(Pipe::In2).read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(device.pipe(Pipe::In1).read(&mut buf).unwrap(), 1);
+    }
+
+    #[test]
+    fn wrong_direction() {
+        let device = LoopbackDevice::open();
+        assert!(device.pipe(Pipe::In1).write(&[1]).is_err());
+        assert!(device.pipe(Pipe::Out1).read(&mut [0]).is_err());
+    }
+
+    #[test]
+    fn read_blocks_until_written() {
+        let api = Arc::new(LoopbackDevice::default());
+        let device = crate::mock::device(&api);
+        let writer = thread::spawn({
+            let api = api.clone();
+            move || {
+                thread::sleep(Duration::from_millis(20));
+                let device = crate::mock::device(&api);
+                device.pipe(Pipe::Out0).write_all(b"late").unwrap();
+            }
+        });
+
+        let mut buf = [0; 4];
+        device.pipe(Pipe::In0).read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"late");
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn stream_size_all_in() {
+        let device = LoopbackDevice::open();
+        device
+            .pipe(Pipe::Out0)
+            .set_stream_size_for(StreamPipeTarget::AllIn, Some(4))
+            .unwrap();
+        let mut pipe = device.pipe(Pipe::In1);
+        pipe.set_timeout(10).unwrap();
+
+        device.pipe(Pipe::Out1).write_all(&[1, 2]).unwrap();
+        let mut buf = [0; 8];
+        assert!(pipe.read(&mut buf).is_err());
+        device.pipe(Pipe::Out1).write_all(&[3, 4]).unwrap();
+        assert_eq!(pipe.read(&mut buf).unwrap(), 4);
+    }
+
+    #[test]
+    fn stream_size() {
+        let device = LoopbackDevice::open();
+        let mut pipe = device.pipe(Pipe::In1);
+        pipe.set_timeout(10).unwrap();
+        pipe.set_stream_size(Some(4)).unwrap();
+
+        device.pipe(Pipe::Out1).write_all(&[1, 2]).unwrap();
+        let mut buf = [0; 8];
+        assert!(pipe.read(&mut buf).is_err());
+
+        device.pipe(Pipe::Out1).write_all(&[3, 4, 5]).unwrap();
+        assert_eq!(pipe.read(&mut buf).unwrap(), 5);
+        assert_eq!(buf[..5], [1, 2, 3, 4, 5]);
+
+        pipe.set_stream_size(None).unwrap();
+        device.pipe(Pipe::Out1).write_all(&[6]).unwrap();
+        assert_eq!(pipe.read(&mut buf).unwrap(), 1);
+    }
+
+    #[test]
+    fn pending_bytes() {
+        let device = LoopbackDevice::open();
+        device.pipe(Pipe::Out1).write_all(&[1, 2, 3]).unwrap();
+        assert_eq!(device.pipe(Pipe::In1).pending_bytes(), Ok(3));
+        assert_eq!(device.pipe(Pipe::In0).pending_bytes(), Ok(0));
+
+        device.pipe(Pipe::In1).read_exact(&mut [0; 2]).unwrap();
+        assert_eq!(device.pipe(Pipe::In1).pending_bytes(), Ok(1));
+    }
+
+    #[test]
+    fn flush_discards_data() {
+        let device = LoopbackDevice::open();
+        device.pipe(Pipe::In1).set_timeout(10).unwrap();
+        device.pipe(Pipe::Out1).write_all(&[1, 2]).unwrap();
+        device.pipe(Pipe::In1).reset().unwrap();
+        assert!(device.pipe(Pipe::In1).read(&mut [0]).is_err());
+    }
+
+    #[test]
+    fn flush_in_pipe_discards_stale_data() {
+        let device = LoopbackDevice::open();
+        device.pipe(Pipe::In1).set_timeout(10).unwrap();
+        device.pipe(Pipe::Out1).write_all(&[1, 2]).unwrap();
+        device.pipe(Pipe::In1).flush_pipe().unwrap();
+        device.pipe(Pipe::Out1).write_all(&[3]).unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(device.pipe(Pipe::In1).read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 3);
+    }
+}
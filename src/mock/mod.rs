@@ -0,0 +1,924 @@
+//! Hardware-free implementation of the driver, for testing code built on this crate.
+//!
+//! This module is only available with the `mock` feature enabled.
+//!
+//! A [`MockApi`] records every driver call made through it and simulates pipes, the GPIO
+//! register, and the chip configuration. Failures can be injected for any call. Use [`device`]
+//! to obtain a [`Device`] backed by a mock; every abstraction reached through that device
+//! (pipes, GPIOs, etc.) then talks to the mock instead of the driver.
+//!
+//! For examples and end-to-end tests, [`LoopbackDevice`] instead echoes data written to
+//! each OUT pipe back through the paired IN pipe.
+//!
+//! # Example
+//!
+//! ```
+//! use std::{io::Read, sync::Arc};
+//! use d3xx::{mock::{self, MockApi}, Pipe};
+//!
+//! let api = Arc::new(MockApi::default());
+//! api.push_read(Pipe::In0.into(), b"hello");
+//!
+//! let device = mock::device(&api);
+//! let mut buf = [0; 5];
+//! device.pipe(Pipe::In0).read_exact(&mut buf).unwrap();
+//! assert_eq!(&buf, b"hello");
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::{c_char, c_void},
+    mem::MaybeUninit,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+mod loopback;
+
+pub use crate::ffi::api::D3xxApi;
+#[cfg(feature = "config")]
+use crate::ffi::FT_60XCONFIGURATION;
+use crate::{
+    ffi::{
+        util::as_uninit_mut, _OVERLAPPED, BOOL, E_FT_NOTIFICATION_CALLBACK_TYPE,
+        FT_CONFIGURATION_DESCRIPTOR, FT_DEVICE_DESCRIPTOR, FT_DEVICE_LIST_INFO_NODE, FT_HANDLE,
+        FT_INTERFACE_DESCRIPTOR, FT_NOTIFICATION_CALLBACK, FT_NOTIFICATION_CALLBACK_INFO_DATA,
+        FT_NOTIFICATION_CALLBACK_INFO_GPIO, FT_PIPE_INFORMATION, FT_PIPE_TYPE,
+        FT_STRING_DESCRIPTOR, PVOID, ULONG,
+    },
+    notification::NotificationData,
+    D3xxError, Device, Result, StreamPipeTarget,
+};
+pub use loopback::LoopbackDevice;
+
+/// A driver call recorded by [`MockApi`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Call {
+    /// `FT_Create`
+    Create {
+        /// Flags passed to the call.
+        flags: u32,
+    },
+    /// `FT_Close`
+    Close,
+    /// `FT_CreateDeviceInfoList`
+    CreateDeviceInfoList,
+    /// `FT_GetDeviceInfoDetail`
+    DeviceInfoDetail {
+        /// Index in the device info list.
+        index: u32,
+    },
+    /// `FT_GetDeviceInfoList`
+    DeviceInfoList,
+    /// `FT_ResetDevicePort`
+    ResetDevicePort,
+    /// `FT_CycleDevicePort`
+    CycleDevicePort,
+    /// `FT_AbortPipe`
+    AbortPipe {
+        /// Pipe ID.
+        pipe: u8,
+    },
+    /// `FT_FlushPipe`
+    FlushPipe {
+        /// Pipe ID.
+        pipe: u8,
+    },
+    /// `FT_GetPipeInformation`
+    PipeInformation {
+        /// Interface index.
+        interface: u8,
+        /// Pipe ID.
+        pipe: u8,
+    },
+    /// `FT_GetVIDPID`
+    VidPid,
+    /// `FT_GetDriverVersion`
+    DriverVersion,
+    /// `FT_GetLibraryVersion`
+    LibraryVersion,
+    /// `FT_GetDeviceDescriptor`
+    DeviceDescriptor,
+    /// `FT_GetConfigurationDescriptor`
+    ConfigurationDescriptor,
+    /// `FT_GetInterfaceDescriptor`
+    InterfaceDescriptor {
+        /// Interface index.
+        index: u8,
+    },
+    /// `FT_GetStringDescriptor`
+    StringDescriptor {
+        /// Index of the string.
+        index: u8,
+    },
+    /// `FT_ReadPipe`
+    ReadPipe {
+        /// Pipe ID.
+        pipe: u8,
+        /// Length of the buffer.
+        len: usize,
+    },
+    /// `FT_WritePipe`
+    WritePipe {
+        /// Pipe ID.
+        pipe: u8,
+        /// Length of the buffer.
+        len: usize,
+    },
+    /// `FT_ReadPipe` in overlapped mode
+    ReadPipeAsync {
+        /// Pipe ID.
+        pipe: u8,
+        /// Length of the buffer.
+        len: usize,
+    },
+    /// `FT_WritePipe` in overlapped mode
+    WritePipeAsync {
+        /// Pipe ID.
+        pipe: u8,
+        /// Length of the buffer.
+        len: usize,
+    },
+    /// `FT_InitializeOverlapped`
+    InitializeOverlapped,
+    /// `FT_ReleaseOverlapped`
+    ReleaseOverlapped,
+    /// `FT_GetOverlappedResult`
+    OverlappedResult,
+    /// `FT_SetStreamPipe` or `FT_ClearStreamPipe`
+    SetStreamPipe {
+        /// Pipe ID.
+        pipe: u8,
+        /// Pipes the call applies to.
+        target: StreamPipeTarget,
+        /// Stream size, or `None` if streaming was disabled.
+        size: Option<u32>,
+    },
+    /// `FT_SetPipeTimeout`
+    SetPipeTimeout {
+        /// Pipe ID.
+        pipe: u8,
+        /// Timeout in milliseconds.
+        timeout_ms: u32,
+    },
+    /// `FT_GetPipeTimeout`
+    PipeTimeout {
+        /// Pipe ID.
+        pipe: u8,
+    },
+    /// `FT_GetSuspendTimeout`
+    SuspendTimeout,
+    /// `FT_SetSuspendTimeout`
+    SetSuspendTimeout {
+        /// Timeout in milliseconds.
+        timeout_ms: u32,
+    },
+    /// `FT_GetReadQueueStatus` or `FT_GetWriteQueueStatus`
+    QueueStatus {
+        /// Pipe ID.
+        pipe: u8,
+    },
+    /// `FT_EnableGPIO`
+    EnableGpio {
+        /// GPIO mask.
+        mask: u32,
+        /// Direction bits.
+        direction: u32,
+    },
+    /// `FT_SetGPIOPull`
+    SetGpioPull {
+        /// GPIO mask.
+        mask: u32,
+        /// Pull bits.
+        pull: u32,
+    },
+    /// `FT_WriteGPIO`
+    WriteGpio {
+        /// GPIO mask.
+        mask: u32,
+        /// Level bits.
+        level: u32,
+    },
+    /// `FT_ReadGPIO`
+    ReadGpio,
+    /// `FT_SetNotificationCallback`
+    SetNotificationCallback,
+    /// `FT_ClearNotificationCallback`
+    ClearNotificationCallback,
+    /// `FT_GetChipConfiguration`
+    #[cfg(feature = "config")]
+    GetChipConfiguration,
+    /// `FT_SetChipConfiguration`
+    #[cfg(feature = "config")]
+    SetChipConfiguration,
+}
+
+/// A failure injected into [`MockApi`].
+struct Failure {
+    matches: Box<dyn Fn(&Call) -> bool + Send>,
+    error: D3xxError,
+    once: bool,
+}
+
+/// Records every call made through it and simulates the GPIO register,
+/// pipes, and chip configuration.
+///
+/// Every pipe is reported as a bulk pipe with a maximum packet size of 1024 bytes.
+#[derive(Default)]
+pub struct MockApi {
+    calls: Mutex<Vec<Call>>,
+    failures: Mutex<Vec<Failure>>,
+    gpio: Mutex<u32>,
+    driver_version: Mutex<u32>,
+    library_version: Mutex<u32>,
+    device_count: Mutex<u32>,
+    device_flags: Mutex<u32>,
+    /// Timeouts set for each pipe.
+    timeouts: Mutex<HashMap<u8, u32>>,
+    /// Suspend timeout, if one has been set.
+    suspend_timeout: Mutex<Option<u32>>,
+    descriptor: Mutex<FT_DEVICE_DESCRIPTOR>,
+    configuration: Mutex<FT_CONFIGURATION_DESCRIPTOR>,
+    strings: Mutex<HashMap<u8, String>>,
+    #[cfg(feature = "config")]
+    chip_config: Mutex<Option<FT_60XCONFIGURATION>>,
+    /// Chunks of data waiting to be read from each pipe.
+    reads: Mutex<HashMap<u8, VecDeque<Vec<u8>>>>,
+    /// Transfers written to each pipe, one entry per call.
+    writes: Mutex<HashMap<u8, Vec<Vec<u8>>>>,
+    /// Outcomes of overlapped transfers.
+    completions: Completions,
+    /// Installed notification callback and the address of its context.
+    notification: Mutex<Option<(NotificationCallback, usize)>>,
+    /// Number of `create`/`close` calls currently in progress.
+    active: AtomicUsize,
+    /// Highest value `active` has reached.
+    max_active: AtomicUsize,
+}
+
+/// Notification callback registered with the driver.
+type NotificationCallback = unsafe extern "C" fn(PVOID, E_FT_NOTIFICATION_CALLBACK_TYPE, PVOID);
+
+/// Outcomes of overlapped transfers, which complete as soon as they are started.
+///
+/// Each overlapped structure is identified by its event handle, which is set to a
+/// unique non-null value when initialized.
+#[derive(Debug, Default)]
+struct Completions {
+    next: AtomicUsize,
+    results: Mutex<HashMap<usize, Result<usize>>>,
+}
+
+impl Completions {
+    fn initialize(&self) -> _OVERLAPPED {
+        let id = self.next.fetch_add(1, Ordering::SeqCst) + 1;
+        // SAFETY: `_OVERLAPPED` consists of integers and pointers, for which zero is valid.
+        let mut overlapped: _OVERLAPPED = unsafe { std::mem::zeroed() };
+        overlapped.hEvent = std::ptr::null_mut::<c_void>().wrapping_add(id);
+        overlapped
+    }
+
+    fn id(overlapped: &_OVERLAPPED) -> usize {
+        overlapped.hEvent as usize
+    }
+
+    fn complete(&self, overlapped: &_OVERLAPPED, result: Result<usize>) {
+        lock(&self.results).insert(Self::id(overlapped), result);
+    }
+
+    /// Outcome of the transfer started with `overlapped`, which fails with
+    /// [`D3xxError::InvalidParameter`] if there is none.
+    fn result(&self, overlapped: &_OVERLAPPED) -> Result<usize> {
+        lock(&self.results)
+            .remove(&Self::id(overlapped))
+            .unwrap_or(Err(D3xxError::InvalidParameter))
+    }
+
+    fn release(&self, overlapped: &_OVERLAPPED) {
+        lock(&self.results).remove(&Self::id(overlapped));
+    }
+}
+
+/// Lock a mutex, ignoring poisoning caused by a panicking test.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Copy a string into a null-terminated C string buffer, truncating it if needed.
+fn c_chars<const N: usize>(value: &str) -> [c_char; N] {
+    let mut out = [0; N];
+    for (dst, &src) in out[..N - 1].iter_mut().zip(value.as_bytes()) {
+        *dst = c_char::from_ne_bytes([src]);
+    }
+    out
+}
+
+impl MockApi {
+    /// Calls made so far, in order.
+    #[must_use]
+    pub fn calls(&self) -> Vec<Call> {
+        lock(&self.calls).clone()
+    }
+
+    /// Highest number of `create`/`close` calls which were in progress at once.
+    #[must_use]
+    pub fn max_concurrent(&self) -> usize {
+        self.max_active.load(Ordering::SeqCst)
+    }
+
+    /// Record entry into a call which should be serialized, lingering briefly
+    /// so that overlapping calls are observed.
+    fn enter(&self) {
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_active.fetch_max(active, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(1));
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Queue a chunk of data to be read from a pipe.
+    ///
+    /// Each read returns at most one chunk. Reading a pipe with no chunks
+    /// queued fails with [`D3xxError::Timeout`].
+    pub fn push_read(&self, pipe: u8, chunk: &[u8]) {
+        lock(&self.reads)
+            .entry(pipe)
+            .or_default()
+            .push_back(chunk.to_vec());
+    }
+
+    /// Transfers written to a pipe so far, one entry per call.
+    #[must_use]
+    pub fn writes(&self, pipe: u8) -> Vec<Vec<u8>> {
+        lock(&self.writes).get(&pipe).cloned().unwrap_or_default()
+    }
+
+    /// Current value of the simulated GPIO register.
+    #[must_use]
+    pub fn gpio(&self) -> u32 {
+        *lock(&self.gpio)
+    }
+
+    /// Set the value of the simulated GPIO register.
+    pub fn set_gpio(&self, value: u32) {
+        *lock(&self.gpio) = value;
+    }
+
+    /// Set the number of devices reported as connected.
+    pub fn set_device_count(&self, count: u32) {
+        *lock(&self.device_count) = count;
+    }
+
+    /// Set the flags reported for every connected device.
+    pub fn set_device_flags(&self, flags: u32) {
+        *lock(&self.device_flags) = flags;
+    }
+
+    /// Set the encoded driver version reported by `FT_GetDriverVersion`.
+    ///
+    /// Until this is called the version is zero.
+    pub fn set_driver_version(&self, version: u32) {
+        *lock(&self.driver_version) = version;
+    }
+
+    /// Set the encoded library version reported by `FT_GetLibraryVersion`.
+    ///
+    /// Until this is called the version is zero.
+    pub fn set_library_version(&self, version: u32) {
+        *lock(&self.library_version) = version;
+    }
+
+    /// Set the simulated device descriptor.
+    ///
+    /// Until this is called the descriptor is zeroed.
+    pub fn set_device_descriptor(&self, descriptor: FT_DEVICE_DESCRIPTOR) {
+        *lock(&self.descriptor) = descriptor;
+    }
+
+    /// Set the simulated configuration descriptor.
+    ///
+    /// Until this is called the descriptor is zeroed. Interface descriptors are
+    /// available for each of the `bNumInterfaces` interfaces.
+    pub fn set_configuration_descriptor(&self, descriptor: FT_CONFIGURATION_DESCRIPTOR) {
+        *lock(&self.configuration) = descriptor;
+    }
+
+    /// Set the simulated string descriptor at `index`.
+    ///
+    /// Strings which have not been set are empty.
+    pub fn set_string(&self, index: u8, value: &str) {
+        lock(&self.strings).insert(index, value.to_owned());
+    }
+
+    /// Current simulated chip configuration, if one has been set.
+    #[cfg(feature = "config")]
+    #[must_use]
+    pub fn chip_config(&self) -> Option<FT_60XCONFIGURATION> {
+        *lock(&self.chip_config)
+    }
+
+    /// Set the simulated chip configuration.
+    #[cfg(feature = "config")]
+    pub fn set_chip_config(&self, config: FT_60XCONFIGURATION) {
+        *lock(&self.chip_config) = Some(config);
+    }
+
+    /// Invoke the installed notification callback with `data`, as the driver would when
+    /// the device sends a notification.
+    ///
+    /// The callback runs on the calling thread. Nothing happens if no callback is
+    /// installed.
+    pub fn notify(&self, data: NotificationData) {
+        let Some((callback, context)) = *lock(&self.notification) else {
+            return;
+        };
+        let context = context as PVOID;
+        // SAFETY: the context stays valid while the callback is installed, and the info
+        // pointer matches the callback type.
+        unsafe {
+            let data_info = |endpoint_byte, size| FT_NOTIFICATION_CALLBACK_INFO_DATA {
+                ulRecvNotificationLength: ULONG::try_from(size).unwrap_or(ULONG::MAX),
+                ucEndpointNo: endpoint_byte,
+            };
+            match data {
+                NotificationData::Data { endpoint, size } => {
+                    let mut info = data_info(endpoint.into(), size);
+                    callback(
+                        context,
+                        E_FT_NOTIFICATION_CALLBACK_TYPE::E_FT_NOTIFICATION_CALLBACK_TYPE_DATA,
+                        std::ptr::addr_of_mut!(info).cast(),
+                    );
+                }
+                NotificationData::Unknown {
+                    endpoint_byte,
+                    size,
+                } => {
+                    let mut info = data_info(endpoint_byte, size);
+                    callback(
+                        context,
+                        E_FT_NOTIFICATION_CALLBACK_TYPE::E_FT_NOTIFICATION_CALLBACK_TYPE_DATA,
+                        std::ptr::addr_of_mut!(info).cast(),
+                    );
+                }
+                NotificationData::Gpio { gpio0, gpio1 } => {
+                    let mut info = FT_NOTIFICATION_CALLBACK_INFO_GPIO {
+                        bGPIO0: BOOL::from(gpio0 != 0),
+                        bGPIO1: BOOL::from(gpio1 != 0),
+                    };
+                    callback(
+                        context,
+                        E_FT_NOTIFICATION_CALLBACK_TYPE::E_FT_NOTIFICATION_CALLBACK_TYPE_GPIO,
+                        std::ptr::addr_of_mut!(info).cast(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Make all subsequent calls matching `matches` fail with the given error.
+    pub fn fail_when(&self, matches: impl Fn(&Call) -> bool + Send + 'static, error: D3xxError) {
+        self.inject(Box::new(matches), error, false);
+    }
+
+    /// Make the next call matching `matches` fail with the given error.
+    pub fn fail_once_when(
+        &self,
+        matches: impl Fn(&Call) -> bool + Send + 'static,
+        error: D3xxError,
+    ) {
+        self.inject(Box::new(matches), error, true);
+    }
+
+    fn inject(&self, matches: Box<dyn Fn(&Call) -> bool + Send>, error: D3xxError, once: bool) {
+        lock(&self.failures).push(Failure {
+            matches,
+            error,
+            once,
+        });
+    }
+
+    /// Describe the device at `index` using the simulated device descriptor and
+    /// strings, with the index as its location ID.
+    fn device_node(&self, index: u32) -> FT_DEVICE_LIST_INFO_NODE {
+        let descriptor = *lock(&self.descriptor);
+        let strings = lock(&self.strings);
+        let string = |index| strings.get(&index).map_or("", String::as_str);
+        FT_DEVICE_LIST_INFO_NODE {
+            Flags: *lock(&self.device_flags),
+            Type: 601,
+            ID: u32::from(descriptor.idVendor) << 16 | u32::from(descriptor.idProduct),
+            LocId: index,
+            SerialNumber: c_chars(string(descriptor.iSerialNumber)),
+            Description: c_chars(string(descriptor.iProduct)),
+            ftHandle: std::ptr::null_mut(),
+        }
+    }
+
+    /// Record a call, returning the injected failure for it, if any.
+    fn record(&self, call: Call) -> Result<()> {
+        lock(&self.calls).push(call);
+        let mut failures = lock(&self.failures);
+        match failures.iter().position(|f| (f.matches)(&call)) {
+            Some(i) if failures[i].once => Err(failures.remove(i).error),
+            Some(i) => Err(failures[i].error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for MockApi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockApi")
+            .field("calls", &*lock(&self.calls))
+            .field("gpio", &self.gpio())
+            .finish_non_exhaustive()
+    }
+}
+
+impl D3xxApi for MockApi {
+    fn create(&self, _arg: PVOID, flags: ULONG) -> Result<FT_HANDLE> {
+        self.enter();
+        self.record(Call::Create { flags })?;
+        Ok(NonNull::<c_void>::dangling().as_ptr())
+    }
+
+    fn close(&self, _handle: FT_HANDLE) -> Result<()> {
+        self.enter();
+        self.record(Call::Close)
+    }
+
+    fn create_device_info_list(&self) -> Result<u32> {
+        self.record(Call::CreateDeviceInfoList)?;
+        Ok(*lock(&self.device_count))
+    }
+
+    fn device_info_detail(&self, index: u32) -> Result<FT_DEVICE_LIST_INFO_NODE> {
+        self.record(Call::DeviceInfoDetail { index })?;
+        if index >= *lock(&self.device_count) {
+            return Err(D3xxError::NoMoreItems);
+        }
+        Ok(self.device_node(index))
+    }
+
+    fn device_info_list(&self, capacity: u32) -> Result<Vec<FT_DEVICE_LIST_INFO_NODE>> {
+        self.record(Call::DeviceInfoList)?;
+        let count = capacity.min(*lock(&self.device_count));
+        Ok((0..count).map(|index| self.device_node(index)).collect())
+    }
+
+    fn reset_device_port(&self, _handle: FT_HANDLE) -> Result<()> {
+        self.record(Call::ResetDevicePort)
+    }
+
+    fn cycle_device_port(&self, _handle: FT_HANDLE) -> Result<()> {
+        self.record(Call::CycleDevicePort)
+    }
+
+    fn abort_pipe(&self, _handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        self.record(Call::AbortPipe { pipe })
+    }
+
+    fn flush_pipe(&self, _handle: FT_HANDLE, pipe: u8) -> Result<()> {
+        self.record(Call::FlushPipe { pipe })
+    }
+
+    fn pipe_information(
+        &self,
+        _handle: FT_HANDLE,
+        interface: u8,
+        pipe: u8,
+    ) -> Result<FT_PIPE_INFORMATION> {
+        self.record(Call::PipeInformation { interface, pipe })?;
+        Ok(FT_PIPE_INFORMATION {
+            PipeType: FT_PIPE_TYPE::FTPipeTypeBulk,
+            PipeId: pipe,
+            MaximumPacketSize: 1024,
+            Interval: 0,
+        })
+    }
+
+    /// Reports the IDs from the simulated device descriptor.
+    fn vid_pid(&self, _handle: FT_HANDLE) -> Result<(u16, u16)> {
+        self.record(Call::VidPid)?;
+        let descriptor = lock(&self.descriptor);
+        Ok((descriptor.idVendor, descriptor.idProduct))
+    }
+
+    fn driver_version(&self, _handle: FT_HANDLE) -> Result<u32> {
+        self.record(Call::DriverVersion)?;
+        Ok(*lock(&self.driver_version))
+    }
+
+    fn library_version(&self) -> Result<u32> {
+        self.record(Call::LibraryVersion)?;
+        Ok(*lock(&self.library_version))
+    }
+
+    fn device_descriptor(&self, _handle: FT_HANDLE) -> Result<FT_DEVICE_DESCRIPTOR> {
+        self.record(Call::DeviceDescriptor)?;
+        Ok(*lock(&self.descriptor))
+    }
+
+    fn configuration_descriptor(&self, _handle: FT_HANDLE) -> Result<FT_CONFIGURATION_DESCRIPTOR> {
+        self.record(Call::ConfigurationDescriptor)?;
+        Ok(*lock(&self.configuration))
+    }
+
+    /// Reports a descriptor numbered after `index` for each configured interface.
+    fn interface_descriptor(
+        &self,
+        _handle: FT_HANDLE,
+        index: u8,
+    ) -> Result<FT_INTERFACE_DESCRIPTOR> {
+        self.record(Call::InterfaceDescriptor { index })?;
+        if index >= lock(&self.configuration).bNumInterfaces {
+            return Err(D3xxError::InvalidParameter);
+        }
+        Ok(FT_INTERFACE_DESCRIPTOR {
+            bInterfaceNumber: index,
+            ..FT_INTERFACE_DESCRIPTOR::default()
+        })
+    }
+
+    fn string_descriptor(&self, _handle: FT_HANDLE, index: u8) -> Result<FT_STRING_DESCRIPTOR> {
+        self.record(Call::StringDescriptor { index })?;
+        let mut descriptor = FT_STRING_DESCRIPTOR::default();
+        if let Some(value) = lock(&self.strings).get(&index) {
+            // Leave room for the null terminator.
+            let len = descriptor.szString.len() - 1;
+            for (dst, src) in descriptor.szString[..len]
+                .iter_mut()
+                .zip(value.encode_utf16())
+            {
+                *dst = src;
+            }
+        }
+        Ok(descriptor)
+    }
+
+    fn read_pipe(
+        &self,
+        _handle: FT_HANDLE,
+        pipe: u8,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Result<usize> {
+        self.record(Call::ReadPipe {
+            pipe,
+            len: buf.len(),
+        })?;
+        let mut reads = lock(&self.reads);
+        let queue = reads.entry(pipe).or_default();
+        let chunk = queue.front_mut().ok_or(D3xxError::Timeout)?;
+        let n = chunk.len().min(buf.len());
+        for (dst, &src) in buf.iter_mut().zip(&chunk[..n]) {
+            dst.write(src);
+        }
+        chunk.drain(..n);
+        if chunk.is_empty() {
+            queue.pop_front();
+        }
+        Ok(n)
+    }
+
+    fn write_pipe(&self, _handle: FT_HANDLE, pipe: u8, buf: &[u8]) -> Result<usize> {
+        self.record(Call::WritePipe {
+            pipe,
+            len: buf.len(),
+        })?;
+        lock(&self.writes)
+            .entry(pipe)
+            .or_default()
+            .push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    /// Completes immediately, as [`read_pipe`](D3xxApi::read_pipe) would.
+    fn read_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &mut [u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        self.record(Call::ReadPipeAsync {
+            pipe,
+            len: buf.len(),
+        })?;
+        let result = self.read_pipe(handle, pipe, as_uninit_mut(buf));
+        self.completions.complete(overlapped, result);
+        Ok(())
+    }
+
+    /// Completes immediately, as [`write_pipe`](D3xxApi::write_pipe) would.
+    fn write_pipe_async(
+        &self,
+        handle: FT_HANDLE,
+        pipe: u8,
+        buf: &[u8],
+        overlapped: &mut _OVERLAPPED,
+    ) -> Result<()> {
+        self.record(Call::WritePipeAsync {
+            pipe,
+            len: buf.len(),
+        })?;
+        let result = self.write_pipe(handle, pipe, buf);
+        self.completions.complete(overlapped, result);
+        Ok(())
+    }
+
+    fn initialize_overlapped(&self, _handle: FT_HANDLE) -> Result<_OVERLAPPED> {
+        self.record(Call::InitializeOverlapped)?;
+        Ok(self.completions.initialize())
+    }
+
+    fn release_overlapped(&self, _handle: FT_HANDLE, overlapped: &mut _OVERLAPPED) -> Result<()> {
+        self.record(Call::ReleaseOverlapped)?;
+        self.completions.release(overlapped);
+        Ok(())
+    }
+
+    fn overlapped_result(
+        &self,
+        _handle: FT_HANDLE,
+        overlapped: &mut _OVERLAPPED,
+        _wait: bool,
+    ) -> Result<usize> {
+        self.record(Call::OverlappedResult)?;
+        self.completions.result(overlapped)
+    }
+
+    fn set_stream_pipe(
+        &self,
+        _handle: FT_HANDLE,
+        pipe: u8,
+        target: StreamPipeTarget,
+        size: Option<u32>,
+    ) -> Result<()> {
+        self.record(Call::SetStreamPipe { pipe, target, size })
+    }
+
+    fn set_pipe_timeout(&self, _handle: FT_HANDLE, pipe: u8, timeout_ms: u32) -> Result<()> {
+        self.record(Call::SetPipeTimeout { pipe, timeout_ms })?;
+        lock(&self.timeouts).insert(pipe, timeout_ms);
+        Ok(())
+    }
+
+    /// Reports the last timeout set for the pipe, or the driver's default of 5 seconds.
+    fn pipe_timeout(&self, _handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        self.record(Call::PipeTimeout { pipe })?;
+        Ok(lock(&self.timeouts).get(&pipe).copied().unwrap_or(5000))
+    }
+
+    /// Reports the number of bytes queued with [`push_read`](Self::push_read) for IN pipes,
+    /// and zero for OUT pipes.
+    /// Reports the last suspend timeout set, or the driver's default of 10 seconds.
+    fn suspend_timeout(&self, _handle: FT_HANDLE) -> Result<u32> {
+        self.record(Call::SuspendTimeout)?;
+        Ok(lock(&self.suspend_timeout).unwrap_or(10_000))
+    }
+
+    fn set_suspend_timeout(&self, _handle: FT_HANDLE, timeout_ms: u32) -> Result<()> {
+        self.record(Call::SetSuspendTimeout { timeout_ms })?;
+        *lock(&self.suspend_timeout) = Some(timeout_ms);
+        Ok(())
+    }
+
+    fn queue_status(&self, _handle: FT_HANDLE, pipe: u8) -> Result<u32> {
+        self.record(Call::QueueStatus { pipe })?;
+        let queued: usize = lock(&self.reads)
+            .get(&pipe)
+            .map_or(0, |chunks| chunks.iter().map(Vec::len).sum());
+        Ok(u32::try_from(queued).unwrap_or(u32::MAX))
+    }
+
+    fn enable_gpio(&self, _handle: FT_HANDLE, mask: u32, direction: u32) -> Result<()> {
+        self.record(Call::EnableGpio { mask, direction })
+    }
+
+    fn set_gpio_pull(&self, _handle: FT_HANDLE, mask: u32, pull: u32) -> Result<()> {
+        self.record(Call::SetGpioPull { mask, pull })
+    }
+
+    fn write_gpio(&self, _handle: FT_HANDLE, mask: u32, level: u32) -> Result<()> {
+        self.record(Call::WriteGpio { mask, level })?;
+        let mut gpio = lock(&self.gpio);
+        *gpio = (*gpio & !mask) | (level & mask);
+        Ok(())
+    }
+
+    fn read_gpio(&self, _handle: FT_HANDLE) -> Result<u32> {
+        self.record(Call::ReadGpio)?;
+        Ok(self.gpio())
+    }
+
+    /// Stores the callback, to be invoked by [`notify`](Self::notify).
+    fn set_notification_callback(
+        &self,
+        _handle: FT_HANDLE,
+        callback: FT_NOTIFICATION_CALLBACK,
+        context: PVOID,
+    ) -> Result<()> {
+        self.record(Call::SetNotificationCallback)?;
+        *lock(&self.notification) = callback.map(|callback| (callback, context as usize));
+        Ok(())
+    }
+
+    fn clear_notification_callback(&self, _handle: FT_HANDLE) -> Result<()> {
+        self.record(Call::ClearNotificationCallback)?;
+        *lock(&self.notification) = None;
+        Ok(())
+    }
+
+    #[cfg(feature = "config")]
+    fn chip_configuration(&self, _handle: FT_HANDLE) -> Result<FT_60XCONFIGURATION> {
+        self.record(Call::GetChipConfiguration)?;
+        self.chip_config().ok_or(D3xxError::OtherError)
+    }
+
+    #[cfg(feature = "config")]
+    fn set_chip_configuration(
+        &self,
+        _handle: FT_HANDLE,
+        config: &FT_60XCONFIGURATION,
+    ) -> Result<()> {
+        self.record(Call::SetChipConfiguration)?;
+        self.set_chip_config(*config);
+        Ok(())
+    }
+}
+
+/// Create a device backed by the given implementation instead of the D3XX driver.
+///
+/// The device is given a dangling handle, which is passed to `api` on every call.
+/// It must not be passed to the D3XX bindings.
+pub fn device<A: D3xxApi + 'static>(api: &Arc<A>) -> Device {
+    // SAFETY: the handle is only ever given to `api`, which never receives a handle
+    // from anywhere else.
+    unsafe { Device::with_api(NonNull::<c_void>::dangling().as_ptr(), api.clone()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+    use crate::Pipe;
+
+    #[test]
+    fn read_exact_across_chunks() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(Pipe::In0.into(), &[1, 2, 3]);
+        api.push_read(Pipe::In0.into(), &[4, 5, 6, 7]);
+        let device = device(&api);
+
+        let mut buf = [0; 7];
+        device.pipe(Pipe::In0).read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(
+            api.calls(),
+            [
+                Call::ReadPipe { pipe: 0x82, len: 7 },
+                Call::ReadPipe { pipe: 0x82, len: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_exact_times_out() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(Pipe::In0.into(), &[1, 2]);
+        let device = device(&api);
+
+        let mut buf = [0; 4];
+        let err = device.pipe(Pipe::In0).read_exact(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn write_all() {
+        let api = Arc::new(MockApi::default());
+        let device = device(&api);
+
+        device.pipe(Pipe::Out0).write_all(b"hello").unwrap();
+        assert_eq!(api.writes(Pipe::Out0.into()), [b"hello".to_vec()]);
+    }
+
+    #[test]
+    fn write_all_error() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |c| matches!(c, Call::WritePipe { .. }),
+            D3xxError::DeviceNotConnected,
+        );
+        let device = device(&api);
+
+        let err = device.pipe(Pipe::Out0).write_all(b"hello").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotConnected);
+        assert!(api.writes(Pipe::Out0.into()).is_empty());
+    }
+}
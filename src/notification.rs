@@ -13,6 +13,9 @@
 //! start/stop signals or status updates. It is not intended for endpoints that
 //! send large amounts of data, such as a video stream.
 //!
+//! Instead of handling notifications inside a callback, they may be collected in a
+//! [`NotificationQueue`] and drained in batches.
+//!
 //! # Example
 //!
 //! A simple use case might look like this:
@@ -42,23 +45,34 @@
 //!         NotificationData::Gpio { gpio0, gpio1 } => {
 //!             println!("GPIO0: {gpio0}, GPIO1: {gpio1}");
 //!         }
+//!         NotificationData::Unknown { endpoint_byte, .. } => {
+//!             println!("Unexpected endpoint: {endpoint_byte:#04x}");
+//!         }
 //!     }
 //! }
 //!
 //! let device = Device::open("serial number").unwrap();
 //! let device = Arc::new(Mutex::new(device));
+//! // Keep the callback installed until the device is closed, instead of clearing it
+//! // when the returned handle is dropped.
 //! device
 //!     .lock()
 //!     .unwrap()
 //!     .set_notification_callback(callback, Some(device.clone()))
-//!     .unwrap();
+//!     .unwrap()
+//!     .leak();
 
 use std::{
+    collections::{HashMap, VecDeque},
     ffi::c_void,
     panic::{AssertUnwindSafe, UnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError,
+    },
 };
 
-use crate::{ffi, try_d3xx, D3xxError, Pipe, Result};
+use crate::{ffi, D3xxError, Device, Pipe, Result};
 
 /// Information regarding a notification sent by a device.
 ///
@@ -79,8 +93,30 @@ pub struct Notification<T: Sync + UnwindSafe> {
 
 impl<T: Sync + UnwindSafe> Notification<T> {
     /// Get the context associated with this notification.
+    ///
+    /// The context is freed once the callback's [`NotificationHandle`] is dropped, so
+    /// the reference is only valid for as long as the notification.
+    ///
+    /// ```compile_fail
+    /// use std::sync::OnceLock;
+    /// use d3xx::{notification::Notification, Device};
+    ///
+    /// static STORED: OnceLock<&'static u32> = OnceLock::new();
+    ///
+    /// let device = Device::open("ABC123").unwrap();
+    /// let handle = device
+    ///     .set_notification_callback(
+    ///         |n: Notification<u32>| {
+    ///             let _ = STORED.set(n.context().unwrap());
+    ///         },
+    ///         Some(1),
+    ///     )
+    ///     .unwrap();
+    /// drop(handle);
+    /// ```
     #[must_use]
-    pub fn context(&self) -> Option<&'static T> {
+    pub fn context(&self) -> Option<&T> {
+        // SAFETY: the context outlives every call of the callback it belongs to.
         unsafe { self.context.as_ref() }
     }
 
@@ -134,10 +170,11 @@ where
 
 /// Data associated with a [`Notification`].
 ///
-/// Two variants are defined: `Data` and `Gpio`. The `Data` variant is used when
-/// data is received on an endpoint, while the `Gpio` variant is used when the
-/// state of the GPIO pins changes. Note that to receive either variant the
-/// corresponding endpoint or GPIO pins must be enabled for notifications.
+/// The `Data` variant is used when data is received on an endpoint, while the `Gpio`
+/// variant is used when the state of the GPIO pins changes. Note that to receive either
+/// variant the corresponding endpoint or GPIO pins must be enabled for notifications.
+/// Data notifications for an endpoint which is not an IN pipe are reported as `Unknown`
+/// rather than dropped.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum NotificationData {
     /// Data notification.
@@ -155,51 +192,349 @@ pub enum NotificationData {
         /// The state of GPIO1.
         gpio1: usize,
     },
+    /// Data notification for an endpoint which is not a known IN pipe.
+    ///
+    /// This is not expected from a correctly functioning driver, but is passed on so that
+    /// the notification is not silently lost.
+    Unknown {
+        /// The endpoint address reported by the driver.
+        endpoint_byte: u8,
+        /// The number of bytes received.
+        size: usize,
+    },
 }
 
-/// Set a notification callback.
+/// Set a notification callback, returning a handle which clears it when dropped.
 ///
 /// Internally this function registers a separate "trampoline" callback with the driver to
 /// support different `T` parameters. The trampoline callback is responsible for calling the
 /// user-provided callback with the correct types. For this, a struct containing extra
-/// information is leaked to provide the trampoline with the necessary information.
-///
-/// # Warning
-///
-/// It is unknown whether the D3XX driver releases the leaked memory when the callback is
-/// cleared because the documentation does not specify this. For now it is assumed that the
-/// memory is released when the callback is cleared/changed. Until this is confirmed, it is
-/// recommended to only set the callback smaller number of times, and with a `T` that is small
-/// enough to not cause memory issues.
+/// information is allocated and given to the driver as the callback context. It is owned
+/// by the returned [`NotificationHandle`], which frees it once the callback is cleared.
 pub(crate) fn set_notification_callback<F, T>(
-    handle: ffi::HANDLE,
+    device: &Device,
     callback: F,
     context: Option<T>,
-) -> Result<()>
+) -> Result<NotificationHandle<'_>>
 where
     T: Sync + UnwindSafe,
     F: Fn(Notification<T>) + UnwindSafe,
 {
-    // TODO: determine whether the memory is freed when the callback is changed.
-    // If it isn't, we can store a pointer to the context in the device and free it with a
-    // destructor closure when the notification callback is changes or when the
-    // device is closed.
-    let internal_context = Box::into_raw(Box::new(InternalContext { callback, context }));
-    try_d3xx!(unsafe {
-        ffi::FT_SetNotificationCallback(handle, Some(trampoline::<T, F>), internal_context.cast())
+    let internal_context: *mut c_void =
+        Box::into_raw(Box::new(InternalContext { callback, context })).cast();
+    in_flight().register(internal_context);
+    let result = device.api().set_notification_callback(
+        device.ffi_handle(),
+        Some(trampoline::<T, F>),
+        internal_context,
+    );
+    if let Err(e) = result {
+        // SAFETY: the driver did not accept the context, so it is still owned here.
+        unsafe { free_context::<T, F>(internal_context) };
+        return Err(e);
+    }
+    device.set_notification_context(internal_context);
+    Ok(NotificationHandle {
+        device,
+        context: internal_context,
+        free: free_context::<T, F>,
     })
 }
 
-/// Clear the notification callback.
+/// Free a context allocated by [`set_notification_callback`].
+///
+/// Clearing a callback stops the driver from starting new calls, but a call which
+/// started before may still be running on another thread. The context is only freed
+/// once every such call has returned; see [`InFlight`].
+///
+/// # Safety
+///
+/// `context` must be a pointer to an [`InternalContext<T, F>`] obtained from
+/// [`Box::into_raw`], for which the driver will not start new calls.
+unsafe fn free_context<T, F>(context: *mut c_void)
+where
+    T: Sync + UnwindSafe,
+    F: Fn(Notification<T>) + UnwindSafe,
+{
+    in_flight().unregister(context);
+    drop(Box::from_raw(context.cast::<InternalContext<T, F>>()));
+}
+
+/// Number of running callback calls for each registered context.
+///
+/// The counts are kept outside of the contexts so that [`trampoline`] can check whether
+/// a context is still alive before touching it. A call for a context which is no longer
+/// registered returns without dereferencing the context.
+#[derive(Default)]
+struct InFlight {
+    /// Running calls, keyed by the address of the context.
+    running: Mutex<HashMap<usize, usize>>,
+    /// Signalled whenever a call returns.
+    returned: Condvar,
+}
+
+impl InFlight {
+    fn running(&self) -> MutexGuard<'_, HashMap<usize, usize>> {
+        self.running.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Start tracking calls for a context.
+    fn register(&self, context: *mut c_void) {
+        self.running().insert(context as usize, 0);
+    }
+
+    /// Stop tracking a context, waiting for its running calls to return.
+    ///
+    /// Once this returns, new calls for the context are turned away by [`enter`](Self::enter).
+    fn unregister(&self, context: *mut c_void) {
+        let key = context as usize;
+        let mut running = self
+            .returned
+            .wait_while(self.running(), |running| {
+                running.get(&key).is_some_and(|&count| count != 0)
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+        running.remove(&key);
+    }
+
+    /// Record the start of a call, returning `false` if the context is not registered.
+    fn enter(&self, context: *mut c_void) -> bool {
+        match self.running().get_mut(&(context as usize)) {
+            Some(count) => {
+                *count += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record the end of a call started with [`enter`](Self::enter).
+    fn exit(&self, context: *mut c_void) {
+        if let Some(count) = self.running().get_mut(&(context as usize)) {
+            *count -= 1;
+        }
+        self.returned.notify_all();
+    }
+}
+
+/// The process-wide [`InFlight`] tracker.
+fn in_flight() -> &'static InFlight {
+    static IN_FLIGHT: OnceLock<InFlight> = OnceLock::new();
+    IN_FLIGHT.get_or_init(InFlight::default)
+}
+
+/// Guard for a notification callback, returned by
+/// [`Device::set_notification_callback`](crate::Device::set_notification_callback).
 ///
-/// See the concerns about this in [`set_notification_callback`].
+/// The handle owns the callback and its context. When it is dropped the callback is
+/// cleared, unless another callback has replaced it in the meantime, and the context is
+/// freed. Since the handle borrows the device, the callback cannot outlive it.
 ///
-/// Note that this function is infallible, and it is unclear why due to incorrect
-/// documentation. On one hand the documentation says that the foreign function returns
-/// `FT_STATUS`, but on the other hand the header/bindings indicate that nothing is returned.
-pub(crate) unsafe fn clear_notification_callback(handle: ffi::HANDLE) {
-    unsafe {
-        ffi::FT_ClearNotificationCallback(handle);
+/// Use [`clear`](Self::clear) to observe errors from clearing the callback, or
+/// [`leak`](Self::leak) to keep the callback installed for as long as the device is open.
+#[must_use = "the notification callback is cleared when the handle is dropped"]
+pub struct NotificationHandle<'a> {
+    device: &'a Device,
+    /// The context given to the driver along with the callback.
+    context: *mut c_void,
+    /// Frees `context`, which is type-erased.
+    free: unsafe fn(*mut c_void),
+}
+
+impl NotificationHandle<'_> {
+    /// Clear the callback and free its context, reporting any error.
+    ///
+    /// If the callback cannot be cleared, its context is leaked since the driver may still
+    /// use it.
+    pub fn clear(self) -> Result<()> {
+        std::mem::ManuallyDrop::new(self).release()
+    }
+
+    /// Keep the callback installed, leaking its context.
+    ///
+    /// The callback may still be cleared with
+    /// [`Device::clear_notification_callback`](crate::Device::clear_notification_callback),
+    /// but the context is never freed.
+    pub fn leak(self) {
+        std::mem::forget(self);
+    }
+
+    fn release(&self) -> Result<()> {
+        if self.device.notification_context() == self.context {
+            self.device.clear_notification_callback()?;
+        }
+        // SAFETY: the callback using the context has been cleared or replaced, and the
+        // context is freed at most once since `self` is consumed or being dropped.
+        unsafe { (self.free)(self.context) };
+        Ok(())
+    }
+}
+
+impl Drop for NotificationHandle<'_> {
+    fn drop(&mut self) {
+        let _ = self.release();
+    }
+}
+
+impl std::fmt::Debug for NotificationHandle<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationHandle")
+            .field("device", &self.device)
+            .field("context", &self.context)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a [`NotificationQueue`] does with a notification arriving while it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum QueueOverflow {
+    /// Discard the oldest queued notification to make room for the new one.
+    #[default]
+    DropOldest,
+    /// Discard the new notification, keeping the queue as it is.
+    DropNewest,
+}
+
+/// Bounded queue of notifications, to be drained in batches.
+///
+/// Creating a queue installs a notification callback which pushes the [`NotificationData`]
+/// of every notification into the queue, replacing any callback set previously. The
+/// notifications can then be collected from e.g. a main loop using [`drain`](Self::drain),
+/// which is useful for bursty devices where handling each notification inside the
+/// callback is impractical.
+///
+/// The queue holds at most `capacity` notifications. Once full, notifications are
+/// discarded according to its [`QueueOverflow`] policy, and counted in
+/// [`dropped`](Self::dropped).
+///
+/// As with [`NotificationHandle`], the callback is cleared when the queue is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use d3xx::{notification::{NotificationQueue, QueueOverflow}, Device};
+///
+/// let device = Device::open("ABC123")?;
+/// let queue = NotificationQueue::new(&device, 64, QueueOverflow::DropOldest)?;
+/// loop {
+///     for data in queue.drain() {
+///         println!("{data:?}");
+///     }
+///     # break;
+/// }
+/// # Ok::<(), d3xx::D3xxError>(())
+/// ```
+pub struct NotificationQueue<'a> {
+    state: Arc<QueueState>,
+    handle: NotificationHandle<'a>,
+}
+
+/// State shared between a [`NotificationQueue`] and its callback.
+struct QueueState {
+    items: Mutex<VecDeque<NotificationData>>,
+    capacity: usize,
+    overflow: QueueOverflow,
+    dropped: AtomicUsize,
+}
+
+impl QueueState {
+    fn items(&self) -> MutexGuard<'_, VecDeque<NotificationData>> {
+        // The queue is never left in an inconsistent state, so poisoning can be ignored.
+        self.items.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, data: NotificationData) {
+        let mut items = self.items();
+        if items.len() == self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            match self.overflow {
+                QueueOverflow::DropOldest => {
+                    items.pop_front();
+                }
+                QueueOverflow::DropNewest => return,
+            }
+        }
+        items.push_back(data);
+    }
+}
+
+impl<'a> NotificationQueue<'a> {
+    /// Install a queue holding up to `capacity` notifications as the notification callback.
+    ///
+    /// Returns [`D3xxError::InvalidArgs`] if `capacity` is zero.
+    pub fn new(device: &'a Device, capacity: usize, overflow: QueueOverflow) -> Result<Self> {
+        if capacity == 0 {
+            return Err(D3xxError::InvalidArgs);
+        }
+        let state = Arc::new(QueueState {
+            items: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            overflow,
+            dropped: AtomicUsize::new(0),
+        });
+        let callback_state = Arc::clone(&state);
+        let handle = set_notification_callback(
+            device,
+            move |notification: Notification<()>| callback_state.push(*notification.data()),
+            None,
+        )?;
+        Ok(Self { state, handle })
+    }
+
+    /// Remove and return all queued notifications, oldest first.
+    #[must_use]
+    pub fn drain(&self) -> Vec<NotificationData> {
+        self.state.items().drain(..).collect()
+    }
+
+    /// Number of notifications currently queued.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.state.items().len()
+    }
+
+    /// Whether no notifications are queued.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.state.items().is_empty()
+    }
+
+    /// Maximum number of notifications held by the queue.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.state.capacity
+    }
+
+    /// Policy applied to notifications arriving while the queue is full.
+    #[must_use]
+    pub fn overflow(&self) -> QueueOverflow {
+        self.state.overflow
+    }
+
+    /// Total number of notifications discarded because the queue was full.
+    #[must_use]
+    pub fn dropped(&self) -> usize {
+        self.state.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Clear the callback, reporting any error.
+    ///
+    /// Notifications still queued are discarded; [`drain`](Self::drain) them first if
+    /// they are needed.
+    pub fn clear(self) -> Result<()> {
+        self.handle.clear()
+    }
+}
+
+impl std::fmt::Debug for NotificationQueue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("overflow", &self.overflow())
+            .field("dropped", &self.dropped())
+            .field("handle", &self.handle)
+            .finish()
     }
 }
 
@@ -220,7 +555,8 @@ pub(crate) unsafe fn clear_notification_callback(handle: ffi::HANDLE) {
 /// between different types. To avoid undefined behavior, the caller must ensure
 /// the following:
 ///
-/// - `callback_context` is a valid pointer to an [`InternalContext<T>`] and `T` is correct.
+/// - `callback_context` was given to the driver by [`set_notification_callback`] along with
+///   this trampoline, so that it points to an [`InternalContext<T, F>`] until it is freed.
 /// - `callback_info` matches the corresponding `callback_type`.
 unsafe extern "C" fn trampoline<T: Sync + UnwindSafe, F: Fn(Notification<T>) + UnwindSafe>(
     callback_context: *mut c_void,
@@ -229,6 +565,10 @@ unsafe extern "C" fn trampoline<T: Sync + UnwindSafe, F: Fn(Notification<T>) + U
 ) {
     let data = extract_notification_data(callback_type, callback_info);
     if let Ok(data) = data {
+        if !in_flight().enter(callback_context) {
+            // The context has been freed.
+            return;
+        }
         let context = &*(callback_context as *const InternalContext<T, F>);
         let callback = AssertUnwindSafe(context.callback());
         let notification = Notification {
@@ -238,7 +578,8 @@ unsafe extern "C" fn trampoline<T: Sync + UnwindSafe, F: Fn(Notification<T>) + U
         if let Err(e) = std::panic::catch_unwind(|| callback(notification)) {
             eprintln!("notification callback panicked: {e:?}");
         }
-    };
+        in_flight().exit(callback_context);
+    }
 }
 
 /// Casts the callback info to the correct [`NotificationData`] variant.
@@ -252,13 +593,17 @@ unsafe fn extract_notification_data(
     callback_type: ffi::E_FT_NOTIFICATION_CALLBACK_TYPE,
     callback_info: *mut c_void,
 ) -> Result<NotificationData> {
-    fn extract_data_variant(callback_info: *mut c_void) -> Result<NotificationData> {
+    fn extract_data_variant(callback_info: *mut c_void) -> NotificationData {
         let callback_info =
             unsafe { *callback_info.cast::<ffi::FT_NOTIFICATION_CALLBACK_INFO_DATA>() };
-        Ok(NotificationData::Data {
-            endpoint: Pipe::try_from(callback_info.ucEndpointNo).or(Err(D3xxError::OtherError))?,
-            size: callback_info.ulRecvNotificationLength as usize,
-        })
+        let size = callback_info.ulRecvNotificationLength as usize;
+        match Pipe::try_from(callback_info.ucEndpointNo) {
+            Ok(endpoint) if endpoint.is_in() => NotificationData::Data { endpoint, size },
+            _ => NotificationData::Unknown {
+                endpoint_byte: callback_info.ucEndpointNo,
+                size,
+            },
+        }
     }
 
     fn extract_gpio_variant(callback_info: *mut c_void) -> NotificationData {
@@ -273,7 +618,7 @@ unsafe fn extract_notification_data(
 
     match callback_type {
         ffi::E_FT_NOTIFICATION_CALLBACK_TYPE::E_FT_NOTIFICATION_CALLBACK_TYPE_DATA => {
-            extract_data_variant(callback_info)
+            Ok(extract_data_variant(callback_info))
         }
         ffi::E_FT_NOTIFICATION_CALLBACK_TYPE::E_FT_NOTIFICATION_CALLBACK_TYPE_GPIO => {
             Ok(extract_gpio_variant(callback_info))
@@ -282,3 +627,276 @@ unsafe fn extract_notification_data(
         _ => Err(D3xxError::OtherError),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, Call, MockApi};
+
+    #[test]
+    fn drop_clears_callback() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let context = Arc::new(());
+        let handle = device
+            .set_notification_callback(|_: Notification<Arc<()>>| {}, Some(context.clone()))
+            .unwrap();
+        assert_eq!(Arc::strong_count(&context), 2);
+        assert_eq!(api.calls(), [Call::SetNotificationCallback]);
+
+        drop(handle);
+        assert_eq!(
+            api.calls(),
+            [
+                Call::SetNotificationCallback,
+                Call::ClearNotificationCallback
+            ]
+        );
+        assert_eq!(Arc::strong_count(&context), 1);
+    }
+
+    #[test]
+    fn replaced_callback_is_not_cleared() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let first = device
+            .set_notification_callback(|_: Notification<()>| {}, None)
+            .unwrap();
+        let second = device
+            .set_notification_callback(|_: Notification<()>| {}, None)
+            .unwrap();
+        drop(first);
+        assert!(!api.calls().contains(&Call::ClearNotificationCallback));
+        second.clear().unwrap();
+        assert_eq!(api.calls().last(), Some(&Call::ClearNotificationCallback));
+    }
+
+    #[test]
+    fn release_waits_for_running_callback() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let (entered, wait_entered) = std::sync::mpsc::channel();
+        let entered = Mutex::new(entered);
+        let context = Arc::new(AtomicUsize::new(0));
+        let handle = device
+            .set_notification_callback(
+                move |n: Notification<Arc<AtomicUsize>>| {
+                    let _ = entered.lock().unwrap().send(());
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    // The context is still alive after the handle starts being dropped.
+                    n.context().unwrap().fetch_add(1, Ordering::SeqCst);
+                },
+                Some(context.clone()),
+            )
+            .unwrap();
+
+        std::thread::scope(|s| {
+            s.spawn(|| api.notify(NotificationData::Gpio { gpio0: 1, gpio1: 0 }));
+            wait_entered.recv().unwrap();
+            drop(handle);
+            // Dropping the handle waited for the callback to return.
+            assert_eq!(context.load(Ordering::SeqCst), 1);
+        });
+        assert_eq!(Arc::strong_count(&context), 1);
+    }
+
+    #[test]
+    fn freed_context_is_not_entered() {
+        let mut context = 0u8;
+        let context: *mut c_void = std::ptr::addr_of_mut!(context).cast();
+        in_flight().register(context);
+        assert!(in_flight().enter(context));
+        in_flight().exit(context);
+        in_flight().unregister(context);
+        assert!(!in_flight().enter(context));
+    }
+
+    #[test]
+    fn manual_clear_frees_on_drop() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let context = Arc::new(());
+        let handle = device
+            .set_notification_callback(|_: Notification<Arc<()>>| {}, Some(context.clone()))
+            .unwrap();
+        device.clear_notification_callback().unwrap();
+        drop(handle);
+        // The callback was already cleared, so it is not cleared again.
+        assert_eq!(
+            api.calls(),
+            [
+                Call::SetNotificationCallback,
+                Call::ClearNotificationCallback
+            ]
+        );
+        assert_eq!(Arc::strong_count(&context), 1);
+    }
+
+    #[test]
+    fn mock_notify() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let _handle = device
+            .set_notification_callback(
+                |notification: Notification<Arc<std::sync::Mutex<Vec<NotificationData>>>>| {
+                    let received = notification.context().unwrap();
+                    received.lock().unwrap().push(*notification.data());
+                },
+                Some(received.clone()),
+            )
+            .unwrap();
+        let data = NotificationData::Data {
+            endpoint: Pipe::In1,
+            size: 12,
+        };
+        let gpio = NotificationData::Gpio { gpio0: 1, gpio1: 0 };
+        api.notify(data);
+        api.notify(gpio);
+        assert_eq!(*received.lock().unwrap(), [data, gpio]);
+    }
+
+    #[test]
+    fn unexpected_endpoint() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let _handle = device
+            .set_notification_callback_mut(
+                move |notification: Notification<()>| sender.send(*notification.data()).unwrap(),
+                None,
+            )
+            .unwrap();
+        for endpoint_byte in [0x02, 0x99] {
+            api.notify(NotificationData::Unknown {
+                endpoint_byte,
+                size: 4,
+            });
+        }
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            [
+                NotificationData::Unknown {
+                    endpoint_byte: 0x02,
+                    size: 4
+                },
+                NotificationData::Unknown {
+                    endpoint_byte: 0x99,
+                    size: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fn_mut_callback_accumulates() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut count = 0;
+        let handle = device
+            .set_notification_callback_mut(
+                move |_: Notification<()>| {
+                    count += 1;
+                    sender.send(count).unwrap();
+                },
+                None,
+            )
+            .unwrap();
+        let gpio = NotificationData::Gpio { gpio0: 1, gpio1: 1 };
+        api.notify(gpio);
+        std::thread::scope(|s| {
+            s.spawn(|| api.notify(gpio));
+        });
+        api.notify(gpio);
+        handle.clear().unwrap();
+        api.notify(gpio);
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn leak_keeps_callback() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        device
+            .set_notification_callback(|_: Notification<()>| {}, None)
+            .unwrap()
+            .leak();
+        assert_eq!(api.calls(), [Call::SetNotificationCallback]);
+    }
+
+    #[test]
+    fn failed_set_frees_context() {
+        let api = Arc::new(MockApi::default());
+        api.fail_once_when(
+            |call| *call == Call::SetNotificationCallback,
+            D3xxError::InvalidHandle,
+        );
+        let device = mock::device(&api);
+        let context = Arc::new(());
+        let result =
+            device.set_notification_callback(|_: Notification<Arc<()>>| {}, Some(context.clone()));
+        assert_eq!(result.err(), Some(D3xxError::InvalidHandle));
+        assert_eq!(Arc::strong_count(&context), 1);
+    }
+
+    #[test]
+    fn queue_drains_in_order() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let queue = NotificationQueue::new(&device, 4, QueueOverflow::DropOldest).unwrap();
+        let data = NotificationData::Data {
+            endpoint: Pipe::In0,
+            size: 8,
+        };
+        let gpio = NotificationData::Gpio { gpio0: 1, gpio1: 0 };
+        api.notify(data);
+        api.notify(gpio);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.drain(), [data, gpio]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.drain(), []);
+        assert_eq!(queue.dropped(), 0);
+
+        queue.clear().unwrap();
+        assert_eq!(
+            api.calls(),
+            [
+                Call::SetNotificationCallback,
+                Call::ClearNotificationCallback
+            ]
+        );
+    }
+
+    #[test]
+    fn queue_overflow() {
+        let data = |size| NotificationData::Data {
+            endpoint: Pipe::In1,
+            size,
+        };
+        for (overflow, expected) in [
+            (QueueOverflow::DropOldest, [data(3), data(4)]),
+            (QueueOverflow::DropNewest, [data(1), data(2)]),
+        ] {
+            let api = Arc::new(MockApi::default());
+            let device = mock::device(&api);
+            let queue = NotificationQueue::new(&device, 2, overflow).unwrap();
+            for size in 1..=4 {
+                api.notify(data(size));
+            }
+            assert_eq!(queue.dropped(), 2);
+            assert_eq!(queue.drain(), expected);
+        }
+    }
+
+    #[test]
+    fn queue_zero_capacity() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        assert_eq!(
+            NotificationQueue::new(&device, 0, QueueOverflow::DropOldest).unwrap_err(),
+            D3xxError::InvalidArgs
+        );
+        assert_eq!(api.calls(), []);
+    }
+}
@@ -9,9 +9,19 @@
 /// 1. Create an `Overlapped` instance with [`Overlapped::new`].
 /// 2. Perform the read/write operation in overlapped mode.
 /// 3. Poll the `Overlapped` instance until the transfer is complete.
-use std::{future::Future, marker::PhantomData, mem::MaybeUninit};
+use std::{
+    cell::RefCell,
+    future::Future,
+    mem::ManuallyDrop,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::{Duration, Instant},
+};
 
-use crate::{ffi, try_d3xx, util::PhantomLifetime, D3xxError, Device, Result};
+use crate::{ffi, D3xxError, Device, Result};
 
 /// Wrapper around the `FT_OVERLAPPED` structure.
 ///
@@ -20,12 +30,32 @@ use crate::{ffi, try_d3xx, util::PhantomLifetime, D3xxError, Device, Result};
 /// the `async`/`await` Rust syntax.
 ///
 /// The lifetime of the `Overlapped` instance is tied to the lifetime of the `Device` instance;
-/// the device cannot be closed while the `Overlapped` instance is in use.
+/// the device cannot be closed while the `Overlapped` instance is in use. The only way to
+/// create one is through a `&Device`, so the borrow checker enforces this, including for
+/// the futures returned by [`PipeIo::read_async`](crate::PipeIo::read_async) and
+/// [`PipeIo::write_async`](crate::PipeIo::write_async):
+///
+/// ```compile_fail
+/// use d3xx::{Device, Pipe};
+///
+/// let device = Device::open("ABC123").unwrap();
+/// let pipe = device.pipe(Pipe::In0);
+/// let mut buf = [0; 64];
+/// let transfer = pipe.read_async(&mut buf);
+/// drop(device);
+/// drop(transfer);
+/// ```
 pub struct Overlapped<'a> {
-    handle: ffi::HANDLE,
+    /// The device the structure was initialized for.
+    device: &'a Device,
     inner: ffi::_OVERLAPPED,
-    /// Ties the lifetime of this struct to the lifetime of the source [`Device`](crate::Device) instance.
-    _lifetime_constraint: PhantomLifetime<'a>,
+    /// Point after which the future resolves to [`D3xxError::Timeout`].
+    deadline: Option<Instant>,
+    /// Token which makes the future resolve to [`D3xxError::OperationAborted`] once cancelled.
+    cancel: Option<CancelToken>,
+    /// Pipe of a transfer which was started using this structure and may still be in
+    /// progress.
+    pending: Option<u8>,
 }
 
 impl<'a> Overlapped<'a> {
@@ -33,29 +63,35 @@ impl<'a> Overlapped<'a> {
     ///
     /// The lifetime of the `Overlapped` instance is tied to the lifetime of the `Device` instance
     /// to avoid use-after-free errors.
-    #[allow(unused)]
     pub(crate) fn new(device: &'a Device) -> Result<Self> {
-        Self::with_handle(device.handle())
+        let inner = device.api().initialize_overlapped(device.ffi_handle())?;
+        Ok(Self {
+            device,
+            inner,
+            deadline: None,
+            cancel: None,
+            pending: None,
+        })
     }
 
-    /// Create a new `Overlapped` instance using the given handle.
+    /// Resolve to [`D3xxError::Timeout`] if the transfer has not completed within `timeout`
+    /// of calling this method.
     ///
-    /// # Safety
+    /// The transfer itself is only cancelled once the structure is dropped.
+    #[must_use]
+    pub(crate) fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    /// Resolve to [`D3xxError::OperationAborted`] once `token` is cancelled.
     ///
-    /// Care must be taken to ensure that the handle is valid for the lifetime of the `Overlapped`
-    /// instance.
-    pub(crate) fn with_handle(handle: ffi::FT_HANDLE) -> Result<Self> {
-        let mut overlapped: MaybeUninit<ffi::_OVERLAPPED> = MaybeUninit::uninit();
-        try_d3xx!(unsafe {
-            ffi::FT_InitializeOverlapped(handle, std::ptr::addr_of_mut!(overlapped).cast())
-        })?;
-        // SAFETY: `overlapped` is properly initialized by `FT_InitializeOverlapped`.
-        let overlapped = unsafe { overlapped.assume_init() };
-        Ok(Self {
-            handle,
-            inner: overlapped,
-            _lifetime_constraint: PhantomData,
-        })
+    /// As with [`with_timeout`](Self::with_timeout), the transfer is cancelled once the
+    /// structure is dropped.
+    #[must_use]
+    pub(crate) fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
     }
 
     /// Get a reference to the underlying `FT_OVERLAPPED` structure.
@@ -78,6 +114,27 @@ impl<'a> Overlapped<'a> {
         &mut self.inner
     }
 
+    /// Record that a transfer on `pipe` was started using this structure.
+    ///
+    /// Until the transfer is seen to complete, dropping the structure aborts the pipe and
+    /// waits for the driver to finish with it before releasing it.
+    pub(crate) fn set_pending(&mut self, pipe: u8) {
+        self.pending = Some(pipe);
+    }
+
+    /// Take the underlying structure without releasing it.
+    fn into_inner(self) -> ffi::_OVERLAPPED {
+        ManuallyDrop::new(self).inner
+    }
+
+    /// Block until the transfer is complete, then release the structure.
+    ///
+    /// This is the blocking counterpart to awaiting the `Overlapped` instance, for callers
+    /// without an async runtime. On success the number of bytes transferred is returned.
+    pub fn wait(mut self) -> Result<usize> {
+        self.poll_once(true)
+    }
+
     /// Poll the overlapped operation once.
     ///
     /// If `wait` is `true` then the operation will block until the transfer is complete.
@@ -86,16 +143,14 @@ impl<'a> Overlapped<'a> {
     ///
     /// If the operation is complete then the number of bytes transferred is returned.
     fn poll_once(&mut self, wait: bool) -> Result<usize> {
-        let mut transferred: ffi::ULONG = 0;
-        try_d3xx!(unsafe {
-            ffi::FT_GetOverlappedResult(
-                self.handle,
-                self.inner_mut(),
-                std::ptr::addr_of_mut!(transferred),
-                ffi::BOOL::from(wait),
-            )
-        })?;
-        Ok(transferred as usize)
+        let result =
+            self.device
+                .api()
+                .overlapped_result(self.device.ffi_handle(), &mut self.inner, wait);
+        if !matches!(result, Err(D3xxError::IoPending | D3xxError::IoIncomplete)) {
+            self.pending = None;
+        }
+        result
     }
 }
 
@@ -106,8 +161,18 @@ impl Future for Overlapped<'_> {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Self::Output> {
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return std::task::Poll::Ready(Err(D3xxError::OperationAborted));
+        }
         match self.poll_once(false) {
             Ok(transferred) => std::task::Poll::Ready(Ok(transferred)),
+            Err(D3xxError::IoPending | D3xxError::IoIncomplete)
+                if self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline) =>
+            {
+                std::task::Poll::Ready(Err(D3xxError::Timeout))
+            }
             Err(D3xxError::IoPending | D3xxError::IoIncomplete) => {
                 cx.waker().wake_by_ref();
                 std::task::Poll::Pending
@@ -119,8 +184,696 @@ impl Future for Overlapped<'_> {
 
 impl Drop for Overlapped<'_> {
     fn drop(&mut self) {
-        unsafe {
-            ffi::FT_ReleaseOverlapped(self.handle, self.inner_mut() as *mut ffi::_OVERLAPPED);
+        // A future dropped while pending, or a timed out transfer, leaves the driver
+        // writing to the structure. Abort the transfer and wait for the driver to report
+        // its completion, so the structure is not released while still in use. Waiting is
+        // bounded by the abort, which completes the transfer.
+        if let Some(pipe) = self.pending {
+            let _ = self.device.api().abort_pipe(self.device.ffi_handle(), pipe);
+            let _ = self.poll_once(true);
+        }
+        let _ = release(self.device, &mut self.inner);
+    }
+}
+
+/// Release an overlapped structure initialized for the given device.
+fn release(device: &Device, inner: &mut ffi::_OVERLAPPED) -> Result<()> {
+    device.api().release_overlapped(device.ffi_handle(), inner)
+}
+
+/// Token for cooperatively cancelling asynchronous transfers from outside their futures.
+///
+/// Passing a token to [`PipeIo::read_async_cancellable`](crate::PipeIo::read_async_cancellable)
+/// or [`PipeIo::write_async_cancellable`](crate::PipeIo::write_async_cancellable) makes the
+/// transfer resolve to [`D3xxError::OperationAborted`] the next time it is polled after
+/// [`cancel`](Self::cancel) is called, aborting the pipe. Clones share the same state, so one
+/// token may cancel several transfers, and may be cancelled from another thread.
+///
+/// A cancelled token stays cancelled; transfers started with it fail immediately.
+///
+/// # Example
+///
+/// ```no_run
+/// use d3xx::{CancelToken, Device, Pipe};
+///
+/// # async fn example() -> d3xx::Result<()> {
+/// let device = Device::open("ABC123")?;
+/// let token = CancelToken::new();
+/// let stop = token.clone();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_secs(1));
+///     stop.cancel();
+/// });
+/// let mut buf = [0; 64];
+/// let result = device
+///     .pipe(Pipe::In0)
+///     .read_async_cancellable(&mut buf, &token)
+///     .await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a token which is not cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the transfers using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Check if the token has been cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// A pool of reusable overlapped structures for a single device.
+///
+/// Each asynchronous transfer needs an overlapped structure, which is normally initialized
+/// before the transfer and released after it. For frequent small transfers this overhead
+/// adds up; passing a pool to [`PipeIo::read_async_with`](crate::PipeIo::read_async_with) or
+/// [`PipeIo::write_async_with`](crate::PipeIo::write_async_with) instead recycles structures
+/// from earlier transfers. A structure is reset before reuse, keeping the event created
+/// by the driver.
+///
+/// The pool grows as needed when more transfers are in flight than it has structures
+/// available. Structures used by a failed transfer are released rather than recycled.
+/// All remaining structures are released when the pool is dropped.
+///
+/// A pool borrows its device, so it cannot outlive it:
+///
+/// ```compile_fail
+/// use d3xx::{Device, OverlappedPool};
+///
+/// let pool = {
+///     let device = Device::open("ABC123").unwrap();
+///     OverlappedPool::new(&device, 4).unwrap()
+/// };
+/// ```
+///
+/// # Example
+///
+/// ```no_run
+/// use d3xx::{Device, OverlappedPool, Pipe};
+///
+/// # async fn example() -> d3xx::Result<()> {
+/// let device = Device::open("ABC123")?;
+/// let pool = OverlappedPool::new(&device, 4)?;
+/// let mut buf = [0; 64];
+/// loop {
+///     let n = device.pipe(Pipe::In0).read_async_with(&mut buf, &pool).await?;
+///     // ...
+/// }
+/// # }
+/// ```
+pub struct OverlappedPool<'a> {
+    device: &'a Device,
+    free: RefCell<Vec<ffi::_OVERLAPPED>>,
+}
+
+impl<'a> OverlappedPool<'a> {
+    /// Create a pool for the given device with `capacity` structures initialized up front.
+    pub fn new(device: &'a Device, capacity: usize) -> Result<Self> {
+        let pool = Self {
+            device,
+            free: RefCell::new(Vec::with_capacity(capacity)),
+        };
+        for _ in 0..capacity {
+            let inner = Overlapped::new(device)?.into_inner();
+            pool.free.borrow_mut().push(inner);
+        }
+        Ok(pool)
+    }
+
+    /// The device this pool belongs to.
+    #[must_use]
+    pub fn device(&self) -> &'a Device {
+        self.device
+    }
+
+    /// Number of structures currently available for reuse.
+    #[must_use]
+    pub fn available(&self) -> usize {
+        self.free.borrow().len()
+    }
+
+    /// Take a structure from the pool, initializing a new one if none are available.
+    pub(crate) fn take(&self) -> Result<Overlapped<'a>> {
+        let inner = self.free.borrow_mut().pop();
+        match inner {
+            Some(inner) => Ok(Overlapped {
+                device: self.device,
+                inner,
+                deadline: None,
+                cancel: None,
+                pending: None,
+            }),
+            None => Overlapped::new(self.device),
+        }
+    }
+
+    /// Reset a structure after a completed transfer and return it to the pool.
+    pub(crate) fn put(&self, overlapped: Overlapped<'a>) {
+        let mut inner = overlapped.into_inner();
+        inner.Internal = 0;
+        inner.InternalHigh = 0;
+        inner.__bindgen_anon_1.Pointer = std::ptr::null_mut();
+        self.free.borrow_mut().push(inner);
+    }
+}
+
+impl Drop for OverlappedPool<'_> {
+    fn drop(&mut self) {
+        for mut inner in self.free.get_mut().drain(..) {
+            let _ = release(self.device, &mut inner);
+        }
+    }
+}
+
+impl std::fmt::Debug for OverlappedPool<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OverlappedPool")
+            .field("device", self.device)
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+/// Drive a future to completion on the current thread.
+///
+/// This is a minimal executor for using the asynchronous pipe API, such as
+/// [`PipeIo::read_async`](crate::PipeIo::read_async), without an async runtime. The
+/// thread is parked while the future is pending and unparked when it is woken. Transfer
+/// futures wake themselves whenever they are pending, so they are polled in a loop until
+/// the transfer completes.
+///
+/// For a single transfer,
+/// [`PipeIo::read_overlapped_blocking`](crate::PipeIo::read_overlapped_blocking) and
+/// [`PipeIo::write_overlapped_blocking`](crate::PipeIo::write_overlapped_blocking) wait in
+/// the driver instead of polling.
+///
+/// # Example
+///
+/// ```no_run
+/// use d3xx::{block_on, Device, Pipe};
+///
+/// let device = Device::open("ABC123")?;
+/// let mut buf = [0; 64];
+/// let n = block_on(device.pipe(Pipe::In0).read_async(&mut buf))?;
+/// # Ok::<(), d3xx::D3xxError>(())
+/// ```
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// Waker which unparks the thread running [`block_on`].
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Wrap an asynchronous transfer in a `tracing` span.
+///
+/// The span carries the pipe ID and requested length, and records the completion
+/// status and number of bytes transferred once the transfer finishes.
+#[cfg(feature = "tracing")]
+pub(crate) async fn instrument(
+    direction: &'static str,
+    pipe: u8,
+    len: usize,
+    transfer: impl Future<Output = Result<usize>>,
+) -> Result<usize> {
+    use tracing::{field::Empty, Instrument};
+
+    let span = tracing::debug_span!(
+        "transfer",
+        direction,
+        pipe,
+        len,
+        status = Empty,
+        transferred = Empty
+    );
+    let result = transfer.instrument(span.clone()).await;
+    match &result {
+        Ok(transferred) => {
+            span.record("status", "ok");
+            span.record("transferred", transferred);
+        }
+        Err(e) => {
+            span.record("status", tracing::field::debug(e));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{self, Call, MockApi},
+        Pipe,
+    };
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Poll a future which is expected to be ready immediately.
+    fn now<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut future = std::pin::pin!(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future is not ready"),
+        }
+    }
+
+    fn count(api: &MockApi, call: Call) -> usize {
+        api.calls().into_iter().filter(|c| *c == call).count()
+    }
+
+    #[test]
+    fn async_transfer_without_pool() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abc");
+        let device = mock::device(&api);
+        let mut buf = [0; 8];
+        assert_eq!(now(device.pipe(Pipe::In0).read_async(&mut buf)), Ok(3));
+        assert_eq!(&buf[..3], b"abc");
+        assert_eq!(
+            api.calls(),
+            [
+                Call::InitializeOverlapped,
+                Call::ReadPipeAsync { pipe: 0x82, len: 8 },
+                Call::ReadPipe { pipe: 0x82, len: 8 },
+                Call::OverlappedResult,
+                Call::ReleaseOverlapped,
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_transfers_skip_driver() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pool = OverlappedPool::new(&device, 1).unwrap();
+        let calls = api.calls();
+        let timeout = Duration::from_secs(1);
+        let (read, write) = (device.pipe(Pipe::In0), device.pipe(Pipe::Out0));
+        assert_eq!(now(read.read_async(&mut [])), Ok(0));
+        assert_eq!(now(read.read_async_with(&mut [], &pool)), Ok(0));
+        assert_eq!(now(read.read_async_timeout(&mut [], timeout)), Ok(0));
+        assert_eq!(read.read_overlapped_blocking(&mut []), Ok(0));
+        assert_eq!(now(write.write_async(&[])), Ok(0));
+        assert_eq!(now(write.write_async_with(&[], &pool)), Ok(0));
+        assert_eq!(now(write.write_async_timeout(&[], timeout)), Ok(0));
+        assert_eq!(write.write_overlapped_blocking(&[]), Ok(0));
+        assert_eq!(api.calls(), calls);
+    }
+
+    #[test]
+    fn blocking_wait() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abc");
+        let device = mock::device(&api);
+        let mut buf = [0; 8];
+        let pipe = device.pipe(Pipe::In0);
+        assert_eq!(pipe.read_overlapped_blocking(&mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"abc");
+        assert_eq!(pipe.write_overlapped_blocking(b"hello"), Ok(5));
+        assert_eq!(count(&api, Call::OverlappedResult), 2);
+        assert_eq!(count(&api, Call::ReleaseOverlapped), 2);
+    }
+
+    #[test]
+    fn blocking_wait_error_aborts() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(|c| *c == Call::OverlappedResult, D3xxError::IoError);
+        let device = mock::device(&api);
+        let result = device.pipe(Pipe::Out1).write_overlapped_blocking(b"hello");
+        assert_eq!(result, Err(D3xxError::IoError));
+        assert_eq!(count(&api, Call::AbortPipe { pipe: 0x03 }), 1);
+        assert_eq!(count(&api, Call::ReleaseOverlapped), 1);
+    }
+
+    #[test]
+    fn async_timeout_expires() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abc");
+        // The transfer never completes.
+        api.fail_when(|c| *c == Call::OverlappedResult, D3xxError::IoIncomplete);
+        let device = mock::device(&api);
+
+        let timeout = Duration::from_millis(20);
+        let start = Instant::now();
+        let mut buf = [0; 8];
+        let result = block_on(device.pipe(Pipe::In0).read_async_timeout(&mut buf, timeout));
+        assert_eq!(result, Err(D3xxError::Timeout));
+        assert!(start.elapsed() >= timeout);
+
+        let calls = api.calls();
+        let abort = calls
+            .iter()
+            .position(|c| *c == Call::AbortPipe { pipe: 0x82 });
+        let release = calls.iter().position(|c| *c == Call::ReleaseOverlapped);
+        assert!(abort.unwrap() < release.unwrap());
+    }
+
+    #[test]
+    fn drop_while_pending_aborts_before_release() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(|c| *c == Call::OverlappedResult, D3xxError::IoIncomplete);
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In1);
+        let mut buf = [0; 8];
+        {
+            let waker = Waker::from(Arc::new(NoopWaker));
+            let mut transfer = std::pin::pin!(pipe.read_async(&mut buf));
+            let poll = transfer.as_mut().poll(&mut Context::from_waker(&waker));
+            assert!(poll.is_pending());
+        }
+        assert_eq!(
+            api.calls(),
+            [
+                Call::InitializeOverlapped,
+                Call::ReadPipeAsync { pipe: 0x83, len: 8 },
+                Call::ReadPipe { pipe: 0x83, len: 8 },
+                Call::OverlappedResult,
+                Call::AbortPipe { pipe: 0x83 },
+                Call::OverlappedResult,
+                Call::ReleaseOverlapped,
+            ]
+        );
+    }
+
+    #[test]
+    fn completed_transfer_is_not_aborted() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        assert_eq!(now(device.pipe(Pipe::Out2).write_async(b"data")), Ok(4));
+        assert_eq!(
+            device.pipe(Pipe::Out2).write_overlapped_blocking(b"data"),
+            Ok(4)
+        );
+        assert_eq!(count(&api, Call::AbortPipe { pipe: 0x04 }), 0);
+        assert_eq!(count(&api, Call::ReleaseOverlapped), 2);
+    }
+
+    #[test]
+    fn write_batch_submits_before_waiting() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let bufs = vec![b"abc".to_vec(), Vec::new(), b"hello".to_vec()];
+        assert_eq!(
+            now(device.pipe(Pipe::Out1).write_batch_async(bufs)),
+            Ok(vec![3, 0, 5])
+        );
+        assert_eq!(
+            api.calls(),
+            [
+                Call::InitializeOverlapped,
+                Call::WritePipeAsync { pipe: 0x03, len: 3 },
+                Call::WritePipe { pipe: 0x03, len: 3 },
+                Call::InitializeOverlapped,
+                Call::WritePipeAsync { pipe: 0x03, len: 5 },
+                Call::WritePipe { pipe: 0x03, len: 5 },
+                Call::OverlappedResult,
+                Call::OverlappedResult,
+                Call::ReleaseOverlapped,
+                Call::ReleaseOverlapped,
+            ]
+        );
+    }
+
+    #[test]
+    fn write_batch_error_aborts() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |c| *c == Call::WritePipeAsync { pipe: 0x02, len: 2 },
+            D3xxError::IoError,
+        );
+        let device = mock::device(&api);
+        let bufs = vec![b"abc".to_vec(), b"de".to_vec(), b"f".to_vec()];
+        assert_eq!(
+            now(device.pipe(Pipe::Out0).write_batch_async(bufs)),
+            Err(D3xxError::IoError)
+        );
+        assert_eq!(count(&api, Call::WritePipeAsync { pipe: 0x02, len: 1 }), 0);
+        // Once for the failed submission, and once more when cancelling the first write.
+        assert_eq!(count(&api, Call::AbortPipe { pipe: 0x02 }), 2);
+        assert_eq!(count(&api, Call::InitializeOverlapped), 2);
+        assert_eq!(count(&api, Call::ReleaseOverlapped), 2);
+    }
+
+    #[test]
+    fn cancel_aborts_pending_transfer() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(|c| *c == Call::OverlappedResult, D3xxError::IoIncomplete);
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+        let token = CancelToken::new();
+        let mut buf = [0; 8];
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut transfer = std::pin::pin!(pipe.read_async_cancellable(&mut buf, &token));
+        assert!(transfer.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(count(&api, Call::AbortPipe { pipe: 0x82 }), 0);
+
+        token.clone().cancel();
+        assert_eq!(
+            transfer.as_mut().poll(&mut cx),
+            Poll::Ready(Err(D3xxError::OperationAborted))
+        );
+        let calls = api.calls();
+        let abort = calls
+            .iter()
+            .position(|c| *c == Call::AbortPipe { pipe: 0x82 });
+        let release = calls.iter().position(|c| *c == Call::ReleaseOverlapped);
+        assert!(abort.unwrap() < release.unwrap());
+    }
+
+    #[test]
+    fn cancelled_token_skips_driver() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let token = CancelToken::new();
+        token.cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(
+            now(device
+                .pipe(Pipe::Out0)
+                .write_async_cancellable(b"data", &token)),
+            Err(D3xxError::OperationAborted)
+        );
+        assert_eq!(api.calls(), []);
+    }
+
+    #[test]
+    fn block_on_drives_pending_transfer() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abc");
+        for _ in 0..2 {
+            api.fail_once_when(|c| *c == Call::OverlappedResult, D3xxError::IoPending);
+        }
+        let device = mock::device(&api);
+        let mut buf = [0; 8];
+        assert_eq!(block_on(device.pipe(Pipe::In0).read_async(&mut buf)), Ok(3));
+        assert_eq!(&buf[..3], b"abc");
+        assert_eq!(count(&api, Call::OverlappedResult), 3);
+        assert_eq!(count(&api, Call::AbortPipe { pipe: 0x82 }), 0);
+    }
+
+    #[test]
+    fn async_timeout_not_reached() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let result = block_on(
+            device
+                .pipe(Pipe::Out0)
+                .write_async_timeout(b"data", Duration::from_secs(10)),
+        );
+        assert_eq!(result, Ok(4));
+        assert_eq!(count(&api, Call::AbortPipe { pipe: 0x02 }), 0);
+    }
+
+    #[test]
+    fn pool_reuses_structures() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pool = OverlappedPool::new(&device, 2).unwrap();
+        assert_eq!(pool.available(), 2);
+        assert_eq!(count(&api, Call::InitializeOverlapped), 2);
+
+        let pipe = device.pipe(Pipe::Out0);
+        for _ in 0..10 {
+            assert_eq!(now(pipe.write_async_with(b"data", &pool)), Ok(4));
+        }
+        assert_eq!(api.writes(0x02).len(), 10);
+        assert_eq!(pool.available(), 2);
+        assert_eq!(count(&api, Call::InitializeOverlapped), 2);
+        assert_eq!(count(&api, Call::ReleaseOverlapped), 0);
+
+        drop(pool);
+        assert_eq!(count(&api, Call::ReleaseOverlapped), 2);
+    }
+
+    #[test]
+    fn pool_grows_when_empty() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abc");
+        let device = mock::device(&api);
+        let pool = OverlappedPool::new(&device, 0).unwrap();
+        let mut buf = [0; 3];
+        assert_eq!(
+            now(device.pipe(Pipe::In0).read_async_with(&mut buf, &pool)),
+            Ok(3)
+        );
+        assert_eq!(pool.available(), 1);
+        assert_eq!(count(&api, Call::InitializeOverlapped), 1);
+    }
+
+    #[test]
+    fn pool_releases_after_failure() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pool = OverlappedPool::new(&device, 1).unwrap();
+        let mut buf = [0; 3];
+        assert_eq!(
+            now(device.pipe(Pipe::In0).read_async_with(&mut buf, &pool)),
+            Err(D3xxError::Timeout)
+        );
+        assert_eq!(pool.available(), 0);
+        assert_eq!(count(&api, Call::ReleaseOverlapped), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "different device")]
+    fn pool_from_other_device() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let other = mock::device(&api);
+        let pool = OverlappedPool::new(&other, 1).unwrap();
+        let _ = now(device.pipe(Pipe::Out0).write_async_with(b"x", &pool));
+    }
+
+    #[cfg(feature = "tracing")]
+    mod tracing_spans {
+        use std::{
+            collections::HashMap,
+            fmt::Debug,
+            sync::{Arc, Mutex},
+        };
+
+        use tracing::{
+            field::{Field, Visit},
+            span::{Attributes, Id, Record},
+            Event, Metadata, Subscriber,
+        };
+
+        use super::{super::*, now};
+
+        /// Subscriber which keeps the name and fields of every span.
+        #[derive(Default, Clone)]
+        struct Capture(Arc<Mutex<Vec<Span>>>);
+
+        type Span = (&'static str, HashMap<&'static str, String>);
+
+        struct Fields<'a>(&'a mut HashMap<&'static str, String>);
+
+        impl Visit for Fields<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.insert(field.name(), format!("{value:?}"));
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut spans = self.0.lock().unwrap();
+                let mut fields = HashMap::new();
+                span.record(&mut Fields(&mut fields));
+                spans.push((span.metadata().name(), fields));
+                Id::from_u64(spans.len() as u64)
+            }
+
+            fn record(&self, span: &Id, values: &Record<'_>) {
+                let mut spans = self.0.lock().unwrap();
+                let index = usize::try_from(span.into_u64()).unwrap() - 1;
+                values.record(&mut Fields(&mut spans[index].1));
+            }
+
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+            fn event(&self, _event: &Event<'_>) {}
+
+            fn enter(&self, _span: &Id) {}
+
+            fn exit(&self, _span: &Id) {}
+        }
+
+        fn spans(f: impl FnOnce()) -> Vec<Span> {
+            let capture = Capture::default();
+            tracing::subscriber::with_default(capture.clone(), f);
+            let spans = capture.0.lock().unwrap();
+            spans.clone()
+        }
+
+        #[test]
+        fn span_records_completion() {
+            let spans = spans(|| {
+                assert_eq!(
+                    now(instrument("read", 0x82, 1024, async { Ok(512) })),
+                    Ok(512)
+                );
+            });
+            let (name, fields) = &spans[0];
+            assert_eq!(*name, "transfer");
+            assert_eq!(fields["direction"], "\"read\"");
+            assert_eq!(fields["pipe"], "130");
+            assert_eq!(fields["len"], "1024");
+            assert_eq!(fields["status"], "\"ok\"");
+            assert_eq!(fields["transferred"], "512");
+        }
+
+        #[test]
+        fn span_records_error() {
+            let spans = spans(|| {
+                let result = now(instrument("write", 0x02, 8, async {
+                    Err(D3xxError::Timeout)
+                }));
+                assert_eq!(result, Err(D3xxError::Timeout));
+            });
+            let (_, fields) = &spans[0];
+            assert_eq!(fields["direction"], "\"write\"");
+            assert_eq!(fields["status"], "Timeout");
+            assert!(!fields.contains_key("transferred"));
         }
     }
 }
@@ -1,13 +1,17 @@
 use std::{
-    io::{Read, Write},
-    marker::PhantomData,
+    io::{BufReader, BufWriter, IoSlice, IoSliceMut, Read, Write},
+    mem::MaybeUninit,
+    time::{Duration, Instant},
 };
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::{
-    descriptor::PipeInfo, ffi, overlapped::Overlapped, try_d3xx, util::PhantomLifetime, D3xxError,
-    Device, Result,
+    descriptor::PipeInfo,
+    ffi::{self, util::as_uninit_mut},
+    overlapped::{CancelToken, Overlapped, OverlappedPool},
+    util::timeout_millis,
+    D3xxError, Device, Result,
 };
 
 /// Provides read/write access to an endpoint on the device.
@@ -33,18 +37,16 @@ use crate::{
 ///    .write(&buf)
 ///    .unwrap();
 /// ```
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct PipeIo<'a> {
-    /// Handle to the device.
-    ///
-    /// Rust's type system ensures through the lifetime parameter that this handle
-    /// cannot outlive the `Device` instance it belongs to.
-    handle: ffi::FT_HANDLE,
+    /// The device the pipe belongs to.
+    device: &'a Device,
     /// The pipe ID this instance is associated with.
     id: Pipe,
-    /// Lifetime marker, required since `PipeIo` does not contain any references
-    /// with lifetime `'a`
-    _lifetime_constraint: PhantomLifetime<'a>,
+    /// Whether transfers in the wrong direction for the pipe are rejected.
+    checked: bool,
+    /// Which transfer errors cause the pipe to be aborted.
+    abort_policy: AbortPolicy,
 }
 
 impl<'a> PipeIo<'a> {
@@ -54,12 +56,53 @@ impl<'a> PipeIo<'a> {
     #[must_use]
     pub fn new(device: &'a Device, id: Pipe) -> Self {
         Self {
-            handle: device.handle(),
+            device,
+            id,
+            checked: false,
+            abort_policy: device.abort_policy(),
+        }
+    }
+
+    /// Create a new `PipeIo` instance which rejects transfers in the wrong direction.
+    ///
+    /// Reading from an OUT pipe or writing to an IN pipe fails with
+    /// [`D3xxError::InvalidArgs`] without calling the driver, which would otherwise
+    /// return a less descriptive error. This applies to every read and write method,
+    /// synchronous or asynchronous. Instances created with [`new`](Self::new) pass such
+    /// transfers to the driver unchanged.
+    ///
+    /// For improved ergonomics it is recommended to use [`Device::pipe_checked`] instead
+    /// of this method.
+    #[must_use]
+    pub fn new_checked(device: &'a Device, id: Pipe) -> Self {
+        Self {
+            device,
             id,
-            _lifetime_constraint: PhantomData,
+            checked: true,
+            abort_policy: device.abort_policy(),
         }
     }
 
+    /// Use the given policy to decide which transfer errors abort the pipe.
+    ///
+    /// By default the policy set with [`Device::set_abort_policy`] is used.
+    #[must_use]
+    pub fn with_abort_policy(mut self, policy: AbortPolicy) -> Self {
+        self.abort_policy = policy;
+        self
+    }
+
+    /// Get the policy deciding which transfer errors abort the pipe.
+    #[must_use]
+    pub fn abort_policy(&self) -> AbortPolicy {
+        self.abort_policy
+    }
+
+    /// Handle of the device the pipe belongs to.
+    fn handle(&self) -> ffi::FT_HANDLE {
+        self.device.ffi_handle()
+    }
+
     /// Get the pipe ID.
     #[must_use]
     pub fn id(&self) -> Pipe {
@@ -67,58 +110,110 @@ impl<'a> PipeIo<'a> {
     }
 
     /// Get the descriptor for this endpoint.
+    ///
+    /// The descriptor is read from interface 1, which holds the data pipes of `FT60x`
+    /// devices. Use [`descriptor_for_interface`](Self::descriptor_for_interface) for
+    /// devices exposing the pipe on another interface.
     pub fn descriptor(&self) -> Result<PipeInfo> {
         // FT60x devices have 2 interfaces, and 0 is reserved.
         // Page 33: https://ftdichip.com/wp-content/uploads/2020/07/AN_379-D3xx-Programmers-Guide-1.pdf
         const INTERFACE_INDEX: ffi::UCHAR = 1;
-        let mut info = ffi::FT_PIPE_INFORMATION::default();
-        try_d3xx!(unsafe {
-            ffi::FT_GetPipeInformation(
-                self.handle,
-                INTERFACE_INDEX,
-                ffi::UCHAR::from(self.id),
-                &mut info,
-            )
-        })?;
+        self.descriptor_for_interface(INTERFACE_INDEX)
+    }
+
+    /// Get the descriptor for this endpoint, as exposed on the given interface.
+    pub fn descriptor_for_interface(&self, interface: u8) -> Result<PipeInfo> {
+        let info =
+            self.device
+                .api()
+                .pipe_information(self.handle(), interface, u8::from(self.id))?;
         PipeInfo::new(info)
     }
 
+    /// Wrap this pipe in a [`BufPipeReader`] with a buffer of one maximum-size packet.
+    ///
+    /// Small reads are then served from the buffer, reducing the number of driver calls.
+    pub fn into_buf_reader(self) -> Result<BufPipeReader<'a>> {
+        let capacity = self.descriptor()?.max_packet_size();
+        Ok(BufReader::with_capacity(capacity, self))
+    }
+
+    /// Wrap this pipe in a [`BufPipeWriter`] with a buffer of one maximum-size packet.
+    ///
+    /// Small writes are then coalesced into larger transfers, reducing the number of
+    /// driver calls. Buffered data is written when the buffer fills, when
+    /// [`flush`](Write::flush) is called, or when the writer is dropped. Flushing also
+    /// flushes the pipe itself.
+    pub fn into_buf_writer(self) -> Result<BufPipeWriter<'a>> {
+        let capacity = self.descriptor()?.max_packet_size();
+        Ok(BufWriter::with_capacity(capacity, self))
+    }
+
     /// Set the stream size for this pipe.
     ///
-    /// If `size` is `None` then streaming is disabled. Otherwise,
-    /// the pipe will be configured for streaming with the given size.
+    /// If `size` is `None` then streaming is disabled with `FT_ClearStreamPipe`.
+    /// Otherwise, the pipe will be configured for streaming with the given size.
     ///
     /// Stream pipes are general-purpose pipes supporting interrupt, bulk,
     /// and isochronous transfers.
     pub fn set_stream_size(&self, size: Option<usize>) -> Result<()> {
-        #[cfg(windows)]
-        type Bool = ffi::BOOLEAN;
-        #[cfg(not(windows))]
-        type Bool = ffi::BOOL;
-
-        match size {
-            Some(size) => {
-                try_d3xx!(unsafe {
-                    ffi::FT_SetStreamPipe(
-                        self.handle,
-                        Bool::from(false),
-                        Bool::from(false),
-                        self.id as ffi::UCHAR,
-                        size.try_into().or(Err(D3xxError::InvalidArgs))?,
-                    )
-                })
-            }
-            None => {
-                try_d3xx!(unsafe {
-                    ffi::FT_ClearStreamPipe(
-                        self.handle,
-                        Bool::from(false),
-                        Bool::from(false),
-                        self.id as ffi::UCHAR,
-                    )
-                })
+        self.set_stream_size_for(StreamPipeTarget::ThisPipe, size)
+    }
+
+    /// Set the stream size for this pipe, checking it against the pipe's limits first.
+    ///
+    /// Streaming only works correctly when the stream size is a non-zero multiple of the
+    /// pipe's [maximum packet size](PipeInfo::max_packet_size). Other sizes are accepted
+    /// by the driver but cause transfers to fail later, so this fails with
+    /// [`D3xxError::InvalidArgs`] instead. Disabling streaming is never rejected.
+    ///
+    /// The check reads the pipe's [descriptor](Self::descriptor) on every call.
+    pub fn set_stream_size_checked(&self, size: Option<usize>) -> Result<()> {
+        if let Some(size) = size {
+            let packet = self.descriptor()?.max_packet_size();
+            if size == 0 || packet == 0 || size % packet != 0 {
+                return Err(D3xxError::InvalidArgs);
             }
         }
+        self.set_stream_size(size)
+    }
+
+    /// Configure the pipe for streaming until the returned guard is dropped.
+    ///
+    /// The stream size is validated as with
+    /// [`set_stream_size_checked`](Self::set_stream_size_checked). When the guard is
+    /// dropped, pending transfers on the pipe are aborted and streaming is disabled, in
+    /// that order. Use [`StreamGuard::end`] to observe errors from doing so.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::io::Read;
+    /// use d3xx::{Device, Pipe};
+    ///
+    /// let device = Device::open("ABC123").unwrap();
+    /// let stream = device.pipe(Pipe::In0).begin_stream(4096).unwrap();
+    /// let mut buf = vec![0; 4096];
+    /// stream.pipe().clone().read_exact(&mut buf).unwrap();
+    /// stream.end().unwrap();
+    /// ```
+    pub fn begin_stream(&self, transfer_size: usize) -> Result<StreamGuard<'a>> {
+        self.set_stream_size_checked(Some(transfer_size))?;
+        Ok(StreamGuard { pipe: self.clone() })
+    }
+
+    /// Set the stream size for this pipe, or for every pipe in one direction.
+    ///
+    /// This behaves like [`set_stream_size`](Self::set_stream_size), but `target`
+    /// selects which pipes are affected, mirroring the flags of `FT_SetStreamPipe`.
+    pub fn set_stream_size_for(&self, target: StreamPipeTarget, size: Option<usize>) -> Result<()> {
+        let size = size
+            .map(u32::try_from)
+            .transpose()
+            .or(Err(D3xxError::InvalidArgs))?;
+        self.device
+            .api()
+            .set_stream_pipe(self.handle(), u8::from(self.id), target, size)
     }
 
     /// Aborts all pending transfers.
@@ -130,90 +225,1001 @@ impl<'a> PipeIo<'a> {
     /// failure. This method is transparently called in error cases for the user,
     /// so it is not typically necessary to do so manually.
     pub fn abort(&self) -> Result<()> {
-        try_d3xx!(unsafe { ffi::FT_AbortPipe(self.handle, u8::from(self.id)) })
+        self.device
+            .api()
+            .abort_pipe(self.handle(), u8::from(self.id))
+    }
+
+    /// Aborts all pending transfers and then flushes the pipe.
+    ///
+    /// This is the recovery sequence to use after a transfer fails. Both steps are
+    /// always attempted; if either fails, the first error is returned.
+    ///
+    /// Neither this, [`abort`](Self::abort), nor [`flush`](Write::flush) change the
+    /// pipe's stream size, so a streaming pipe keeps streaming afterwards. D3XX has no
+    /// other pipe reset function; `FT_ClearStreamPipe` only disables streaming, and is
+    /// called by [`set_stream_size(None)`](Self::set_stream_size).
+    pub fn reset(&self) -> Result<()> {
+        let abort = self.abort();
+        let flush = self.flush_pipe();
+        abort.and(flush)
     }
 
-    /// Aborts all pending transfers on the specified pipe if the given result is an error.
+    /// Flush the pipe with `FT_FlushPipe`.
+    ///
+    /// Unlike [`flush`](Write::flush), this is not tied to the [`Write`] trait and may be
+    /// called on any pipe. On an IN pipe it discards data which has been received from
+    /// the device but not yet read, so that the next read only returns fresh data. Data
+    /// still buffered on the device side of the link is not affected.
+    pub fn flush_pipe(&self) -> Result<()> {
+        self.device
+            .api()
+            .flush_pipe(self.handle(), u8::from(self.id))
+    }
+
+    /// Aborts all pending transfers on the specified pipe if the given result is an error
+    /// which the pipe's [`AbortPolicy`] applies to.
     ///
     /// This is a convenience method for aborting a pipe on read/write failure, as required
     /// by the driver. See D3XX Programmer's Guide, pg. 15 for more information.
     ///
     /// Returns the given result for convenience.
-    fn maybe_abort<T>(&self, res: Result<T>) -> Result<T> {
+    pub(crate) fn maybe_abort<T>(&self, res: Result<T>) -> Result<T> {
         res.map_err(|e| {
-            let _ = self.abort();
+            if self.abort_policy.aborts_on(e) {
+                let _ = self.abort();
+            }
             e
         })
     }
 
+    /// Result to return instead of reading `len` bytes, if the driver should not be
+    /// called.
+    ///
+    /// Reads from an OUT pipe are rejected if the pipe is [checked](Self::new_checked),
+    /// and empty reads return `Ok(0)` since the driver's handling of zero-length
+    /// transfers is undocumented.
+    fn skip_read(&self, len: usize) -> Option<Result<usize>> {
+        if self.checked && self.id.is_out() {
+            Some(Err(D3xxError::InvalidArgs))
+        } else if len == 0 {
+            Some(Ok(0))
+        } else {
+            None
+        }
+    }
+
+    /// Result to return instead of writing `len` bytes, if the driver should not be
+    /// called.
+    ///
+    /// See [`skip_read`](Self::skip_read).
+    fn skip_write(&self, len: usize) -> Option<Result<usize>> {
+        if self.checked && self.id.is_in() {
+            Some(Err(D3xxError::InvalidArgs))
+        } else if len == 0 {
+            Some(Ok(0))
+        } else {
+            None
+        }
+    }
+
+    /// Synchronous read, aborting the pipe on failure.
+    fn read_pipe(&self, buf: &mut [u8]) -> Result<usize> {
+        self.read_uninit(as_uninit_mut(buf))
+    }
+
+    /// Synchronous write, aborting the pipe on failure.
+    fn write_pipe(&self, buf: &[u8]) -> Result<usize> {
+        if let Some(result) = self.skip_write(buf.len()) {
+            return result;
+        }
+        let res = self
+            .device
+            .api()
+            .write_pipe(self.handle(), u8::from(self.id), buf);
+        self.maybe_abort(res)
+    }
+
+    /// Read from the pipe into a possibly uninitialized buffer.
+    ///
+    /// This behaves like [`Read::read`], but avoids having to initialize the buffer
+    /// beforehand, which is wasteful for large reads.
+    ///
+    /// On success, the number of bytes read `n` is returned and the first `n` elements
+    /// of `buf` are guaranteed to be initialized. The rest of `buf` is left untouched,
+    /// and nothing is guaranteed to be initialized on failure. The pipe is aborted if
+    /// the read fails. An empty `buf` returns `Ok(0)` without calling the driver.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use d3xx::{Device, Pipe};
+    /// # let device = Device::open("ABC123")?;
+    /// let mut buf = Vec::with_capacity(1024 * 1024);
+    /// let n = device.pipe(Pipe::In0).read_uninit(buf.spare_capacity_mut())?;
+    /// // SAFETY: `read_uninit` initialized the first `n` bytes.
+    /// unsafe { buf.set_len(n) };
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    pub fn read_uninit(&self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        if let Some(result) = self.skip_read(buf.len()) {
+            return result;
+        }
+        let res = self
+            .device
+            .api()
+            .read_pipe(self.handle(), u8::from(self.id), buf);
+        self.maybe_abort(res)
+    }
+
+    /// Read up to `max` bytes from the pipe, appending them to `buf`.
+    ///
+    /// Capacity for `max` more bytes is reserved in `buf` first, and the data is read
+    /// directly into it without initializing or copying. On success the length of `buf`
+    /// grows by the number of bytes read, which is returned. On failure `buf` keeps its
+    /// length and the pipe is aborted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use d3xx::{Device, Pipe};
+    /// # let device = Device::open("ABC123")?;
+    /// let mut buf = bytes::BytesMut::new();
+    /// let n = device.pipe(Pipe::In0).read_buf(&mut buf, 4096)?;
+    /// assert_eq!(buf.len(), n);
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    #[cfg(feature = "bytes")]
+    pub fn read_buf(&self, buf: &mut bytes::BytesMut, max: usize) -> Result<usize> {
+        buf.reserve(max);
+        let n = self.read_uninit(&mut buf.spare_capacity_mut()[..max])?;
+        // SAFETY: `read_uninit` initialized the `n` bytes following the current length.
+        unsafe { buf.set_len(buf.len() + n) };
+        Ok(n)
+    }
+
+    /// Measure the throughput of the pipe by transferring `total_bytes` in `chunk`-sized
+    /// transfers.
+    ///
+    /// Input pipes are read from and output pipes are written to, using a scratch buffer
+    /// whose contents are discarded. Each transfer is subject to the
+    /// [pipe timeout](Self::set_timeout). The benchmark ends early if a transfer moves no
+    /// data, in which case fewer than `total_bytes` are reported.
+    ///
+    /// Returns [`D3xxError::InvalidArgs`] if `chunk` is zero. If a transfer fails the
+    /// pipe is aborted and the error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use d3xx::{Device, Pipe};
+    /// # let device = Device::open("ABC123")?;
+    /// let result = device.pipe(Pipe::Out0).benchmark(64 << 20, 1 << 20)?;
+    /// println!("{:.1} MB/s", result.bytes_per_sec() / 1e6);
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    pub fn benchmark(&self, total_bytes: usize, chunk: usize) -> Result<Throughput> {
+        self.benchmark_with(total_bytes, chunk, Instant::now)
+    }
+
+    /// Run a benchmark, using `now` to measure the elapsed time.
+    fn benchmark_with(
+        &self,
+        total_bytes: usize,
+        chunk: usize,
+        mut now: impl FnMut() -> Instant,
+    ) -> Result<Throughput> {
+        if chunk == 0 {
+            return Err(D3xxError::InvalidArgs);
+        }
+        let mut scratch = vec![0; chunk.min(total_bytes)];
+        let mut bytes = 0;
+        let start = now();
+        while bytes < total_bytes {
+            let len = chunk.min(total_bytes - bytes);
+            let n = if self.id.is_in() {
+                self.read_pipe(&mut scratch[..len])?
+            } else {
+                self.write_pipe(&scratch[..len])?
+            };
+            if n == 0 {
+                break;
+            }
+            bytes += n;
+        }
+        Ok(Throughput {
+            bytes,
+            elapsed: now() - start,
+        })
+    }
+
+    /// Read until the device stops sending data, up to `max` bytes.
+    ///
+    /// Data is read in chunks, with the buffer growing geometrically so that it need not
+    /// be sized in advance. Reading stops when:
+    ///
+    /// - `max` bytes have been read,
+    /// - a read returns fewer bytes than requested, or
+    /// - a read times out after at least one chunk has been received.
+    ///
+    /// A timeout before any data is received is returned as an error. The time spent
+    /// waiting for the end of data is bounded by the [pipe timeout](Self::set_timeout).
+    pub fn read_to_vec(&self, max: usize) -> Result<Vec<u8>> {
+        const INITIAL_CHUNK: usize = 512;
+
+        let mut buf = Vec::new();
+        while buf.len() < max {
+            let start = buf.len();
+            let chunk = start.max(INITIAL_CHUNK).min(max - start);
+            buf.resize(start + chunk, 0);
+            match self.read_pipe(&mut buf[start..]) {
+                Ok(n) => {
+                    buf.truncate(start + n);
+                    if n < chunk {
+                        break;
+                    }
+                }
+                Err(D3xxError::Timeout) if start > 0 => {
+                    buf.truncate(start);
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Iterate over the data received on the pipe in chunks of at most `size` bytes.
+    ///
+    /// Each item is an owned buffer truncated to the number of bytes actually read.
+    /// Iteration ends when:
+    ///
+    /// - a read returns no data,
+    /// - a read times out after at least one chunk has been received, or
+    /// - a read fails, in which case the error is yielded as the last item.
+    ///
+    /// A timeout before any data is received is yielded as an error, like
+    /// [`read_to_vec`](Self::read_to_vec). If `size` is zero, a single
+    /// [`D3xxError::InvalidArgs`] is yielded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use d3xx::{Device, Pipe};
+    /// # let device = Device::open("ABC123")?;
+    /// for chunk in device.pipe(Pipe::In1).chunks(4096) {
+    ///     println!("received {} bytes", chunk?.len());
+    /// }
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = Result<Vec<u8>>> + 'a {
+        let pipe = self.clone();
+        let mut received = false;
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            if size == 0 {
+                done = true;
+                return Some(Err(D3xxError::InvalidArgs));
+            }
+            let mut buf = vec![0; size];
+            match pipe.read_pipe(&mut buf) {
+                Ok(0) => {
+                    done = true;
+                    None
+                }
+                Err(D3xxError::Timeout) if received => {
+                    done = true;
+                    None
+                }
+                Ok(n) => {
+                    received = true;
+                    buf.truncate(n);
+                    Some(Ok(buf))
+                }
+                Err(e) => {
+                    done = true;
+                    Some(Err(e))
+                }
+            }
+        })
+    }
+
     /// Get the timeout in milliseconds for the specified pipe.
     ///
-    /// This method is only available on Windows.
-    #[cfg(windows)]
+    /// This is only supported on Windows, and fails with [`D3xxError::NotSupported`]
+    /// elsewhere.
     pub fn timeout(&self) -> Result<u32> {
-        let mut timeout = 0;
-        try_d3xx!(unsafe { ffi::FT_GetPipeTimeout(self.handle, u8::from(self.id), &mut timeout) })?;
-        Ok(timeout)
+        self.device
+            .api()
+            .pipe_timeout(self.handle(), u8::from(self.id))
     }
 
     /// Set the timeout in milliseconds for the specified pipe.
     pub fn set_timeout(&self, timeout: u32) -> Result<()> {
-        try_d3xx!(unsafe { ffi::FT_SetPipeTimeout(self.handle, u8::from(self.id), timeout) })
+        self.device
+            .api()
+            .set_pipe_timeout(self.handle(), u8::from(self.id), timeout)?;
+        self.device
+            .update_pipe_state(self.id, |state| state.timeout = Some(timeout));
+        Ok(())
+    }
+
+    /// Run `f` with the pipe timeout temporarily set to `timeout`.
+    ///
+    /// The current timeout is saved, replaced for the duration of `f`, and restored
+    /// afterwards, including if `f` panics. The timeout is rounded up to a whole number
+    /// of milliseconds; a zero duration sets no timeout.
+    ///
+    /// Fails without calling `f` if the timeout cannot be set, and with the error from
+    /// restoring it if that fails afterwards. Errors returned by `f` itself are part of
+    /// `R`.
+    ///
+    /// The timeout to restore is read with [`timeout`](Self::timeout), which is only
+    /// supported on Windows. Elsewhere the timeout last set through this crate is
+    /// restored, or the driver's default of 5 seconds if there is none.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::{io::Read, time::Duration};
+    /// # use d3xx::{Device, Pipe};
+    /// # let device = Device::open("ABC123")?;
+    /// let pipe = device.pipe(Pipe::In0);
+    /// let mut buf = [0; 64];
+    /// let probe = pipe.with_timeout(Duration::from_millis(10), || pipe.clone().read(&mut buf))?;
+    /// # Ok::<(), d3xx::D3xxError>(())
+    /// ```
+    pub fn with_timeout<R>(&self, timeout: Duration, f: impl FnOnce() -> R) -> Result<R> {
+        let guard = TimeoutGuard::new(self)?;
+        self.set_timeout(timeout_millis(timeout))?;
+        let result = f();
+        guard.restore()?;
+        Ok(result)
+    }
+
+    /// Read until `buf` is full or `deadline` passes.
+    ///
+    /// Before each read the [pipe timeout](Self::set_timeout) is set to the time
+    /// remaining until the deadline, so that the sequence of reads as a whole gives up
+    /// at `deadline`. The previous timeout is restored afterwards, even if a read fails.
+    ///
+    /// On success the number of bytes read is returned, which is less than `buf.len()`
+    /// if the deadline passed first. If the deadline passes before any data is
+    /// received, [`D3xxError::Timeout`] is returned instead. A read which returns no
+    /// data also ends the sequence, as if the deadline had passed.
+    ///
+    /// If a read fails with another error after data has been received, the data is
+    /// returned and the error is discarded. An error which persists is reported by the
+    /// next read of the pipe.
+    ///
+    /// The previous timeout is restored as described for
+    /// [`with_timeout`](Self::with_timeout).
+    pub fn read_until_deadline(&self, buf: &mut [u8], deadline: Instant) -> Result<usize> {
+        let guard = TimeoutGuard::new(self)?;
+        let result = self.read_with_deadline(buf, deadline);
+        let restore = guard.restore();
+        let n = result?;
+        restore?;
+        Ok(n)
+    }
+
+    fn read_with_deadline(&self, buf: &mut [u8], deadline: Instant) -> Result<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            // A timeout of zero means "wait forever", so round up to a millisecond.
+            let timeout = u32::try_from(remaining.as_millis().max(1)).unwrap_or(u32::MAX);
+            self.set_timeout(timeout)?;
+            match self.read_pipe(&mut buf[n..]) {
+                Ok(0) | Err(D3xxError::Timeout) => break,
+                Ok(read) => n += read,
+                // The data already read would be lost by returning the error.
+                Err(_) if n > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if n == 0 && !buf.is_empty() {
+            return Err(D3xxError::Timeout);
+        }
+        Ok(n)
+    }
+
+    /// Get the number of bytes waiting in the driver's queue for this pipe.
+    ///
+    /// For IN pipes this is data received from the device which has not been read yet,
+    /// so a non-zero value means a read will not have to wait. For OUT pipes this is
+    /// data which has been written but not yet sent to the device.
+    ///
+    /// This is only supported on Linux and macOS, and fails with
+    /// [`D3xxError::NotSupported`] on Windows. There is no way to emulate it there,
+    /// since probing the pipe with a read would consume the data.
+    pub fn pending_bytes(&self) -> Result<usize> {
+        let queued = self
+            .device
+            .api()
+            .queue_status(self.handle(), u8::from(self.id))?;
+        Ok(queued as usize)
     }
 
     /// Asynchronous read into the given buffer.
     ///
-    /// On success the number of bytes read is returned.
+    /// On success the number of bytes read is returned. An empty `buf` completes
+    /// immediately with `Ok(0)` without starting a transfer.
+    ///
+    /// With the `tracing` feature enabled, the transfer is wrapped in a `transfer` span.
     ///
     /// # Panics
     ///
     /// Panics if `buf.len()` exceeds `std::ffi::c_ulong::MAX`
     pub async fn read_async(&self, buf: &mut [u8]) -> Result<usize> {
-        let mut overlapped = Overlapped::with_handle(self.handle)?;
-        self.maybe_abort(ffi::util::read_pipe_async(
-            self.handle,
+        if let Some(result) = self.skip_read(buf.len()) {
+            return result;
+        }
+        let mut overlapped = Overlapped::new(self.device)?;
+        self.read_async_using(buf, &mut overlapped).await
+    }
+
+    /// Asynchronous read into the given buffer, using an overlapped structure from `pool`.
+    ///
+    /// This behaves like [`read_async`](Self::read_async), but avoids initializing and
+    /// releasing an overlapped structure for every transfer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pool` belongs to a different device, or if `buf.len()` exceeds
+    /// `std::ffi::c_ulong::MAX`.
+    pub async fn read_async_with(
+        &self,
+        buf: &mut [u8],
+        pool: &OverlappedPool<'_>,
+    ) -> Result<usize> {
+        self.check_pool(pool);
+        if let Some(result) = self.skip_read(buf.len()) {
+            return result;
+        }
+        let mut overlapped = pool.take()?;
+        let result = self.read_async_using(buf, &mut overlapped).await;
+        if result.is_ok() {
+            pool.put(overlapped);
+        }
+        result
+    }
+
+    /// Asynchronous write.
+    ///
+    /// On success the number of bytes written is returned. An empty `buf` completes
+    /// immediately with `Ok(0)` without starting a transfer.
+    ///
+    /// With the `tracing` feature enabled, the transfer is wrapped in a `transfer` span.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf.len()` exceeds `std::ffi::c_ulong::MAX`
+    pub async fn write_async(&self, buf: &[u8]) -> Result<usize> {
+        if let Some(result) = self.skip_write(buf.len()) {
+            return result;
+        }
+        let mut overlapped = Overlapped::new(self.device)?;
+        self.write_async_using(buf, &mut overlapped).await
+    }
+
+    /// Asynchronous write, using an overlapped structure from `pool`.
+    ///
+    /// This behaves like [`write_async`](Self::write_async), but avoids initializing and
+    /// releasing an overlapped structure for every transfer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pool` belongs to a different device, or if `buf.len()` exceeds
+    /// `std::ffi::c_ulong::MAX`.
+    pub async fn write_async_with(&self, buf: &[u8], pool: &OverlappedPool<'_>) -> Result<usize> {
+        self.check_pool(pool);
+        if let Some(result) = self.skip_write(buf.len()) {
+            return result;
+        }
+        let mut overlapped = pool.take()?;
+        let result = self.write_async_using(buf, &mut overlapped).await;
+        if result.is_ok() {
+            pool.put(overlapped);
+        }
+        result
+    }
+
+    /// Asynchronous read into the given buffer, which can be cancelled using `token`.
+    ///
+    /// This behaves like [`read_async`](Self::read_async), except that once `token` is
+    /// cancelled the pipe is aborted and the read resolves to
+    /// [`D3xxError::OperationAborted`]. The token is checked before the transfer is
+    /// started, and each time the future is polled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf.len()` exceeds `std::ffi::c_ulong::MAX`
+    pub async fn read_async_cancellable(
+        &self,
+        buf: &mut [u8],
+        token: &CancelToken,
+    ) -> Result<usize> {
+        if let Some(result) = self.skip_read(buf.len()) {
+            return result;
+        }
+        if token.is_cancelled() {
+            return Err(D3xxError::OperationAborted);
+        }
+        let mut overlapped = Overlapped::new(self.device)?.with_cancel(token.clone());
+        self.read_async_using(buf, &mut overlapped).await
+    }
+
+    /// Asynchronous write, which can be cancelled using `token`.
+    ///
+    /// See [`read_async_cancellable`](Self::read_async_cancellable) for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf.len()` exceeds `std::ffi::c_ulong::MAX`
+    pub async fn write_async_cancellable(&self, buf: &[u8], token: &CancelToken) -> Result<usize> {
+        if let Some(result) = self.skip_write(buf.len()) {
+            return result;
+        }
+        if token.is_cancelled() {
+            return Err(D3xxError::OperationAborted);
+        }
+        let mut overlapped = Overlapped::new(self.device)?.with_cancel(token.clone());
+        self.write_async_using(buf, &mut overlapped).await
+    }
+
+    /// Submit a batch of asynchronous writes, then wait for all of them to complete.
+    ///
+    /// Every write is started before any of them is waited on, so the driver can queue
+    /// the whole batch instead of the caller paying the round trip of each write in turn.
+    /// The buffers are owned by the returned future, which keeps them alive for as long as
+    /// their transfers may be in progress.
+    ///
+    /// On success the number of bytes written from each buffer is returned, in order.
+    /// Empty buffers complete with `0` without starting a transfer. If any write fails to
+    /// start or complete, the pipe is aborted and the error is returned; writes submitted
+    /// before the failure are cancelled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use d3xx::{Device, Pipe};
+    ///
+    /// # async fn example() -> d3xx::Result<()> {
+    /// let device = Device::open("ABC123")?;
+    /// let commands = vec![b"reset".to_vec(), b"start".to_vec()];
+    /// let written = device.pipe(Pipe::Out0).write_batch_async(commands).await?;
+    /// assert_eq!(written, [5, 5]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of any buffer exceeds `std::ffi::c_ulong::MAX`
+    pub async fn write_batch_async(&self, bufs: Vec<Vec<u8>>) -> Result<Vec<usize>> {
+        // Declared after `bufs`, so pending transfers are cancelled before the buffers
+        // they read from are freed.
+        let mut transfers = Vec::with_capacity(bufs.len());
+        for buf in &bufs {
+            if let Some(result) = self.skip_write(buf.len()) {
+                result?;
+                transfers.push(None);
+                continue;
+            }
+            let mut overlapped = Overlapped::new(self.device)?;
+            let res = self.device.api().write_pipe_async(
+                self.handle(),
+                u8::from(self.id),
+                buf,
+                overlapped.inner_mut(),
+            );
+            self.maybe_abort(res)?;
+            overlapped.set_pending(u8::from(self.id));
+            transfers.push(Some(overlapped));
+        }
+
+        let mut written = Vec::with_capacity(transfers.len());
+        for transfer in &mut transfers {
+            let n = match transfer {
+                Some(overlapped) => self.maybe_abort(overlapped.await)?,
+                None => 0,
+            };
+            written.push(n);
+        }
+        Ok(written)
+    }
+
+    /// Asynchronous read into the given buffer, failing with [`D3xxError::Timeout`] if the
+    /// transfer does not complete within `timeout`.
+    ///
+    /// This bounds how long a read may wait on an unresponsive device. Unlike the timeout
+    /// set with [`set_timeout`](Self::set_timeout), it is enforced by this crate using a
+    /// monotonic clock. The pipe is aborted when the timeout expires.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf.len()` exceeds `std::ffi::c_ulong::MAX`
+    pub async fn read_async_timeout(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        if let Some(result) = self.skip_read(buf.len()) {
+            return result;
+        }
+        let mut overlapped = Overlapped::new(self.device)?.with_timeout(timeout);
+        self.read_async_using(buf, &mut overlapped).await
+    }
+
+    /// Asynchronous write, failing with [`D3xxError::Timeout`] if the transfer does not
+    /// complete within `timeout`.
+    ///
+    /// See [`read_async_timeout`](Self::read_async_timeout) for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf.len()` exceeds `std::ffi::c_ulong::MAX`
+    pub async fn write_async_timeout(&self, buf: &[u8], timeout: Duration) -> Result<usize> {
+        if let Some(result) = self.skip_write(buf.len()) {
+            return result;
+        }
+        let mut overlapped = Overlapped::new(self.device)?.with_timeout(timeout);
+        self.write_async_using(buf, &mut overlapped).await
+    }
+
+    /// Read into the given buffer using an overlapped transfer, blocking until it completes.
+    ///
+    /// This starts the transfer like [`read_async`](Self::read_async), but waits for it in
+    /// `FT_GetOverlappedResult` instead of requiring an async runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf.len()` exceeds `std::ffi::c_ulong::MAX`
+    pub fn read_overlapped_blocking(&self, buf: &mut [u8]) -> Result<usize> {
+        if let Some(result) = self.skip_read(buf.len()) {
+            return result;
+        }
+        let mut overlapped = Overlapped::new(self.device)?;
+        let res = self.device.api().read_pipe_async(
+            self.handle(),
             u8::from(self.id),
             buf,
             overlapped.inner_mut(),
-        ))?;
-        overlapped.await
+        );
+        self.maybe_abort(res)?;
+        overlapped.set_pending(u8::from(self.id));
+        self.maybe_abort(overlapped.wait())
     }
 
-    /// Asynchronous write.
+    /// Write the given buffer using an overlapped transfer, blocking until it completes.
     ///
-    /// On success the number of bytes written is returned.
+    /// See [`read_overlapped_blocking`](Self::read_overlapped_blocking) for details.
     ///
     /// # Panics
     ///
     /// Panics if `buf.len()` exceeds `std::ffi::c_ulong::MAX`
-    pub async fn write_async(&self, buf: &[u8]) -> Result<usize> {
-        let mut overlapped = Overlapped::with_handle(self.handle)?;
-        self.maybe_abort(ffi::util::write_pipe_async(
-            self.handle,
+    pub fn write_overlapped_blocking(&self, buf: &[u8]) -> Result<usize> {
+        if let Some(result) = self.skip_write(buf.len()) {
+            return result;
+        }
+        let mut overlapped = Overlapped::new(self.device)?;
+        let res = self.device.api().write_pipe_async(
+            self.handle(),
             u8::from(self.id),
             buf,
             overlapped.inner_mut(),
-        ))?;
-        overlapped.await
+        );
+        self.maybe_abort(res)?;
+        overlapped.set_pending(u8::from(self.id));
+        self.maybe_abort(overlapped.wait())
     }
+
+    fn check_pool(&self, pool: &OverlappedPool<'_>) {
+        assert!(
+            std::ptr::eq(pool.device(), self.device),
+            "overlapped pool belongs to a different device"
+        );
+    }
+
+    /// Asynchronous read using the given overlapped structure.
+    async fn read_async_using(
+        &self,
+        buf: &mut [u8],
+        overlapped: &mut Overlapped<'_>,
+    ) -> Result<usize> {
+        #[cfg(feature = "tracing")]
+        let len = buf.len();
+        let transfer = async {
+            let res = self.device.api().read_pipe_async(
+                self.handle(),
+                u8::from(self.id),
+                buf,
+                overlapped.inner_mut(),
+            );
+            self.maybe_abort(res)?;
+            overlapped.set_pending(u8::from(self.id));
+            overlapped.await
+        };
+        #[cfg(feature = "tracing")]
+        let transfer = crate::overlapped::instrument("read", u8::from(self.id), len, transfer);
+        transfer.await
+    }
+
+    /// Asynchronous write using the given overlapped structure.
+    async fn write_async_using(
+        &self,
+        buf: &[u8],
+        overlapped: &mut Overlapped<'_>,
+    ) -> Result<usize> {
+        #[cfg(feature = "tracing")]
+        let len = buf.len();
+        let transfer = async {
+            let res = self.device.api().write_pipe_async(
+                self.handle(),
+                u8::from(self.id),
+                buf,
+                overlapped.inner_mut(),
+            );
+            self.maybe_abort(res)?;
+            overlapped.set_pending(u8::from(self.id));
+            overlapped.await
+        };
+        #[cfg(feature = "tracing")]
+        let transfer = crate::overlapped::instrument("write", u8::from(self.id), len, transfer);
+        transfer.await
+    }
+}
+
+/// State kept by a [`Device`] for each of its pipes between calls.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct PipeState {
+    /// Timeout last set through [`PipeIo::set_timeout`].
+    pub(crate) timeout: Option<u32>,
 }
 
-impl<'a> Write for PipeIo<'a> {
+/// Restores the timeout a pipe had when the guard was created.
+///
+/// The timeout is restored by [`restore`](Self::restore), or when the guard is dropped
+/// (e.g. while unwinding), ignoring errors.
+struct TimeoutGuard<'p, 'a> {
+    pipe: &'p PipeIo<'a>,
+    /// Timeout to restore.
+    previous: u32,
+}
+
+impl<'p, 'a> TimeoutGuard<'p, 'a> {
+    /// Timeout of a pipe when the device is opened, in milliseconds.
+    const DRIVER_DEFAULT: u32 = 5000;
+
+    /// Save the current timeout of `pipe`.
+    ///
+    /// Where the driver cannot report the timeout, the one last set through this crate
+    /// is saved instead, or the driver's default if there is none.
+    fn new(pipe: &'p PipeIo<'a>) -> Result<Self> {
+        let previous = match pipe.timeout() {
+            Ok(timeout) => timeout,
+            Err(D3xxError::NotSupported) => pipe
+                .device
+                .update_pipe_state(pipe.id, |state| state.timeout)
+                .unwrap_or(Self::DRIVER_DEFAULT),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { pipe, previous })
+    }
+
+    /// Restore the saved timeout, reporting any error.
+    fn restore(self) -> Result<()> {
+        let guard = std::mem::ManuallyDrop::new(self);
+        guard.pipe.set_timeout(guard.previous)
+    }
+}
+
+impl Drop for TimeoutGuard<'_, '_> {
+    fn drop(&mut self) {
+        let _ = self.pipe.set_timeout(self.previous);
+    }
+}
+
+/// A pipe configured for streaming, created by [`PipeIo::begin_stream`].
+///
+/// Dropping the guard aborts pending transfers and disables streaming on the pipe,
+/// ignoring errors.
+#[derive(Debug)]
+#[must_use = "streaming is disabled when the guard is dropped"]
+pub struct StreamGuard<'a> {
+    pipe: PipeIo<'a>,
+}
+
+impl<'a> StreamGuard<'a> {
+    /// Get the pipe being streamed.
+    #[must_use]
+    pub fn pipe(&self) -> &PipeIo<'a> {
+        &self.pipe
+    }
+
+    /// Abort pending transfers and disable streaming, consuming the guard.
+    ///
+    /// Both steps are always attempted; if either fails, the first error is returned.
+    pub fn end(self) -> Result<()> {
+        let guard = std::mem::ManuallyDrop::new(self);
+        guard.teardown()
+    }
+
+    fn teardown(&self) -> Result<()> {
+        let abort = self.pipe.abort();
+        let clear = self.pipe.set_stream_size(None);
+        abort.and(clear)
+    }
+}
+
+impl Drop for StreamGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.teardown();
+    }
+}
+
+/// Result of [`PipeIo::benchmark`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Throughput {
+    bytes: usize,
+    elapsed: Duration,
+}
+
+impl Throughput {
+    /// Number of bytes transferred.
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Time taken to transfer the data.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Average transfer rate in bytes per second, or zero if no time elapsed.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bytes_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.bytes as f64 / secs
+        }
+    }
+}
+
+/// Pipes affected by [`PipeIo::set_stream_size_for`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum StreamPipeTarget {
+    /// Only the pipe the method is called on.
+    ThisPipe,
+    /// Every IN (read) pipe.
+    AllIn,
+    /// Every OUT (write) pipe.
+    AllOut,
+}
+
+impl StreamPipeTarget {
+    /// Value of the `bAllWritePipes` flag.
+    pub(crate) fn all_out(self) -> bool {
+        self == Self::AllOut
+    }
+
+    /// Value of the `bAllReadPipes` flag.
+    pub(crate) fn all_in(self) -> bool {
+        self == Self::AllIn
+    }
+}
+
+/// Decides which transfer errors cause a pipe to be [aborted](PipeIo::abort).
+///
+/// The D3XX Programmer's Guide recommends aborting a pipe after any failed transfer,
+/// which is the default. Aborting discards data the driver has buffered for the pipe,
+/// so a read which merely timed out while the device had nothing to send may instead
+/// be retried without aborting, using [`ExceptTimeout`](Self::ExceptTimeout).
+///
+/// The policy only applies to errors reported for a transfer. A transfer which is
+/// still pending when its overlapped structure is released, such as one cut short by
+/// [`PipeIo::read_async_timeout`], is always aborted.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum AbortPolicy {
+    /// Abort the pipe on every error.
+    #[default]
+    Always,
+    /// Abort the pipe on every error except [`D3xxError::Timeout`].
+    ExceptTimeout,
+    /// Never abort the pipe automatically.
+    Never,
+}
+
+impl AbortPolicy {
+    /// Whether a transfer failing with `error` aborts the pipe under this policy.
+    #[must_use]
+    pub const fn aborts_on(self, error: D3xxError) -> bool {
+        match self {
+            Self::Always => true,
+            Self::ExceptTimeout => !matches!(error, D3xxError::Timeout),
+            Self::Never => false,
+        }
+    }
+}
+
+/// A buffered reader for a pipe, created by [`PipeIo::into_buf_reader`].
+pub type BufPipeReader<'a> = BufReader<PipeIo<'a>>;
+
+/// A buffered writer for a pipe, created by [`PipeIo::into_buf_writer`].
+///
+/// Dropping the writer writes any buffered data, ignoring errors. Call
+/// [`flush`](Write::flush) beforehand to handle them.
+pub type BufPipeWriter<'a> = BufWriter<PipeIo<'a>>;
+
+impl PartialEq for PipeIo<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle() == other.handle() && self.id == other.id
+    }
+}
+
+impl Eq for PipeIo<'_> {}
+
+impl Write for PipeIo<'_> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let res = ffi::util::write_pipe(self.handle, u8::from(self.id), buf);
-        Ok(self.maybe_abort(res)?)
+        Ok(self.write_pipe(buf)?)
+    }
+
+    /// Writes the slices in a single transfer.
+    ///
+    /// Unless there is at most one non-empty slice, the slices are first copied
+    /// into a contiguous buffer.
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        let mut non_empty = bufs.iter().filter(|buf| !buf.is_empty());
+        match (non_empty.next(), non_empty.next()) {
+            (None, _) => Ok(0),
+            (Some(buf), None) => self.write(buf),
+            _ => self.write(
+                &bufs
+                    .iter()
+                    .flat_map(|buf| buf.iter().copied())
+                    .collect::<Vec<_>>(),
+            ),
+        }
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        try_d3xx!(unsafe { ffi::FT_FlushPipe(self.handle, u8::from(self.id)) })?;
-        Ok(())
+        Ok(self.flush_pipe()?)
     }
 }
 
-impl<'a> Read for PipeIo<'a> {
+impl Read for PipeIo<'_> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let res = ffi::util::read_pipe(self.handle, u8::from(self.id), buf);
-        Ok(self.maybe_abort(res)?)
+        Ok(self.read_pipe(buf)?)
+    }
+
+    /// Reads into the slices using a single transfer.
+    ///
+    /// Unless there is at most one non-empty slice, data is read into a contiguous
+    /// buffer and then copied into the slices in order.
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+        if bufs.iter().filter(|buf| !buf.is_empty()).count() <= 1 {
+            return match bufs.iter_mut().find(|buf| !buf.is_empty()) {
+                Some(buf) => self.read(buf),
+                None => Ok(0),
+            };
+        }
+        let mut data = vec![0; bufs.iter().map(|buf| buf.len()).sum()];
+        let n = self.read(&mut data)?;
+        let mut remaining = &data[..n];
+        for buf in bufs {
+            let len = buf.len().min(remaining.len());
+            buf[..len].copy_from_slice(&remaining[..len]);
+            remaining = &remaining[len..];
+        }
+        Ok(n)
     }
 }
 
@@ -244,6 +1250,33 @@ pub enum Pipe {
 }
 
 impl Pipe {
+    /// All pipes, inputs first.
+    #[must_use]
+    pub const fn all() -> [Self; 8] {
+        [
+            Self::In0,
+            Self::In1,
+            Self::In2,
+            Self::In3,
+            Self::Out0,
+            Self::Out1,
+            Self::Out2,
+            Self::Out3,
+        ]
+    }
+
+    /// All input (read) pipes.
+    #[must_use]
+    pub const fn inputs() -> [Self; 4] {
+        [Self::In0, Self::In1, Self::In2, Self::In3]
+    }
+
+    /// All output (write) pipes.
+    #[must_use]
+    pub const fn outputs() -> [Self; 4] {
+        [Self::Out0, Self::Out1, Self::Out2, Self::Out3]
+    }
+
     /// Check if the pipe is an input (read) pipe.
     #[inline]
     #[must_use]
@@ -257,6 +1290,16 @@ impl Pipe {
     pub fn is_out(self) -> bool {
         (self as u8) & 0x80 == 0
     }
+
+    /// Position of the pipe in [`Pipe::all`].
+    pub(crate) fn index(self) -> usize {
+        let n = usize::from((self as u8) & 0x7F) - 2;
+        if self.is_in() {
+            n
+        } else {
+            n + 4
+        }
+    }
 }
 
 /// The type of a pipe.
@@ -299,7 +1342,927 @@ impl From<ffi::FT_PIPE_TYPE> for PipeType {
 
 #[cfg(test)]
 mod tests {
+    use std::{panic::AssertUnwindSafe, sync::Arc};
+
     use super::*;
+    use crate::mock::{self, Call, MockApi};
+
+    #[test]
+    fn buf_writer_coalesces() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let mut writer = device.pipe(Pipe::Out1).into_buf_writer().unwrap();
+        assert_eq!(writer.capacity(), 1024);
+        for i in 0..100u8 {
+            writer.write_all(&[i; 4]).unwrap();
+        }
+        assert!(api.writes(0x03).is_empty());
+
+        writer.flush().unwrap();
+        let transfers = api.writes(0x03);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].len(), 400);
+        assert_eq!(api.calls().last(), Some(&Call::FlushPipe { pipe: 0x03 }));
+    }
+
+    #[test]
+    fn buf_writer_writes_on_drop() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let mut writer = device.pipe(Pipe::Out0).into_buf_writer().unwrap();
+        writer.write_all(b"abc").unwrap();
+        writer.write_all(b"def").unwrap();
+        drop(writer);
+        assert_eq!(api.writes(0x02), [b"abcdef".to_vec()]);
+    }
+
+    #[test]
+    fn buf_reader() {
+        use std::io::BufRead;
+
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"line one\nline two\n");
+        let device = mock::device(&api);
+        let mut reader = device.pipe(Pipe::In0).into_buf_reader().unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "line one\n");
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "line two\n");
+        let reads = api
+            .calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::ReadPipe { .. }))
+            .count();
+        assert_eq!(reads, 1);
+    }
+
+    #[test]
+    fn read_to_vec_until_timeout() {
+        let api = Arc::new(MockApi::default());
+        let data: Vec<u8> = (0..2048u32).map(|i| (i % 251) as u8).collect();
+        api.push_read(0x82, &data[..512]);
+        api.push_read(0x82, &data[512..1024]);
+        api.push_read(0x82, &data[1024..]);
+        let device = mock::device(&api);
+        assert_eq!(device.pipe(Pipe::In0).read_to_vec(1 << 20).unwrap(), data);
+
+        let reads: Vec<_> = api
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::ReadPipe { len, .. } => Some(len),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reads, [512, 512, 1024, 2048]);
+        // the final read timed out, so the pipe is aborted
+        assert_eq!(api.calls().last(), Some(&Call::AbortPipe { pipe: 0x82 }));
+    }
+
+    #[test]
+    fn read_to_vec_short_read() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, &[1; 512]);
+        api.push_read(0x82, &[2; 100]);
+        api.push_read(0x82, &[3; 10]);
+        let device = mock::device(&api);
+        let data = device.pipe(Pipe::In0).read_to_vec(4096).unwrap();
+        assert_eq!(data.len(), 612);
+        assert_eq!(data[511..513], [1, 2]);
+    }
+
+    #[test]
+    fn read_to_vec_max() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, &[7; 4096]);
+        let device = mock::device(&api);
+        assert_eq!(device.pipe(Pipe::In0).read_to_vec(1000).unwrap(), [7; 1000]);
+        assert_eq!(device.pipe(Pipe::In0).read_to_vec(0).unwrap(), []);
+    }
+
+    #[test]
+    fn read_to_vec_timeout_without_data() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        assert_eq!(
+            device.pipe(Pipe::In0).read_to_vec(4096),
+            Err(D3xxError::Timeout)
+        );
+    }
+
+    #[test]
+    fn chunks_until_timeout() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x83, &[1; 4096]);
+        api.push_read(0x83, &[2; 4096]);
+        api.push_read(0x83, &[3; 100]);
+        let device = mock::device(&api);
+        let chunks: Vec<_> = device
+            .pipe(Pipe::In1)
+            .chunks(4096)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(chunks, [vec![1; 4096], vec![2; 4096], vec![3; 100]]);
+        // the final read timed out, so the pipe is aborted
+        assert_eq!(api.calls().last(), Some(&Call::AbortPipe { pipe: 0x83 }));
+    }
+
+    #[test]
+    fn chunks_split_large_reads() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, &[5; 10]);
+        let device = mock::device(&api);
+        let lens: Vec<_> = device
+            .pipe(Pipe::In0)
+            .chunks(4)
+            .map(|chunk| chunk.unwrap().len())
+            .collect();
+        assert_eq!(lens, [4, 4, 2]);
+    }
+
+    #[test]
+    fn chunks_timeout_without_data() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let mut chunks = device.pipe(Pipe::In0).chunks(4096);
+        assert_eq!(chunks.next(), Some(Err(D3xxError::Timeout)));
+        assert_eq!(chunks.next(), None);
+    }
+
+    #[test]
+    fn chunks_error() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, &[1; 8]);
+        api.push_read(0x82, &[2; 8]);
+        api.fail_once_when(
+            |call| matches!(call, Call::ReadPipe { .. }),
+            D3xxError::IoError,
+        );
+        let device = mock::device(&api);
+        let mut chunks = device.pipe(Pipe::In0).chunks(8);
+        assert_eq!(chunks.next(), Some(Err(D3xxError::IoError)));
+        assert_eq!(chunks.next(), None);
+
+        let mut chunks = device.pipe(Pipe::In0).chunks(0);
+        assert_eq!(chunks.next(), Some(Err(D3xxError::InvalidArgs)));
+        assert_eq!(chunks.next(), None);
+    }
+
+    #[test]
+    fn flush_in_pipe() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        device.pipe(Pipe::In2).flush_pipe().unwrap();
+        device.pipe(Pipe::Out2).flush_pipe().unwrap();
+        assert_eq!(
+            api.calls(),
+            [
+                Call::FlushPipe { pipe: 0x84 },
+                Call::FlushPipe { pipe: 0x04 }
+            ]
+        );
+    }
+
+    #[test]
+    fn descriptor_interface() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In1);
+        assert_eq!(pipe.descriptor().unwrap().id(), Pipe::In1);
+        assert_eq!(pipe.descriptor_for_interface(2).unwrap().id(), Pipe::In1);
+        assert_eq!(
+            api.calls(),
+            [
+                Call::PipeInformation {
+                    interface: 1,
+                    pipe: 0x83
+                },
+                Call::PipeInformation {
+                    interface: 2,
+                    pipe: 0x83
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn checked_rejects_wrong_direction() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abc");
+        let device = mock::device(&api);
+        let mut buf = [0; 8];
+        let err = device.pipe_checked(Pipe::Out0).read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let err = device.pipe_checked(Pipe::In0).write(b"abc").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            device
+                .pipe_checked(Pipe::In0)
+                .write_overlapped_blocking(&[]),
+            Err(D3xxError::InvalidArgs)
+        );
+        assert!(api.calls().is_empty());
+
+        assert_eq!(device.pipe_checked(Pipe::In0).read(&mut buf).unwrap(), 3);
+        assert_eq!(device.pipe_checked(Pipe::Out0).write(b"abc").unwrap(), 3);
+    }
+
+    #[test]
+    fn unchecked_passes_wrong_direction() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x02, b"abc");
+        let device = mock::device(&api);
+        assert_eq!(device.pipe(Pipe::Out0).read(&mut [0; 8]).unwrap(), 3);
+        assert_eq!(device.pipe(Pipe::In0).write(b"abc").unwrap(), 3);
+        assert_eq!(api.writes(0x82), [b"abc".to_vec()]);
+    }
+
+    #[test]
+    fn empty_transfers_skip_driver() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abc");
+        let device = mock::device(&api);
+        assert_eq!(device.pipe(Pipe::In0).read(&mut []).unwrap(), 0);
+        assert_eq!(device.pipe(Pipe::In0).read_uninit(&mut []), Ok(0));
+        assert_eq!(device.pipe(Pipe::Out0).write(&[]).unwrap(), 0);
+        assert_eq!(
+            std::io::copy(&mut [].as_slice(), &mut device.pipe(Pipe::Out0)).unwrap(),
+            0
+        );
+        assert!(api.calls().is_empty());
+    }
+
+    #[test]
+    fn read_uninit() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"partial");
+        let device = mock::device(&api);
+
+        let mut buf = Vec::with_capacity(64);
+        let n = device
+            .pipe(Pipe::In0)
+            .read_uninit(buf.spare_capacity_mut())
+            .unwrap();
+        assert_eq!(n, 7);
+        // SAFETY: `read_uninit` initialized the first `n` bytes.
+        unsafe { buf.set_len(n) };
+        assert_eq!(buf, b"partial");
+        assert_eq!(
+            api.calls(),
+            [Call::ReadPipe {
+                pipe: 0x82,
+                len: 64
+            }]
+        );
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn read_buf() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"hello");
+        api.push_read(0x82, b" world");
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+
+        let mut buf = bytes::BytesMut::new();
+        assert_eq!(pipe.read_buf(&mut buf, 16), Ok(5));
+        assert_eq!(pipe.read_buf(&mut buf, 3), Ok(3));
+        assert_eq!(pipe.read_buf(&mut buf, 16), Ok(3));
+        assert_eq!(buf, b"hello world"[..]);
+        assert_eq!(pipe.read_buf(&mut buf, 16), Err(D3xxError::Timeout));
+        assert_eq!(buf.len(), 11);
+        assert_eq!(
+            api.calls()[..3],
+            [
+                Call::ReadPipe {
+                    pipe: 0x82,
+                    len: 16
+                },
+                Call::ReadPipe { pipe: 0x82, len: 3 },
+                Call::ReadPipe {
+                    pipe: 0x82,
+                    len: 16
+                },
+            ]
+        );
+    }
+
+    /// Clock which advances by `step` every time it is read.
+    fn fake_clock(step: Duration) -> impl FnMut() -> Instant {
+        let mut now = Instant::now();
+        move || {
+            now += step;
+            now
+        }
+    }
+
+    #[test]
+    fn benchmark_write() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let result = device
+            .pipe(Pipe::Out0)
+            .benchmark_with(2500, 1000, fake_clock(Duration::from_millis(500)))
+            .unwrap();
+        assert_eq!(result.bytes(), 2500);
+        assert_eq!(result.elapsed(), Duration::from_millis(500));
+        assert!((result.bytes_per_sec() - 5000.0).abs() < f64::EPSILON);
+        assert_eq!(
+            api.calls(),
+            [
+                Call::WritePipe {
+                    pipe: 0x02,
+                    len: 1000
+                },
+                Call::WritePipe {
+                    pipe: 0x02,
+                    len: 1000
+                },
+                Call::WritePipe {
+                    pipe: 0x02,
+                    len: 500
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn benchmark_read() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, &[0; 600]);
+        api.push_read(0x82, &[0; 400]);
+        let device = mock::device(&api);
+        let result = device
+            .pipe(Pipe::In0)
+            .benchmark_with(1000, 512, fake_clock(Duration::from_secs(1)))
+            .unwrap();
+        assert_eq!(result.bytes(), 1000);
+        assert!((result.bytes_per_sec() - 1000.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn benchmark_error_aborts() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, &[0; 512]);
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+        assert_eq!(
+            pipe.benchmark_with(1024, 512, fake_clock(Duration::ZERO)),
+            Err(D3xxError::Timeout)
+        );
+        assert_eq!(api.calls().last(), Some(&Call::AbortPipe { pipe: 0x82 }));
+        assert_eq!(pipe.benchmark(1024, 0), Err(D3xxError::InvalidArgs));
+    }
+
+    #[test]
+    fn read_until_deadline_in_past() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"data");
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+        pipe.set_timeout(1234).unwrap();
+
+        let mut buf = [0; 4];
+        let deadline = Instant::now();
+        assert_eq!(
+            pipe.read_until_deadline(&mut buf, deadline),
+            Err(D3xxError::Timeout)
+        );
+        assert_eq!(
+            api.calls()[1..],
+            [
+                Call::PipeTimeout { pipe: 0x82 },
+                Call::SetPipeTimeout {
+                    pipe: 0x82,
+                    timeout_ms: 1234
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_until_deadline_partial() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"abc");
+        api.push_read(0x82, b"de");
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+
+        let mut buf = [0; 8];
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(pipe.read_until_deadline(&mut buf, deadline), Ok(5));
+        assert_eq!(&buf[..5], b"abcde");
+        assert_eq!(pipe.timeout(), Ok(5000));
+
+        let calls = api.calls();
+        assert_eq!(calls[0], Call::PipeTimeout { pipe: 0x82 });
+        let reads = calls
+            .iter()
+            .filter(|call| matches!(call, Call::ReadPipe { .. }))
+            .count();
+        assert_eq!(reads, 3);
+        // Every read is preceded by a timeout no later than the deadline.
+        for pair in calls.windows(2) {
+            if let [previous, Call::ReadPipe { .. }] = pair {
+                assert!(matches!(
+                    previous,
+                    Call::SetPipeTimeout { timeout_ms, .. } if (1..=60_000).contains(timeout_ms)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn read_until_deadline_restores_after_error() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| matches!(call, Call::ReadPipe { .. }),
+            D3xxError::IoError,
+        );
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+
+        let mut buf = [0; 8];
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(
+            pipe.read_until_deadline(&mut buf, deadline),
+            Err(D3xxError::IoError)
+        );
+        assert_eq!(pipe.timeout(), Ok(5000));
+    }
+
+    #[test]
+    fn read_until_deadline_returns_data_before_error() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x83, b"abc");
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In1);
+        // The first read succeeds, the second fails.
+        api.fail_when(
+            |call| matches!(call, Call::ReadPipe { len: 5, .. }),
+            D3xxError::IoError,
+        );
+
+        let mut buf = [0; 8];
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(pipe.read_until_deadline(&mut buf, deadline), Ok(3));
+        assert_eq!(&buf[..3], b"abc");
+        assert_eq!(pipe.timeout(), Ok(5000));
+        // The error is not kept for a later read.
+        api.push_read(0x83, b"d");
+        assert_eq!(pipe.clone().read(&mut buf[..1]).unwrap(), 1);
+        // An error which persists is reported by the next read.
+        assert_eq!(
+            pipe.read_until_deadline(&mut buf[..5], deadline),
+            Err(D3xxError::IoError)
+        );
+    }
+
+    #[test]
+    fn read_until_deadline_stops_on_empty_read() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"ab");
+        api.push_read(0x82, b"");
+        api.push_read(0x82, b"cd");
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+
+        let mut buf = [0; 8];
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(pipe.read_until_deadline(&mut buf, deadline), Ok(2));
+        assert_eq!(&buf[..2], b"ab");
+        let reads = api
+            .calls()
+            .iter()
+            .filter(|call| matches!(call, Call::ReadPipe { .. }))
+            .count();
+        assert_eq!(reads, 2);
+        // The remaining data is left for the next read.
+        assert_eq!(pipe.read_until_deadline(&mut buf, deadline), Ok(2));
+        assert_eq!(&buf[..2], b"cd");
+    }
+
+    #[test]
+    fn read_until_deadline_without_timeout_query() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| matches!(call, Call::PipeTimeout { .. }),
+            D3xxError::NotSupported,
+        );
+        api.push_read(0x82, b"data");
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+
+        // The timeout has never been set, so the driver's default is restored.
+        let mut buf = [0; 4];
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(pipe.read_until_deadline(&mut buf, deadline), Ok(4));
+        assert_eq!(
+            api.calls().last(),
+            Some(&Call::SetPipeTimeout {
+                pipe: 0x82,
+                timeout_ms: 5000
+            })
+        );
+
+        // The timeout last set through the crate is restored.
+        device.pipe(Pipe::In0).set_timeout(1234).unwrap();
+        assert_eq!(
+            pipe.read_until_deadline(&mut buf, deadline),
+            Err(D3xxError::Timeout)
+        );
+        assert_eq!(
+            api.calls().last(),
+            Some(&Call::SetPipeTimeout {
+                pipe: 0x82,
+                timeout_ms: 1234
+            })
+        );
+    }
+
+    #[test]
+    fn pipe_index() {
+        for (i, pipe) in Pipe::all().into_iter().enumerate() {
+            assert_eq!(pipe.index(), i);
+        }
+    }
+
+    #[test]
+    fn with_timeout_restores() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+        pipe.set_timeout(700).unwrap();
+
+        let inner = pipe.with_timeout(Duration::from_millis(10), || pipe.timeout());
+        assert_eq!(inner, Ok(Ok(10)));
+        assert_eq!(pipe.timeout(), Ok(700));
+
+        let result =
+            pipe.with_timeout(Duration::from_millis(10), || pipe.clone().read(&mut [0; 8]));
+        assert_eq!(
+            result.unwrap().unwrap_err().kind(),
+            std::io::ErrorKind::TimedOut
+        );
+        assert_eq!(pipe.timeout(), Ok(700));
+    }
+
+    #[test]
+    fn with_timeout_restores_default_without_timeout_query() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| matches!(call, Call::PipeTimeout { .. }),
+            D3xxError::NotSupported,
+        );
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::Out0);
+
+        // No timeout was set through the crate, so the driver's default is restored.
+        assert_eq!(pipe.with_timeout(Duration::from_millis(10), || ()), Ok(()));
+        assert_eq!(
+            api.calls()[1..],
+            [
+                Call::SetPipeTimeout {
+                    pipe: 0x02,
+                    timeout_ms: 10
+                },
+                Call::SetPipeTimeout {
+                    pipe: 0x02,
+                    timeout_ms: 5000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn with_timeout_restores_on_panic() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            pipe.with_timeout(Duration::from_millis(10), || panic!("probe failed"))
+        }));
+        assert!(result.is_err());
+        assert_eq!(pipe.timeout(), Ok(5000));
+    }
+
+    #[test]
+    fn with_timeout_set_fails() {
+        let api = Arc::new(MockApi::default());
+        api.fail_once_when(
+            |call| matches!(call, Call::SetPipeTimeout { .. }),
+            D3xxError::InvalidParameter,
+        );
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+        let mut called = false;
+        assert_eq!(
+            pipe.with_timeout(Duration::from_millis(10), || called = true),
+            Err(D3xxError::InvalidParameter)
+        );
+        assert!(!called);
+        assert_eq!(pipe.timeout(), Ok(5000));
+    }
+
+    #[test]
+    fn read_uninit_error_aborts() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let mut buf = [MaybeUninit::uninit(); 16];
+        assert_eq!(
+            device.pipe(Pipe::In0).read_uninit(&mut buf),
+            Err(D3xxError::Timeout)
+        );
+        assert_eq!(api.calls()[1..], [Call::AbortPipe { pipe: 0x82 }]);
+    }
+
+    #[test]
+    fn timeout_does_not_abort_except_timeout() {
+        let api = Arc::new(MockApi::default());
+        let mut device = mock::device(&api);
+        device.set_abort_policy(AbortPolicy::ExceptTimeout);
+        let mut buf = [0; 16];
+        let pipe = device.pipe(Pipe::In0);
+        assert_eq!(pipe.abort_policy(), AbortPolicy::ExceptTimeout);
+        assert_eq!(pipe.read_pipe(&mut buf), Err(D3xxError::Timeout));
+        assert!(!api.calls().contains(&Call::AbortPipe { pipe: 0x82 }));
+
+        // Other errors still abort the pipe.
+        api.fail_once_when(|c| matches!(c, Call::ReadPipe { .. }), D3xxError::IoError);
+        assert_eq!(pipe.read_pipe(&mut buf), Err(D3xxError::IoError));
+        assert_eq!(api.calls().last(), Some(&Call::AbortPipe { pipe: 0x82 }));
+    }
+
+    #[test]
+    fn abort_policy_override() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In1).with_abort_policy(AbortPolicy::Never);
+        let mut buf = [0; 16];
+        assert_eq!(pipe.read_pipe(&mut buf), Err(D3xxError::Timeout));
+        api.fail_once_when(|c| matches!(c, Call::ReadPipe { .. }), D3xxError::IoError);
+        assert_eq!(pipe.read_pipe(&mut buf), Err(D3xxError::IoError));
+        assert!(!api
+            .calls()
+            .iter()
+            .any(|c| matches!(c, Call::AbortPipe { .. })));
+        // Only this instance is affected.
+        assert_eq!(device.abort_policy(), AbortPolicy::Always);
+        assert_eq!(device.pipe(Pipe::In1).abort_policy(), AbortPolicy::Always);
+    }
+
+    #[test]
+    fn abort_policy_decisions() {
+        assert!(AbortPolicy::Always.aborts_on(D3xxError::Timeout));
+        assert!(!AbortPolicy::ExceptTimeout.aborts_on(D3xxError::Timeout));
+        assert!(AbortPolicy::ExceptTimeout.aborts_on(D3xxError::IoError));
+        assert!(!AbortPolicy::Never.aborts_on(D3xxError::InvalidHandle));
+    }
+
+    #[test]
+    fn write_vectored() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let bufs = [
+            IoSlice::new(b"abc"),
+            IoSlice::new(b""),
+            IoSlice::new(b"de"),
+            IoSlice::new(b"fghi"),
+        ];
+        assert_eq!(device.pipe(Pipe::Out2).write_vectored(&bufs).unwrap(), 9);
+        assert_eq!(api.writes(0x04), [b"abcdefghi".to_vec()]);
+    }
+
+    #[test]
+    fn write_vectored_error_aborts() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| matches!(call, Call::WritePipe { .. }),
+            D3xxError::IoError,
+        );
+        let device = mock::device(&api);
+        let bufs = [IoSlice::new(b"abc"), IoSlice::new(b"de")];
+        assert!(device.pipe(Pipe::Out2).write_vectored(&bufs).is_err());
+        assert_eq!(
+            api.calls(),
+            [
+                Call::WritePipe { pipe: 0x04, len: 5 },
+                Call::AbortPipe { pipe: 0x04 }
+            ]
+        );
+    }
+
+    #[test]
+    fn read_vectored() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x83, b"abcdefg");
+        let device = mock::device(&api);
+        let (mut a, mut b, mut c) = ([0u8; 3], [0u8; 0], [0u8; 8]);
+        let mut bufs = [
+            IoSliceMut::new(&mut a),
+            IoSliceMut::new(&mut b),
+            IoSliceMut::new(&mut c),
+        ];
+        assert_eq!(device.pipe(Pipe::In1).read_vectored(&mut bufs).unwrap(), 7);
+        assert_eq!(&a, b"abc");
+        assert_eq!(&c[..4], b"defg");
+        assert_eq!(
+            api.calls(),
+            [Call::ReadPipe {
+                pipe: 0x83,
+                len: 11
+            }]
+        );
+    }
+
+    #[test]
+    fn read_vectored_single() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x83, b"xy");
+        let device = mock::device(&api);
+        let mut a = [0u8; 4];
+        let mut bufs = [IoSliceMut::new(&mut []), IoSliceMut::new(&mut a)];
+        assert_eq!(device.pipe(Pipe::In1).read_vectored(&mut bufs).unwrap(), 2);
+        assert_eq!(&a[..2], b"xy");
+    }
+
+    #[test]
+    fn reset() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        device.pipe(Pipe::In1).reset().unwrap();
+        assert_eq!(
+            api.calls(),
+            [
+                Call::AbortPipe { pipe: 0x83 },
+                Call::FlushPipe { pipe: 0x83 }
+            ]
+        );
+    }
+
+    #[test]
+    fn reset_flushes_after_abort_error() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| matches!(call, Call::AbortPipe { .. }),
+            D3xxError::IoError,
+        );
+        api.fail_when(
+            |call| matches!(call, Call::FlushPipe { .. }),
+            D3xxError::InvalidParameter,
+        );
+        let device = mock::device(&api);
+        assert_eq!(device.pipe(Pipe::Out0).reset(), Err(D3xxError::IoError));
+        assert_eq!(
+            api.calls(),
+            [
+                Call::AbortPipe { pipe: 0x02 },
+                Call::FlushPipe { pipe: 0x02 }
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_bytes() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x83, b"abc");
+        api.push_read(0x83, b"de");
+        let device = mock::device(&api);
+        assert_eq!(device.pipe(Pipe::In1).pending_bytes(), Ok(5));
+        assert_eq!(device.pipe(Pipe::In2).pending_bytes(), Ok(0));
+        assert_eq!(
+            api.calls(),
+            [
+                Call::QueueStatus { pipe: 0x83 },
+                Call::QueueStatus { pipe: 0x84 }
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_bytes_error() {
+        let api = Arc::new(MockApi::default());
+        api.fail_when(
+            |call| matches!(call, Call::QueueStatus { .. }),
+            D3xxError::NotSupported,
+        );
+        let device = mock::device(&api);
+        assert_eq!(
+            device.pipe(Pipe::In1).pending_bytes(),
+            Err(D3xxError::NotSupported)
+        );
+        // Querying the queue is not a transfer, so the pipe is not aborted.
+        assert_eq!(api.calls(), [Call::QueueStatus { pipe: 0x83 }]);
+    }
+
+    #[test]
+    fn stream_pipe_target_flags() {
+        let flags = |t: StreamPipeTarget| (t.all_out(), t.all_in());
+        assert_eq!(flags(StreamPipeTarget::ThisPipe), (false, false));
+        assert_eq!(flags(StreamPipeTarget::AllIn), (false, true));
+        assert_eq!(flags(StreamPipeTarget::AllOut), (true, false));
+    }
+
+    #[test]
+    fn set_stream_size_checked() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In1);
+        for size in [1024, 4096] {
+            assert_eq!(pipe.set_stream_size_checked(Some(size)), Ok(()));
+        }
+        for size in [0, 512, 1000, 1536] {
+            assert_eq!(
+                pipe.set_stream_size_checked(Some(size)),
+                Err(D3xxError::InvalidArgs),
+                "size {size}"
+            );
+        }
+        assert_eq!(pipe.set_stream_size_checked(None), Ok(()));
+        let sizes: Vec<_> = api
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::SetStreamPipe { size, .. } => Some(size),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sizes, [Some(1024), Some(4096), None]);
+    }
+
+    #[test]
+    fn stream_guard_clears_on_drop() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let stream = device.pipe(Pipe::In2).begin_stream(2048).unwrap();
+        assert_eq!(stream.pipe().id(), Pipe::In2);
+        drop(stream);
+        let target = StreamPipeTarget::ThisPipe;
+        assert_eq!(
+            api.calls()[1..],
+            [
+                Call::SetStreamPipe {
+                    pipe: 0x84,
+                    target,
+                    size: Some(2048),
+                },
+                Call::AbortPipe { pipe: 0x84 },
+                Call::SetStreamPipe {
+                    pipe: 0x84,
+                    target,
+                    size: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_guard_end() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        assert!(device.pipe(Pipe::In0).begin_stream(100).is_err());
+        let stream = device.pipe(Pipe::In0).begin_stream(1024).unwrap();
+        api.fail_once_when(
+            |call| matches!(call, Call::AbortPipe { .. }),
+            D3xxError::IoError,
+        );
+        assert_eq!(stream.end(), Err(D3xxError::IoError));
+        // streaming is still disabled, and only once
+        let clears = api
+            .calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::SetStreamPipe { size: None, .. }))
+            .count();
+        assert_eq!(clears, 1);
+    }
+
+    #[test]
+    fn set_stream_size_for() {
+        let api = Arc::new(MockApi::default());
+        let device = mock::device(&api);
+        let pipe = device.pipe(Pipe::In0);
+        pipe.set_stream_size(Some(512)).unwrap();
+        pipe.set_stream_size_for(StreamPipeTarget::AllIn, None)
+            .unwrap();
+        assert_eq!(
+            api.calls(),
+            [
+                Call::SetStreamPipe {
+                    pipe: 0x82,
+                    target: StreamPipeTarget::ThisPipe,
+                    size: Some(512),
+                },
+                Call::SetStreamPipe {
+                    pipe: 0x82,
+                    target: StreamPipeTarget::AllIn,
+                    size: None,
+                },
+            ]
+        );
+    }
 
     #[test]
     fn pipeid_try_from() {
@@ -319,6 +2282,28 @@ mod tests {
         assert!(Pipe::try_from(0xFF).is_err());
     }
 
+    #[test]
+    fn pipe_all() {
+        let all = Pipe::all();
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[..4], Pipe::inputs());
+        assert_eq!(all[4..], Pipe::outputs());
+        for (i, a) in all.iter().enumerate() {
+            assert!(all[i + 1..].iter().all(|b| a != b));
+        }
+    }
+
+    #[test]
+    fn pipe_inputs_outputs() {
+        assert_eq!(Pipe::inputs(), [Pipe::In0, Pipe::In1, Pipe::In2, Pipe::In3]);
+        assert_eq!(
+            Pipe::outputs(),
+            [Pipe::Out0, Pipe::Out1, Pipe::Out2, Pipe::Out3]
+        );
+        assert!(Pipe::inputs().iter().all(|p| p.is_in()));
+        assert!(Pipe::outputs().iter().all(|p| p.is_out()));
+    }
+
     #[test]
     fn pipe_is_in() {
         assert!(Pipe::In0.is_in());
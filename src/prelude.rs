@@ -1,3 +1,6 @@
 //! Public prelude of the crate containing the most commonly used types and functions.
 #[allow(unused_imports)]
-pub use crate::{list_devices, D3xxError, Device, DeviceInfo, DeviceType, Pipe, PipeIo, Result};
+pub use crate::{
+    count_devices, device_info_detail, list_devices, D3xxError, Device, DeviceInfo, DeviceType,
+    OpenBy, Pipe, PipeIo, Result, SyncDevice,
+};
@@ -1,6 +1,13 @@
+use std::{panic::AssertUnwindSafe, sync::Arc};
+
 use crate::{
-    ffi::{self, with_global_lock},
-    try_d3xx, Device, Result,
+    descriptor::DeviceDescriptor,
+    ffi::{
+        self,
+        api::{instrument, D3xxApi, Ffi},
+        with_global_lock,
+    },
+    D3xxError, Device, DeviceKey, Result,
 };
 
 /// Information about a connected `FT60x` device.
@@ -78,6 +85,11 @@ impl DeviceInfo {
     }
 
     /// Get the device's location ID.
+    ///
+    /// The location ID identifies the physical USB port the device is connected to,
+    /// so unlike the serial number it cannot be reprogrammed. It is the only location
+    /// information reported by the driver; D3XX does not expose the device's location
+    /// path string on any platform.
     #[must_use]
     pub fn location_id(&self) -> u32 {
         self.location_id
@@ -89,12 +101,40 @@ impl DeviceInfo {
         &self.serial_number
     }
 
+    /// Get a key identifying the device, equal to [`Device::key`] once it is opened.
+    #[must_use]
+    pub fn key(&self) -> DeviceKey {
+        DeviceKey::new(&self.serial_number)
+    }
+
     /// Get the device's description.
     #[must_use]
     pub fn description(&self) -> &str {
         &self.description
     }
 
+    /// Re-read the information for this device from the driver.
+    ///
+    /// Fields such as [`is_open`](Self::is_open) describe the device at the time it was
+    /// listed and become stale when the device is opened or closed elsewhere. This
+    /// enumerates the connected devices again, like [`list_devices`], and returns the
+    /// updated information for the device with the same serial number.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`D3xxError::DeviceNotFound`] if the device is no longer connected.
+    pub fn refresh(&self) -> Result<DeviceInfo> {
+        self.refresh_with(&Ffi)
+    }
+
+    /// Re-read the information for this device using the given driver API.
+    fn refresh_with(&self, api: &dyn D3xxApi) -> Result<DeviceInfo> {
+        list_devices_with(api)?
+            .into_iter()
+            .find(|info| info.serial_number == self.serial_number)
+            .ok_or(D3xxError::DeviceNotFound)
+    }
+
     /// Get the device's handle.
     ///
     /// This is probably not useful to you.
@@ -104,6 +144,31 @@ impl DeviceInfo {
     }
 }
 
+impl DeviceInfo {
+    /// Build the information for an open device from its device descriptor.
+    ///
+    /// The descriptor does not identify the chip or its location, so the device
+    /// type is [`DeviceType::Unknown`] and the location ID is zero. The device is
+    /// marked as open, and its speed is derived from the USB version.
+    pub(crate) fn from_descriptor(descriptor: &DeviceDescriptor, handle: ffi::FT_HANDLE) -> Self {
+        let speed = if descriptor.usb_version().major() >= 3 {
+            ffi::FT_FLAGS::FT_FLAGS_SUPERSPEED
+        } else {
+            ffi::FT_FLAGS::FT_FLAGS_HISPEED
+        };
+        Self {
+            flags: ffi::FT_FLAGS::FT_FLAGS_OPENED as u32 | speed as u32,
+            device_type: DeviceType::Unknown,
+            vid: descriptor.inner().idVendor,
+            pid: descriptor.inner().idProduct,
+            location_id: 0,
+            serial_number: descriptor.serial_number().to_owned(),
+            description: descriptor.product().to_owned(),
+            handle,
+        }
+    }
+}
+
 impl From<ffi::FT_DEVICE_LIST_INFO_NODE> for DeviceInfo {
     fn from(info: ffi::FT_DEVICE_LIST_INFO_NODE) -> Self {
         Self::from(&info)
@@ -135,6 +200,7 @@ impl From<&ffi::FT_DEVICE_LIST_INFO_NODE> for DeviceInfo {
 /// Represents the type of `FT60x` device.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DeviceType {
+    /// Unrecognized device type.
     Unknown,
     /// `FT600` device.
     FT600,
@@ -142,6 +208,44 @@ pub enum DeviceType {
     FT601,
 }
 
+/// Part capabilities, according to the FT600/FT601 datasheet.
+///
+/// The two parts differ only in the width of their FIFO bus; both have four IN and four
+/// OUT endpoints, and support the 245 and multi-channel (600) FIFO modes. Nothing is
+/// assumed about an [`Unknown`](Self::Unknown) part.
+impl DeviceType {
+    /// Maximum number of channels, each being a pair of IN and OUT pipes.
+    #[must_use]
+    pub const fn max_channels(self) -> Option<u8> {
+        match self {
+            Self::FT600 | Self::FT601 => Some(4),
+            Self::Unknown => None,
+        }
+    }
+
+    /// Width of the FIFO bus in bits.
+    #[must_use]
+    pub const fn fifo_bus_width(self) -> Option<u8> {
+        match self {
+            Self::FT600 => Some(16),
+            Self::FT601 => Some(32),
+            Self::Unknown => None,
+        }
+    }
+
+    /// Check if the part supports the single-channel 245 FIFO mode.
+    #[must_use]
+    pub const fn supports_245_mode(self) -> bool {
+        matches!(self, Self::FT600 | Self::FT601)
+    }
+
+    /// Check if the part supports the multi-channel 600 FIFO mode.
+    #[must_use]
+    pub const fn supports_600_mode(self) -> bool {
+        matches!(self, Self::FT600 | Self::FT601)
+    }
+}
+
 impl From<u32> for DeviceType {
     fn from(value: u32) -> Self {
         match value {
@@ -181,8 +285,13 @@ impl From<u32> for DeviceType {
 ///     Ok(())
 /// }
 pub fn list_devices() -> Result<Vec<DeviceInfo>> {
-    let devices = with_global_lock(|| -> Result<_> {
-        // Theoretically we can have a mismatch between `buf_capacity` and the actual
+    list_devices_with(&*driver())
+}
+
+/// List the connected devices using the given driver API.
+pub(crate) fn list_devices_with(api: &dyn D3xxApi) -> Result<Vec<DeviceInfo>> {
+    let devices = with_global_lock(AssertUnwindSafe(|| -> Result<_> {
+        // Theoretically we can have a mismatch between the capacity and the actual
         // number of devices expected by `FT_GetDeviceInfoList`. It is very unlikely
         // because the only way it can happen is if both of the following happen:
         //
@@ -191,38 +300,83 @@ pub fn list_devices() -> Result<Vec<DeviceInfo>> {
         //
         // This should not happen in practice if the practice of acquiring the global lock
         // is adhered to.
-        let buf_capacity = create_device_info_list()?;
-        let mut table_len: ffi::DWORD = 0;
-        let mut devices: Vec<ffi::FT_DEVICE_LIST_INFO_NODE> = Vec::with_capacity(buf_capacity);
-        try_d3xx!(unsafe {
-            ffi::FT_GetDeviceInfoList(devices.as_mut_ptr(), std::ptr::addr_of_mut!(table_len))
-        })?;
-        let safe_len = std::cmp::min(buf_capacity, table_len as usize);
-        // SAFETY: the number of devices is less than or equal to the capacity
-        // the vector was created with.
-        unsafe { devices.set_len(safe_len) };
-
-        Ok(devices)
-    })?;
-
-    Ok(devices.into_iter().map(DeviceInfo::from).collect())
+        let capacity = api.create_device_info_list()?;
+        api.device_info_list(capacity)
+    }))?;
+    Ok(devices.iter().map(DeviceInfo::from).collect())
+}
+
+/// Count the connected `FT60x` devices.
+///
+/// This is a cheaper alternative to [`list_devices`] when only the number of devices
+/// is needed, as it does not read or allocate the device table.
+///
+/// This function acquires the [global lock](crate::ffi::with_global_lock), since it
+/// rebuilds the driver's internal device table.
+///
+/// # Example
+///
+/// ```no_run
+/// if d3xx::count_devices()? == 0 {
+///     println!("no devices connected");
+/// }
+/// # Ok::<(), d3xx::D3xxError>(())
+/// ```
+pub fn count_devices() -> Result<usize> {
+    count_devices_with(&*driver())
 }
 
-/// Create a device info list and return the number of devices.
+/// Count the connected devices using the given driver API.
+fn count_devices_with(api: &dyn D3xxApi) -> Result<usize> {
+    with_global_lock(AssertUnwindSafe(|| api.create_device_info_list())).map(|count| count as usize)
+}
+
+/// Get information about the connected device at `index`.
+///
+/// This reads a single entry of the driver's device table, producing the same
+/// [`DeviceInfo`] as the corresponding entry of [`list_devices`] without building
+/// the whole list. Indices range from zero up to the value returned by [`count_devices`].
+///
+/// This function acquires the [global lock](crate::ffi::with_global_lock), since it
+/// rebuilds the driver's internal device table.
+///
+/// # Errors
+///
+/// Returns [`D3xxError::NoMoreItems`](crate::D3xxError::NoMoreItems) if there is no
+/// device at `index`.
 ///
-/// This must be done at least once before calling `FT_GetDeviceInfoList`.
+/// # Example
 ///
-/// Note: the underlying device table does not automatically update; it
-/// must be refreshed when needed by calling this function again.
-fn create_device_info_list() -> Result<usize> {
-    let mut num_devices: ffi::DWORD = 0;
-    try_d3xx!(unsafe { ffi::FT_CreateDeviceInfoList(&mut num_devices) })?;
-    Ok(num_devices as usize)
+/// ```no_run
+/// let info = d3xx::device_info_detail(0)?;
+/// println!("first device: {}", info.serial_number());
+/// # Ok::<(), d3xx::D3xxError>(())
+/// ```
+pub fn device_info_detail(index: usize) -> Result<DeviceInfo> {
+    device_info_detail_with(&*driver(), index)
+}
+
+/// Get information about the device at `index` using the given driver API.
+fn device_info_detail_with(api: &dyn D3xxApi, index: usize) -> Result<DeviceInfo> {
+    with_global_lock(AssertUnwindSafe(|| {
+        let count = api.create_device_info_list()?;
+        let index = match u32::try_from(index) {
+            Ok(index) if index < count => index,
+            _ => return Err(D3xxError::NoMoreItems),
+        };
+        api.device_info_detail(index).map(DeviceInfo::from)
+    }))
+}
+
+/// The driver API used by the enumeration functions, logged like that of a [`Device`].
+fn driver() -> Arc<dyn D3xxApi> {
+    instrument(Arc::new(Ffi))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::{Call, MockApi};
 
     #[test]
     fn device_type_from() {
@@ -231,6 +385,24 @@ mod tests {
         assert_eq!(DeviceType::from(0), DeviceType::Unknown);
     }
 
+    #[test]
+    fn device_type_capabilities() {
+        assert_eq!(DeviceType::FT600.max_channels(), Some(4));
+        assert_eq!(DeviceType::FT600.fifo_bus_width(), Some(16));
+        assert!(DeviceType::FT600.supports_245_mode());
+        assert!(DeviceType::FT600.supports_600_mode());
+
+        assert_eq!(DeviceType::FT601.max_channels(), Some(4));
+        assert_eq!(DeviceType::FT601.fifo_bus_width(), Some(32));
+        assert!(DeviceType::FT601.supports_245_mode());
+        assert!(DeviceType::FT601.supports_600_mode());
+
+        assert_eq!(DeviceType::Unknown.max_channels(), None);
+        assert_eq!(DeviceType::Unknown.fifo_bus_width(), None);
+        assert!(!DeviceType::Unknown.supports_245_mode());
+        assert!(!DeviceType::Unknown.supports_600_mode());
+    }
+
     #[test]
     fn device_info_from() {
         fn array<const A: usize, const B: usize>(array: &[u8; A]) -> [i8; B] {
@@ -265,6 +437,153 @@ mod tests {
         assert_eq!(info.handle(), std::ptr::null_mut());
     }
 
+    #[test]
+    fn count_devices() {
+        let api = MockApi::default();
+        api.set_device_count(3);
+        assert_eq!(count_devices_with(&api), Ok(3));
+        assert_eq!(api.calls(), [Call::CreateDeviceInfoList]);
+
+        api.fail_once_when(|_| true, D3xxError::DeviceListNotReady);
+        assert_eq!(count_devices_with(&api), Err(D3xxError::DeviceListNotReady));
+    }
+
+    #[test]
+    fn device_info_detail() {
+        let api = MockApi::default();
+        api.set_device_count(2);
+        api.set_device_descriptor(ffi::FT_DEVICE_DESCRIPTOR {
+            idVendor: 0x0403,
+            idProduct: 0x601f,
+            iProduct: 2,
+            iSerialNumber: 3,
+            ..Default::default()
+        });
+        api.set_string(2, "FT601");
+        api.set_string(3, "ABC123");
+
+        let info = device_info_detail_with(&api, 1).unwrap();
+        assert_eq!(info.device_type(), DeviceType::FT601);
+        assert_eq!(info.vid(), 0x0403);
+        assert_eq!(info.pid(), 0x601f);
+        assert_eq!(info.location_id(), 1);
+        assert_eq!(info.serial_number(), "ABC123");
+        assert_eq!(info.description(), "FT601");
+        assert_eq!(
+            api.calls(),
+            [
+                Call::CreateDeviceInfoList,
+                Call::DeviceInfoDetail { index: 1 }
+            ]
+        );
+    }
+
+    #[test]
+    fn device_info_detail_out_of_range() {
+        let api = MockApi::default();
+        api.set_device_count(2);
+        assert_eq!(
+            device_info_detail_with(&api, 2),
+            Err(D3xxError::NoMoreItems)
+        );
+        assert_eq!(
+            device_info_detail_with(&api, usize::MAX),
+            Err(D3xxError::NoMoreItems)
+        );
+        // Out-of-range indices never reach the driver.
+        assert!(!api
+            .calls()
+            .iter()
+            .any(|call| matches!(call, Call::DeviceInfoDetail { .. })));
+    }
+
+    /// Mock API describing a single `FT601` with serial number `ABC123`.
+    fn mock_device() -> MockApi {
+        let api = MockApi::default();
+        api.set_device_count(1);
+        api.set_device_descriptor(ffi::FT_DEVICE_DESCRIPTOR {
+            iSerialNumber: 1,
+            ..Default::default()
+        });
+        api.set_string(1, "ABC123");
+        api
+    }
+
+    #[test]
+    fn list_devices() {
+        let api = mock_device();
+        api.set_device_count(2);
+        let devices = list_devices_with(&api).unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[1].location_id(), 1);
+        assert_eq!(devices[1].serial_number(), "ABC123");
+        assert_eq!(
+            api.calls(),
+            [Call::CreateDeviceInfoList, Call::DeviceInfoList]
+        );
+    }
+
+    #[test]
+    fn refresh() {
+        let api = mock_device();
+        let info = list_devices_with(&api).unwrap().remove(0);
+        assert!(!info.is_open());
+
+        api.set_device_flags(ffi::FT_FLAGS::FT_FLAGS_OPENED as u32);
+        let refreshed = info.refresh_with(&api).unwrap();
+        assert!(refreshed.is_open());
+        assert_eq!(refreshed.serial_number(), "ABC123");
+    }
+
+    #[test]
+    fn refresh_disconnected() {
+        let api = mock_device();
+        let info = list_devices_with(&api).unwrap().remove(0);
+
+        api.set_string(1, "DEF456");
+        assert_eq!(info.refresh_with(&api), Err(D3xxError::DeviceNotFound));
+        api.set_device_count(0);
+        assert_eq!(info.refresh_with(&api), Err(D3xxError::DeviceNotFound));
+    }
+
+    #[test]
+    fn device_info_from_descriptor() {
+        let raw = ffi::FT_DEVICE_DESCRIPTOR {
+            bcdUSB: 0x0310,
+            idVendor: 0x0403,
+            idProduct: 0x601f,
+            ..Default::default()
+        };
+        let descriptor = DeviceDescriptor::from_parts(
+            raw,
+            "ABC123".to_owned(),
+            "FTDI".to_owned(),
+            "FTDI SuperSpeed-FIFO Bridge".to_owned(),
+        );
+        let handle = std::ptr::NonNull::dangling().as_ptr();
+        let info = DeviceInfo::from_descriptor(&descriptor, handle);
+        assert!(info.is_open());
+        assert!(info.is_superspeed());
+        assert!(!info.is_hispeed());
+        assert_eq!(info.device_type(), DeviceType::Unknown);
+        assert_eq!(info.vid(), 0x0403);
+        assert_eq!(info.pid(), 0x601f);
+        assert_eq!(info.location_id(), 0);
+        assert_eq!(info.serial_number(), "ABC123");
+        assert_eq!(info.description(), "FTDI SuperSpeed-FIFO Bridge");
+        assert_eq!(info.handle(), handle);
+
+        let raw = ffi::FT_DEVICE_DESCRIPTOR {
+            bcdUSB: 0x0200,
+            ..raw
+        };
+        let descriptor =
+            DeviceDescriptor::from_parts(raw, String::new(), String::new(), String::new());
+        let info = DeviceInfo::from_descriptor(&descriptor, handle);
+        assert!(info.is_hispeed());
+        assert!(!info.is_superspeed());
+    }
+
     #[test]
     fn device_info_flags() {
         let mut raw_info = ffi::FT_DEVICE_LIST_INFO_NODE {
@@ -0,0 +1,582 @@
+use std::{
+    io::{Read, Write},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{
+    descriptor::{ConfigurationDescriptor, DeviceDescriptor, InterfaceDescriptor},
+    Device, Direction, GpioPin, Level, Pipe, PipeIo, Result,
+};
+
+/// A [`Device`] which can be shared between threads.
+///
+/// [`Device`] is `Send + !Sync`, so sharing it between threads requires a
+/// synchronization primitive. `SyncDevice` wraps the device in a [`Mutex`] which is
+/// acquired for the duration of each call, and is cheaply cloneable since clones
+/// share the same device.
+///
+/// Operations which are not provided directly may be performed using
+/// [`SyncDevice::with`] or [`SyncDevice::lock`].
+///
+/// # Example
+///
+/// ```no_run
+/// use std::thread;
+/// use d3xx::{Device, Pipe, SyncDevice};
+///
+/// let device = SyncDevice::new(Device::open("ABC123").unwrap());
+///
+/// let reader = device.clone();
+/// let handle = thread::spawn(move || {
+///     let mut buf = vec![0u8; 1024];
+///     reader.read(Pipe::In0, &mut buf).unwrap();
+/// });
+///
+/// device.write(Pipe::Out0, &[0u8; 1024]).unwrap();
+/// handle.join().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SyncDevice {
+    device: Arc<Mutex<Device>>,
+}
+
+impl SyncDevice {
+    /// Wrap the given device.
+    #[must_use]
+    pub fn new(device: Device) -> Self {
+        Self {
+            device: Arc::new(Mutex::new(device)),
+        }
+    }
+
+    /// Acquire exclusive access to the device.
+    ///
+    /// Other threads will block on calls to this `SyncDevice` (or its clones)
+    /// until the guard is dropped.
+    pub fn lock(&self) -> MutexGuard<'_, Device> {
+        // A panic while the lock is held does not leave the device in an
+        // inconsistent state, so poisoning is ignored.
+        self.device.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run the given closure with exclusive access to the device.
+    pub fn with<R>(&self, f: impl FnOnce(&Device) -> R) -> R {
+        f(&self.lock())
+    }
+
+    /// Read from the given pipe into `buf`.
+    ///
+    /// See [`PipeIo`](crate::PipeIo) for more information.
+    pub fn read(&self, pipe: Pipe, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.lock().pipe(pipe).read(buf)
+    }
+
+    /// Write `buf` to the given pipe.
+    ///
+    /// See [`PipeIo`](crate::PipeIo) for more information.
+    pub fn write(&self, pipe: Pipe, buf: &[u8]) -> std::io::Result<usize> {
+        self.lock().pipe(pipe).write(buf)
+    }
+
+    /// Configure the direction of a GPIO pin.
+    ///
+    /// See [`Gpio::enable`](crate::Gpio::enable).
+    pub fn gpio_enable(&self, pin: GpioPin, direction: Direction) -> Result<()> {
+        self.lock().gpio(pin).enable(direction)
+    }
+
+    /// Read the level of a GPIO pin.
+    ///
+    /// See [`Gpio::read`](crate::Gpio::read).
+    pub fn gpio_read(&self, pin: GpioPin) -> Result<Level> {
+        self.lock().gpio(pin).read()
+    }
+
+    /// Write the level of a GPIO pin.
+    ///
+    /// See [`Gpio::write`](crate::Gpio::write).
+    pub fn gpio_write(&self, pin: GpioPin, level: Level) -> Result<()> {
+        self.lock().gpio(pin).write(level)
+    }
+
+    /// Get the USB device descriptor.
+    ///
+    /// See [`Device::device_descriptor`].
+    pub fn device_descriptor(&self) -> Result<DeviceDescriptor> {
+        self.lock().device_descriptor()
+    }
+
+    /// Get the configuration descriptor.
+    ///
+    /// See [`Device::configuration_descriptor`].
+    pub fn configuration_descriptor(&self) -> Result<ConfigurationDescriptor> {
+        self.lock().configuration_descriptor()
+    }
+
+    /// Get the interface descriptor for the given interface.
+    ///
+    /// See [`Device::interface_descriptor`].
+    pub fn interface_descriptor(&self, interface: u8) -> Result<InterfaceDescriptor> {
+        self.lock().interface_descriptor(interface)
+    }
+
+    /// Create an owned reader for the given input pipe.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pipe` is not an input pipe.
+    #[must_use]
+    pub fn reader(&self, pipe: Pipe) -> PipeReader {
+        PipeReader::new(self.clone(), pipe)
+    }
+
+    /// Create an owned writer for the given output pipe.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pipe` is not an output pipe.
+    #[must_use]
+    pub fn writer(&self, pipe: Pipe) -> PipeWriter {
+        PipeWriter::new(self.clone(), pipe)
+    }
+
+    /// Create an owned handle for the given pipe, which may be moved to other threads
+    /// and tasks.
+    ///
+    /// See [`OwnedPipe`].
+    #[must_use]
+    pub fn owned_pipe(&self, pipe: Pipe) -> OwnedPipe {
+        OwnedPipe::new(self.clone(), pipe)
+    }
+
+    /// Borrow the given pipe for use on a scoped thread.
+    ///
+    /// See [`ScopedPipe`].
+    #[must_use]
+    pub fn scoped_pipe(&self, pipe: Pipe) -> ScopedPipe<'_> {
+        ScopedPipe { device: self, pipe }
+    }
+
+    /// Unwrap the device if this is the only remaining reference to it.
+    ///
+    /// Otherwise, `self` is returned unchanged.
+    pub fn try_into_inner(self) -> std::result::Result<Device, Self> {
+        Arc::try_unwrap(self.device)
+            .map(|mutex| mutex.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .map_err(|device| Self { device })
+    }
+}
+
+impl From<Device> for SyncDevice {
+    fn from(device: Device) -> Self {
+        Self::new(device)
+    }
+}
+
+/// Owned reader for an input pipe.
+///
+/// Unlike [`PipeIo`](crate::PipeIo), a `PipeReader` keeps its device alive and is not
+/// tied to a borrow of it, so it may be stored alongside other state or moved to
+/// another thread.
+///
+/// Each read acquires the device lock for its duration. Since the driver is not assumed
+/// to be thread-safe, a blocking read delays other operations on the same device until
+/// it completes or the [pipe timeout](crate::PipeIo::set_timeout) expires.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::{io::{Read, Write}, thread};
+/// use d3xx::{Device, Pipe, SyncDevice};
+///
+/// let device = SyncDevice::new(Device::open("ABC123").unwrap());
+/// let mut reader = device.reader(Pipe::In0);
+/// let mut writer = device.writer(Pipe::Out0);
+///
+/// let handle = thread::spawn(move || {
+///     let mut buf = vec![0u8; 1024];
+///     reader.read(&mut buf).unwrap();
+/// });
+/// writer.write_all(&[0u8; 1024]).unwrap();
+/// handle.join().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct PipeReader {
+    device: SyncDevice,
+    pipe: Pipe,
+}
+
+impl PipeReader {
+    /// Create a reader for the given input pipe.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pipe` is not an input pipe.
+    #[must_use]
+    pub fn new(device: SyncDevice, pipe: Pipe) -> Self {
+        assert!(pipe.is_in(), "{pipe:?} is not an input pipe");
+        Self { device, pipe }
+    }
+
+    /// Get the pipe ID.
+    #[must_use]
+    pub fn id(&self) -> Pipe {
+        self.pipe
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.device.read(self.pipe, buf)
+    }
+}
+
+/// Owned writer for an output pipe.
+///
+/// This is the output counterpart to [`PipeReader`]; see its documentation for details.
+#[derive(Clone, Debug)]
+pub struct PipeWriter {
+    device: SyncDevice,
+    pipe: Pipe,
+}
+
+impl PipeWriter {
+    /// Create a writer for the given output pipe.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pipe` is not an output pipe.
+    #[must_use]
+    pub fn new(device: SyncDevice, pipe: Pipe) -> Self {
+        assert!(pipe.is_out(), "{pipe:?} is not an output pipe");
+        Self { device, pipe }
+    }
+
+    /// Get the pipe ID.
+    #[must_use]
+    pub fn id(&self) -> Pipe {
+        self.pipe
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.device.write(self.pipe, buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.device.lock().pipe(self.pipe).flush()
+    }
+}
+
+/// A pipe borrowed from a [`SyncDevice`], which can be moved into a scoped thread.
+///
+/// [`PipeIo`](crate::PipeIo) borrows a [`Device`], which is `!Sync`, so it cannot be sent
+/// to another thread. A `ScopedPipe` borrows a `SyncDevice` instead, making it `Send`
+/// while still being tied to the lifetime of the borrow. This pairs with
+/// [`std::thread::scope`] to read and write on separate threads without cloning the
+/// device, as the borrow checker ensures the threads finish before the device is dropped.
+///
+/// Like [`PipeReader`] and [`PipeWriter`], each transfer acquires the device lock for its
+/// duration.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::{io::{Read, Write}, thread};
+/// use d3xx::{Device, Pipe, SyncDevice};
+///
+/// let device = SyncDevice::new(Device::open("ABC123").unwrap());
+/// let mut reader = device.scoped_pipe(Pipe::In0);
+/// let mut writer = device.scoped_pipe(Pipe::Out0);
+///
+/// thread::scope(|s| {
+///     s.spawn(move || {
+///         let mut buf = vec![0u8; 1024];
+///         reader.read(&mut buf).unwrap();
+///     });
+///     s.spawn(move || writer.write_all(&[0u8; 1024]).unwrap());
+/// });
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ScopedPipe<'a> {
+    device: &'a SyncDevice,
+    pipe: Pipe,
+}
+
+impl ScopedPipe<'_> {
+    /// Get the pipe ID.
+    #[must_use]
+    pub fn id(&self) -> Pipe {
+        self.pipe
+    }
+
+    /// Run the given closure with exclusive access to the pipe.
+    ///
+    /// This gives access to the methods of [`PipeIo`](crate::PipeIo) which are not
+    /// available through [`Read`] and [`Write`].
+    pub fn with<R>(&self, f: impl FnOnce(PipeIo<'_>) -> R) -> R {
+        f(self.device.lock().pipe(self.pipe))
+    }
+}
+
+impl Read for ScopedPipe<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.device.read(self.pipe, buf)
+    }
+}
+
+impl Write for ScopedPipe<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.device.write(self.pipe, buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.device.lock().pipe(self.pipe).flush()
+    }
+}
+
+/// A pipe which shares ownership of a [`SyncDevice`].
+///
+/// This is the owned counterpart to [`ScopedPipe`]: it is not tied to a borrow of the
+/// device, so it may be stored alongside other state or moved into a thread or task. The
+/// device stays open until every `OwnedPipe` and other clone of the `SyncDevice` has been
+/// dropped. Unlike [`PipeReader`] and [`PipeWriter`], it may be used in either direction,
+/// and every [`PipeIo`](crate::PipeIo) operation is available through
+/// [`with`](Self::with).
+///
+/// Each operation is performed through a [`ScopedPipe`] borrowed for its duration, and
+/// so acquires the device lock in the same way.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::{io::Write, thread};
+/// use d3xx::{Device, Pipe, SyncDevice};
+///
+/// let device = SyncDevice::new(Device::open("ABC123").unwrap());
+/// let mut pipe = device.owned_pipe(Pipe::Out0);
+/// drop(device);
+/// thread::spawn(move || pipe.write_all(&[0u8; 1024]).unwrap())
+///     .join()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct OwnedPipe {
+    device: SyncDevice,
+    pipe: Pipe,
+}
+
+impl OwnedPipe {
+    /// Create a new `OwnedPipe` for the given device and pipe ID.
+    #[must_use]
+    pub fn new(device: SyncDevice, pipe: Pipe) -> Self {
+        Self { device, pipe }
+    }
+
+    /// Get the pipe ID.
+    #[must_use]
+    pub fn id(&self) -> Pipe {
+        self.pipe
+    }
+
+    /// Get the device the pipe belongs to.
+    #[must_use]
+    pub fn device(&self) -> &SyncDevice {
+        &self.device
+    }
+
+    /// Borrow the pipe as a [`ScopedPipe`].
+    #[must_use]
+    pub fn scoped(&self) -> ScopedPipe<'_> {
+        self.device.scoped_pipe(self.pipe)
+    }
+
+    /// Run the given closure with exclusive access to the pipe.
+    ///
+    /// See [`ScopedPipe::with`].
+    pub fn with<R>(&self, f: impl FnOnce(PipeIo<'_>) -> R) -> R {
+        self.scoped().with(f)
+    }
+}
+
+impl Read for OwnedPipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.scoped().read(buf)
+    }
+}
+
+impl Write for OwnedPipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.scoped().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.scoped().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::mock::{self, Call, MockApi};
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SyncDevice>();
+    }
+
+    #[test]
+    fn calls_serialize() {
+        let api = Arc::new(MockApi::default());
+        let device = SyncDevice::new(mock::device(&api));
+        let active = AtomicUsize::new(0);
+        let max_active = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                let device = device.clone();
+                let (active, max_active) = (&active, &max_active);
+                s.spawn(move || {
+                    for _ in 0..5 {
+                        device.with(|_| {
+                            let n = active.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_active.fetch_max(n, Ordering::SeqCst);
+                            thread::sleep(Duration::from_millis(1));
+                            active.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    }
+                });
+            }
+        });
+        assert_eq!(max_active.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn gpio() {
+        let api = Arc::new(MockApi::default());
+        let device = SyncDevice::new(mock::device(&api));
+        device.gpio_write(GpioPin::Pin1, Level::High).unwrap();
+        assert_eq!(device.gpio_read(GpioPin::Pin1).unwrap(), Level::High);
+        assert_eq!(device.gpio_read(GpioPin::Pin0).unwrap(), Level::Low);
+    }
+
+    #[test]
+    fn reader_writer_threads() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"hello");
+        let device = SyncDevice::new(mock::device(&api));
+        let mut reader = device.reader(Pipe::In0);
+        let mut writer = device.writer(Pipe::Out0);
+        drop(device);
+
+        let reader = thread::spawn(move || {
+            let mut buf = [0u8; 16];
+            let n = reader.read(&mut buf).unwrap();
+            buf[..n].to_vec()
+        });
+        let writer = thread::spawn(move || writer.write_all(b"world").unwrap());
+
+        assert_eq!(reader.join().unwrap(), b"hello");
+        writer.join().unwrap();
+        assert_eq!(api.writes(0x02), [b"world".to_vec()]);
+    }
+
+    #[test]
+    fn scoped_pipe_threads() {
+        fn assert_send<T: Send>() {}
+        assert_send::<ScopedPipe<'_>>();
+
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"hello");
+        let device = SyncDevice::new(mock::device(&api));
+        let mut reader = device.scoped_pipe(Pipe::In0);
+        let mut writer = device.scoped_pipe(Pipe::Out0);
+
+        let read = thread::scope(|s| {
+            let reader = s.spawn(move || {
+                let mut buf = [0u8; 16];
+                let n = reader.read(&mut buf).unwrap();
+                buf[..n].to_vec()
+            });
+            s.spawn(move || writer.write_all(b"world").unwrap());
+            reader.join().unwrap()
+        });
+        assert_eq!(read, b"hello");
+        assert_eq!(api.writes(0x02), [b"world".to_vec()]);
+        assert_eq!(
+            device.scoped_pipe(Pipe::Out0).with(|pipe| pipe.id()),
+            Pipe::Out0
+        );
+    }
+
+    #[test]
+    fn owned_pipe_keeps_device_alive() {
+        fn assert_send<T: Send>() {}
+        assert_send::<OwnedPipe>();
+
+        let api = Arc::new(MockApi::default());
+        let device = SyncDevice::new(mock::device(&api));
+        let mut pipe = device.owned_pipe(Pipe::Out0);
+        drop(device);
+        assert!(!api.calls().contains(&Call::Close));
+
+        let clone = pipe.clone();
+        thread::spawn(move || pipe.write_all(b"data").unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(api.writes(0x02), [b"data".to_vec()]);
+        assert!(!api.calls().contains(&Call::Close));
+        drop(clone);
+        assert_eq!(api.calls().last(), Some(&Call::Close));
+    }
+
+    #[test]
+    fn owned_pipe_read() {
+        let api = Arc::new(MockApi::default());
+        api.push_read(0x82, b"hello");
+        let device = SyncDevice::new(mock::device(&api));
+        let mut pipe = device.owned_pipe(Pipe::In0);
+        assert_eq!(pipe.id(), Pipe::In0);
+        assert_eq!(pipe.with(|pipe| pipe.id()), Pipe::In0);
+
+        let mut buf = [0; 8];
+        assert_eq!(pipe.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+    }
+
+    #[test]
+    fn owned_pipe_write() {
+        let api = Arc::new(MockApi::default());
+        let device = SyncDevice::new(mock::device(&api));
+        let mut pipe = device.owned_pipe(Pipe::Out1);
+        assert_eq!(pipe.scoped().id(), Pipe::Out1);
+        pipe.write_all(b"hello").unwrap();
+        pipe.flush().unwrap();
+        assert_eq!(api.writes(0x03), [b"hello"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an input pipe")]
+    fn reader_rejects_output_pipe() {
+        let api = Arc::new(MockApi::default());
+        let _ = SyncDevice::new(mock::device(&api)).reader(Pipe::Out0);
+    }
+
+    #[test]
+    fn try_into_inner() {
+        let api = Arc::new(MockApi::default());
+        let device = SyncDevice::new(mock::device(&api));
+        let clone = device.clone();
+        let device = device.try_into_inner().unwrap_err();
+        drop(clone);
+        assert!(device.try_into_inner().is_ok());
+    }
+}
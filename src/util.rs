@@ -1,12 +1,49 @@
 //! Internal utility types and functions.
 
-use std::marker::PhantomData;
-
-/// Used to force a lifetime constraint on a type which does not contain any references.
-///
-/// This is important for ensuring that lifetimes are correctly enforced by the type
-/// system, which otherwise would not catch use-after-free errors in this crate.
-pub type PhantomLifetime<'a, T = ()> = PhantomData<&'a T>;
+use std::{marker::PhantomData, time::Duration};
 
 /// Used to force a type to be `!Sync`.
 pub type PhantomUnsync = PhantomData<std::cell::Cell<()>>;
+
+/// Duration of a USB (micro)frame at high speed and above.
+const MICROFRAME: Duration = Duration::from_micros(125);
+
+/// Convert an interval exponent into a duration of `2**(exponent-1)` microframes.
+///
+/// This is how USB high speed and super speed devices encode polling intervals.
+/// The exponent is clamped to the valid range of `1..=16`.
+pub(crate) fn microframe_interval(exponent: u8) -> Duration {
+    MICROFRAME * (1 << (exponent.clamp(1, 16) - 1))
+}
+
+/// Convert a duration into a pipe timeout in milliseconds.
+///
+/// The duration is rounded up to a whole millisecond, so that short timeouts do not
+/// become zero, and saturates at `u32::MAX`. Zero stays zero.
+pub(crate) fn timeout_millis(timeout: Duration) -> u32 {
+    let millis = (timeout.as_nanos() + 999_999) / 1_000_000;
+    u32::try_from(millis).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_millis_rounding() {
+        assert_eq!(timeout_millis(Duration::ZERO), 0);
+        assert_eq!(timeout_millis(Duration::from_nanos(1)), 1);
+        assert_eq!(timeout_millis(Duration::from_micros(1500)), 2);
+        assert_eq!(timeout_millis(Duration::from_secs(2)), 2000);
+        assert_eq!(timeout_millis(Duration::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn microframe_interval_values() {
+        assert_eq!(microframe_interval(1), Duration::from_micros(125));
+        assert_eq!(microframe_interval(4), Duration::from_millis(1));
+        assert_eq!(microframe_interval(16), Duration::from_millis(4096));
+        assert_eq!(microframe_interval(0), microframe_interval(1));
+        assert_eq!(microframe_interval(200), microframe_interval(16));
+    }
+}